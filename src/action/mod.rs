@@ -1,3 +1,5 @@
 pub mod config_generate;
 pub mod config_test;
+#[cfg(feature = "rcon")]
+pub mod rcon;
 pub mod start;
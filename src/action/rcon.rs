@@ -0,0 +1,89 @@
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+
+use clap::ArgMatches;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::config::{self, Config};
+use crate::mc::rcon::Rcon;
+use crate::mc::server_properties;
+use crate::util::error::{quit_error_msg, ErrorHintsBuilder};
+
+/// Invoke RCON command.
+///
+/// Invokes the given command, or opens an interactive prompt if none is given.
+pub async fn invoke(matches: &ArgMatches) -> Result<(), ()> {
+    let config = config::load(matches);
+
+    let mut addr = config.server.address;
+    addr.set_port(config.rcon.port);
+    let connect_timeout = Duration::from_secs(config.rcon.connect_timeout as u64);
+    let timeout = Duration::from_secs(config.rcon.command_timeout as u64);
+    let password = password(&config);
+    let rcon = Rcon::connect(&addr.to_string(), &password, connect_timeout, timeout).await;
+    let mut rcon = match rcon {
+        Ok(rcon) => rcon,
+        Err(err) => quit_error_msg(
+            format!("Failed to connect to server RCON at {}: {}", addr, err),
+            ErrorHintsBuilder::default()
+                .add_info("make sure the server is running with RCON enabled".into())
+                .build()
+                .unwrap(),
+        ),
+    };
+
+    // Invoke single command
+    if let Some(cmd) = matches.values_of("COMMAND") {
+        let cmd = cmd.collect::<Vec<_>>().join(" ");
+        return run(&mut rcon, &cmd).await;
+    }
+
+    // Interactive prompt, until end of input
+    let prompt = std::io::stdin().is_terminal();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        if prompt {
+            print!("> ");
+            let _ = std::io::stdout().flush();
+        }
+
+        let cmd = match lines.next_line().await {
+            Ok(Some(cmd)) => cmd,
+            Ok(None) | Err(_) => break,
+        };
+        let cmd = cmd.trim();
+        if !cmd.is_empty() {
+            run(&mut rcon, cmd).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Invoke a single command, print its response.
+async fn run(rcon: &mut Rcon, cmd: &str) -> Result<(), ()> {
+    match rcon.cmd(cmd).await {
+        Ok(response) => {
+            if !response.is_empty() {
+                println!("{}", response);
+            }
+            Ok(())
+        }
+        Err(err) => {
+            eprintln!("Failed to invoke command through RCON: {}", err);
+            Err(())
+        }
+    }
+}
+
+/// Get RCON password.
+///
+/// Randomized passwords are only stored in the server.properties file.
+fn password(config: &Config) -> String {
+    if !config.rcon.randomize_password {
+        return config.rcon.password.clone();
+    }
+    server_properties::load(config.server.dir())
+        .remove("rcon.password")
+        .unwrap_or_default()
+}
@@ -1,11 +1,14 @@
 use std::collections::HashMap;
+use std::net::TcpListener;
 use std::sync::Arc;
 
 use clap::ArgMatches;
 
-use crate::config::{self, Config};
+use crate::config::{self, Backend, Config, Method, ReadySignal, ReadyWhen};
+use crate::lobby;
 use crate::mc::server_properties;
 use crate::service;
+use crate::util::error::{quit_error, quit_error_msg, ErrorHints, ErrorHintsBuilder};
 
 /// RCON randomized password length.
 #[cfg(feature = "rcon")]
@@ -14,13 +17,145 @@ const RCON_PASSWORD_LENGTH: usize = 32;
 /// Start lazymc.
 pub async fn invoke(matches: &ArgMatches) -> Result<(), ()> {
     // Load config
-    #[allow(unused_mut)]
     let mut config = config::load(matches);
 
+    // Server command is required to run server as process
+    if config.server.backend == Backend::Process && config.server.command.is_none() {
+        quit_error_msg(
+            "Server command must be configured",
+            ErrorHintsBuilder::default()
+                .add_info("set 'server.command' in the config file".into())
+                .build()
+                .unwrap(),
+        );
+    }
+
+    // Systemd unit is required to run server as systemd unit
+    if config.server.backend == Backend::Systemd && config.server.systemd_unit.is_none() {
+        quit_error_msg(
+            "Systemd unit must be configured",
+            ErrorHintsBuilder::default()
+                .add_info("set 'server.systemd_unit' in the config file".into())
+                .build()
+                .unwrap(),
+        );
+    }
+
+    // MAC address is required to wake server machine
+    if config.server.backend == Backend::Wol && config.wol.mac.is_none() {
+        quit_error_msg(
+            "MAC address must be configured for Wake-on-LAN",
+            ErrorHintsBuilder::default()
+                .add_info("set 'wol.mac' in the config file".into())
+                .build()
+                .unwrap(),
+        );
+    }
+
+    // Panel and server are required to manage server through Pterodactyl
+    let pterodactyl = &config.pterodactyl;
+    if config.server.backend == Backend::Pterodactyl
+        && (pterodactyl.url.is_empty() || pterodactyl.server.is_empty())
+    {
+        quit_error_msg(
+            "Pterodactyl panel URL and server must be configured",
+            ErrorHintsBuilder::default()
+                .add_info(
+                    "set 'pterodactyl.url' and 'pterodactyl.server' in the config file".into(),
+                )
+                .build()
+                .unwrap(),
+        );
+    }
+
+    // Readiness signals must be able to mark the server online
+    let monitor = &config.monitor;
+    if monitor.ready_signals.is_empty() {
+        quit_error_msg(
+            "At least one readiness signal must be configured",
+            ErrorHintsBuilder::default()
+                .add_info("set 'monitor.ready_signals' in the config file".into())
+                .build()
+                .unwrap(),
+        );
+    }
+    if monitor.ready_signals.contains(&ReadySignal::Rcon)
+        && (!cfg!(feature = "rcon") || !config.rcon.enabled)
+    {
+        quit_error_msg(
+            "RCON must be enabled to use it as readiness signal",
+            ErrorHintsBuilder::default()
+                .add_info("change 'rcon.enabled' to 'true' in the config file".into())
+                .add_info("or remove 'rcon' from 'monitor.ready_signals' in the config file".into())
+                .build()
+                .unwrap(),
+        );
+    }
+    if monitor.ready_when == ReadyWhen::All
+        && monitor.ready_signals.contains(&ReadySignal::Log)
+        && matches!(
+            config.server.backend,
+            Backend::Systemd | Backend::Ssh | Backend::Wol
+        )
+    {
+        quit_error_msg(
+            "Server backend doesn't report the log readiness signal",
+            ErrorHintsBuilder::default()
+                .add_info("remove 'log' from 'monitor.ready_signals' in the config file".into())
+                .add_info("or change 'monitor.ready_when' to 'any' in the config file".into())
+                .build()
+                .unwrap(),
+        );
+    }
+
+    // Local address to connect to the server from must be of the same IP version
+    if let Some(bind) = config.server.bind_address {
+        if bind.is_ipv4() != config.server.address.is_ipv4() {
+            quit_error_msg(
+                "Bind address must be of the same IP version as the server address",
+                ErrorHintsBuilder::default()
+                    .add_info("change 'server.bind_address' in the config file".into())
+                    .build()
+                    .unwrap(),
+            );
+        }
+    }
+    if config.server.bind_interface.is_some() && !cfg!(target_os = "linux") {
+        quit_error_msg(
+            "Binding to a network interface is only supported on Linux",
+            ErrorHintsBuilder::default()
+                .add_info("remove 'server.bind_interface' from the config file".into())
+                .build()
+                .unwrap(),
+        );
+    }
+
+    if config.socket.reuse_port && !cfg!(unix) {
+        quit_error_msg(
+            "Reusing the public port is only supported on Unix",
+            ErrorHintsBuilder::default()
+                .add_info("remove 'socket.reuse_port' from the config file".into())
+                .build()
+                .unwrap(),
+        );
+    }
+
+    // Lobby transfer requires an offline-mode server
+    if config.join.methods.contains(&Method::Lobby)
+        && server_properties::load(config.server.dir())
+            .get("online-mode")
+            .is_some_and(|online_mode| online_mode == "true")
+    {
+        warn!(target: "lazymc", "Server has online-mode enabled in {}, lobby join method requires it to be disabled", server_properties::FILE);
+    }
+
     // Prepare RCON if enabled
     #[cfg(feature = "rcon")]
     prepare_rcon(&mut config);
 
+    // Move server to internal port if enabled
+    prepare_port(&mut config);
+
     // Rewrite server server.properties file
     rewrite_server_properties(&config);
 
@@ -30,11 +165,48 @@ pub async fn invoke(matches: &ArgMatches) -> Result<(), ()> {
     service::server::service(config).await
 }
 
+/// Move server to a free internal port, so it never conflicts with the public port.
+fn prepare_port(config: &mut Config) {
+    // Skip if not enabled
+    if !config.server.auto_port {
+        return;
+    }
+
+    // Must enable server.properties rewrite
+    if !config.advanced.rewrite_server_properties {
+        quit_error_msg(
+            format!(
+                "You must enable {} rewrite to move the server to an internal port",
+                server_properties::FILE
+            ),
+            ErrorHintsBuilder::default()
+                .add_info(
+                    "change 'advanced.rewrite_server_properties' to 'true' in the config file"
+                        .into(),
+                )
+                .build()
+                .unwrap(),
+        );
+    }
+
+    // Let the OS pick a free port
+    let port = match TcpListener::bind((config.server.address.ip(), 0))
+        .and_then(|listener| listener.local_addr())
+    {
+        Ok(addr) => addr.port(),
+        Err(err) => quit_error(
+            anyhow!(err).context("Failed to find free internal port for server"),
+            ErrorHints::default(),
+        ),
+    };
+
+    info!(target: "lazymc", "Moving server to internal port {}", port);
+    config.server.address.set_port(port);
+}
+
 /// Prepare RCON.
 #[cfg(feature = "rcon")]
 fn prepare_rcon(config: &mut Config) {
-    use crate::util::error::{quit_error_msg, ErrorHintsBuilder};
-
     // On Windows, this must be enabled
     if cfg!(windows) && !config.rcon.enabled {
         quit_error_msg(
@@ -129,13 +301,20 @@ fn rewrite_server_properties(config: &Config) {
     };
 
     // Build list of changes
-    #[allow(unused_mut)]
     let mut changes = HashMap::from([
         ("server-ip", config.server.address.ip().to_string()),
         ("server-port", config.server.address.port().to_string()),
         ("query.port", config.server.address.port().to_string()),
     ]);
 
+    // Lobby transfer requires server compression to match lobby
+    if config.join.methods.contains(&Method::Lobby) {
+        changes.insert(
+            "network-compression-threshold",
+            lobby::COMPRESSION_THRESHOLD.to_string(),
+        );
+    }
+
     // Add RCON configuration
     #[cfg(feature = "rcon")]
     if config.rcon.enabled {
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::Config;
+use crate::favicon;
+use crate::server::{self, ServerState};
+use crate::status;
+
+/// Maximum length of a command line.
+const MAX_LINE_LEN: u64 = 1024;
+
+/// Time after which idle admin clients are disconnected.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Serve admin control interface.
+pub async fn serve(config: Arc<Config>, server: Arc<ServerState>) -> Result<(), ()> {
+    let listener = TcpListener::bind(config.admin.address)
+        .await
+        .map_err(|err| {
+            error!(target: "lazymc::admin", "Failed to bind admin socket: {}", err);
+        })?;
+
+    info!(
+        target: "lazymc",
+        "Serving admin control on {}",
+        config.admin.address,
+    );
+
+    while let Ok((inbound, peer)) = listener.accept().await {
+        debug!(target: "lazymc::admin", "Admin client connected from {}", peer);
+
+        let config = config.clone();
+        let server = server.clone();
+        tokio::spawn(async move {
+            let _ = serve_client(config, server, inbound).await;
+        });
+    }
+
+    Ok(())
+}
+
+/// Serve a single admin client, one command per line.
+async fn serve_client(
+    config: Arc<Config>,
+    server: Arc<ServerState>,
+    mut stream: TcpStream,
+) -> Result<(), ()> {
+    let (reader, mut writer) = stream.split();
+    let mut reader = BufReader::new(reader);
+    let mut authenticated = config.admin.password.is_empty();
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let mut limited = (&mut reader).take(MAX_LINE_LEN);
+        let read = limited.read_until(b'\n', &mut line);
+        match tokio::time::timeout(IDLE_TIMEOUT, read).await {
+            Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break,
+            Ok(Ok(_)) => {}
+        }
+        let line = String::from_utf8_lossy(&line);
+        let (cmd, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        if cmd.is_empty() {
+            continue;
+        }
+
+        let response = match cmd {
+            "auth" => {
+                authenticated = arg == config.admin.password;
+                if authenticated {
+                    "ok".into()
+                } else {
+                    "error invalid password".into()
+                }
+            }
+            _ if !authenticated => "error not authenticated".into(),
+            _ => command(&config, &server, cmd).await,
+        };
+
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .map_err(|_| ())?;
+    }
+
+    Ok(())
+}
+
+/// Invoke an admin command, returns the response line.
+async fn command(config: &Arc<Config>, server: &Arc<ServerState>, cmd: &str) -> String {
+    match cmd {
+        "wake" => {
+            if server.online() {
+                return "ok already online".into();
+            }
+            info!(target: "lazymc::admin", "Waking server as requested through admin control");
+            server::start_server(config.clone(), server.clone());
+            "ok".into()
+        }
+        "sleep" => {
+            if !server.online() && !server.starting() {
+                return "ok already sleeping".into();
+            }
+            info!(target: "lazymc::admin", "Sleeping server as requested through admin control");
+            if server.sleep_server(config).await {
+                "ok".into()
+            } else {
+                "error failed to sleep server".into()
+            }
+        }
+        "status" => {
+            let players = server
+                .clone_status()
+                .filter(|_| server.online())
+                .map(|status| status.players.online)
+                .unwrap_or(0);
+            let start_estimate = server
+                .start_estimate()
+                .map(|estimate| estimate.as_secs().to_string())
+                .unwrap_or_else(|| "unknown".into());
+            format!(
+                "ok state={} players={} wakes={} wakes_today={} start_estimate={}",
+                state(server),
+                players,
+                server.wake_count(),
+                server.wakes_today(),
+                start_estimate,
+            )
+        }
+        "reload" => {
+            info!(target: "lazymc::admin", "Reloading favicons and JSON status");
+            favicon::load(config);
+            status::load(config);
+            server.status_cache.clear();
+            "ok".into()
+        }
+        _ => format!("error unknown command: {}", cmd),
+    }
+}
+
+/// Get name of current server state.
+fn state(server: &ServerState) -> &'static str {
+    if server.frozen() {
+        "frozen"
+    } else if server.online() {
+        "online"
+    } else if server.starting() {
+        "starting"
+    } else {
+        "sleeping"
+    }
+}
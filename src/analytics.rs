@@ -0,0 +1,275 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::os;
+use crate::proto::Client;
+use crate::server::ServerState;
+
+/// Maximum number of pings to keep in memory.
+const MAX_PINGS: usize = 10_000;
+
+/// Time to keep pings in memory for.
+const PING_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Number of top hostnames to show in summary.
+const SUMMARY_HOSTNAMES: usize = 5;
+
+/// Recorded server list ping.
+#[derive(Debug, Clone, Serialize)]
+pub struct Ping {
+    /// Time of ping, as UNIX timestamp in seconds.
+    pub time: u64,
+
+    /// Source IP.
+    pub ip: Option<IpAddr>,
+
+    /// Client protocol version.
+    pub protocol: Option<i32>,
+
+    /// Hostname the client connected with.
+    pub hostname: Option<String>,
+}
+
+/// Rolling log of server list pings.
+#[derive(Debug, Default)]
+pub struct PingLog {
+    /// Recent pings, oldest first.
+    pings: Mutex<VecDeque<Ping>>,
+}
+
+impl PingLog {
+    /// Record status ping from client.
+    ///
+    /// Appends the ping to the configured log file as JSON line.
+    pub fn record(&self, config: &Config, client: &Client) {
+        let ping = Ping {
+            time: now(),
+            ip: client.peer.map(|peer| peer.ip()),
+            protocol: client.protocol(),
+            hostname: client.hostname(),
+        };
+
+        if let Some(path) = &config.analytics.file {
+            let result = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| {
+                    let line = serde_json::to_string(&ping)?;
+                    writeln!(file, "{}", line)
+                });
+            if let Err(err) = result {
+                warn!(target: "lazymc", "Failed to write ping to analytics file: {}", err);
+            }
+        }
+
+        let mut pings = self.pings.lock().unwrap();
+        pings.push_back(ping);
+        let retain_after = now().saturating_sub(PING_RETENTION.as_secs());
+        while pings.len() > MAX_PINGS || pings.front().is_some_and(|p| p.time < retain_after) {
+            pings.pop_front();
+        }
+    }
+
+    /// Summarize pings within the given period until now.
+    pub fn summary(&self, period: Duration) -> Summary {
+        let after = now().saturating_sub(period.as_secs());
+        let pings = self.pings.lock().unwrap();
+        let pings = pings.iter().filter(|ping| ping.time >= after);
+
+        let mut summary = Summary::default();
+        let mut ips = HashSet::new();
+        for ping in pings {
+            summary.total += 1;
+            ips.extend(ping.ip);
+            if let Some(hostname) = &ping.hostname {
+                *summary.hostnames.entry(hostname.clone()).or_default() += 1;
+            }
+        }
+        summary.unique_ips = ips.len();
+        summary
+    }
+}
+
+/// Aggregate ping counts.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Total number of pings.
+    pub total: usize,
+
+    /// Number of unique source IPs.
+    pub unique_ips: usize,
+
+    /// Number of pings per hostname.
+    pub hostnames: HashMap<String, usize>,
+}
+
+impl Summary {
+    /// Most pinged hostnames, with their ping count, most pinged first.
+    pub fn top_hostnames(&self, count: usize) -> Vec<(&str, usize)> {
+        let mut hostnames: Vec<_> = self
+            .hostnames
+            .iter()
+            .map(|(hostname, count)| (hostname.as_str(), *count))
+            .collect();
+        hostnames.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        hostnames.truncate(count);
+        hostnames
+    }
+}
+
+/// Server process resource usage samples since the last summary.
+#[derive(Debug, Default)]
+pub struct ResourceLog {
+    /// Totals of samples since the last summary.
+    totals: Mutex<ResourceSummary>,
+}
+
+impl ResourceLog {
+    /// Record resource usage sample, `None` if the server is asleep.
+    ///
+    /// CPU usage is in percent of a single core, memory in bytes.
+    pub fn record(&self, sample: Option<(f64, u64)>) {
+        let mut totals = self.totals.lock().unwrap();
+        totals.samples += 1;
+        if let Some((cpu, memory)) = sample {
+            totals.awake += 1;
+            totals.cpu += cpu;
+            totals.memory += memory;
+        }
+    }
+
+    /// Take summary of samples since the last summary.
+    pub fn take_summary(&self) -> ResourceSummary {
+        std::mem::take(&mut self.totals.lock().unwrap())
+    }
+}
+
+/// Aggregate resource usage samples.
+#[derive(Debug, Default)]
+pub struct ResourceSummary {
+    /// Total number of samples.
+    pub samples: u32,
+
+    /// Number of samples while the server was awake.
+    pub awake: u32,
+
+    /// Sum of CPU usage in percent while awake.
+    pub cpu: f64,
+
+    /// Sum of memory usage in bytes while awake.
+    pub memory: u64,
+}
+
+impl ResourceSummary {
+    /// Percentage of time the server was awake.
+    pub fn awake_percent(&self) -> f64 {
+        self.awake as f64 / self.samples.max(1) as f64 * 100.0
+    }
+
+    /// Average CPU usage in percent and memory usage in bytes while awake.
+    pub fn average(&self) -> (f64, u64) {
+        let awake = self.awake.max(1);
+        (self.cpu / awake as f64, self.memory / awake as u64)
+    }
+}
+
+/// Periodically sample server process resource usage.
+///
+/// Only samples servers run as process. Frozen servers are asleep.
+pub async fn sample_resources(config: Arc<Config>, server: Arc<ServerState>) {
+    let interval = Duration::from_secs(config.analytics.resource_interval as u64);
+    let mut last: Option<(u32, os::Usage, Instant)> = None;
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let pid = server.pid().filter(|_| !server.frozen());
+        let usage = pid.and_then(|pid| os::usage(pid).map(|usage| (pid, usage, Instant::now())));
+
+        let previous = std::mem::replace(&mut last, usage);
+
+        // Server is asleep if there is no running process
+        let (pid, usage, time) = match usage {
+            Some(usage) => usage,
+            None => {
+                server.resources.record(None);
+                continue;
+            }
+        };
+
+        // Determine CPU usage since the last sample of the same process
+        let (_, last_usage, last_time) = match previous {
+            Some(previous) if previous.0 == pid => previous,
+            _ => continue,
+        };
+        let cpu_time = usage.cpu_time.saturating_sub(last_usage.cpu_time);
+        let cpu = cpu_time.as_secs_f64() / time.duration_since(last_time).as_secs_f64() * 100.0;
+
+        let memory = usage.memory / 1024 / 1024;
+        debug!(target: "lazymc::analytics", "Server using {:.1}% CPU, {} MiB memory", cpu, memory);
+        server.resources.record(Some((cpu, usage.memory)));
+    }
+}
+
+/// Periodically log ping summary.
+pub async fn report(config: Arc<Config>, server: Arc<ServerState>) {
+    let interval = Duration::from_secs(config.analytics.summary_interval as u64);
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let summary = server.pings.summary(interval);
+        let hostnames = summary
+            .top_hostnames(SUMMARY_HOSTNAMES)
+            .iter()
+            .map(|(hostname, count)| format!("{} ({})", hostname, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        info!(
+            target: "lazymc::analytics",
+            "Received {} status pings from {} IPs in the last {}s, hostnames: {}",
+            summary.total,
+            summary.unique_ips,
+            interval.as_secs(),
+            if hostnames.is_empty() { "-" } else { &hostnames },
+        );
+
+        // Report what sleeping saved
+        let resources = server.resources.take_summary();
+        if resources.samples > 0 {
+            let (cpu, memory) = resources.average();
+            info!(
+                target: "lazymc::analytics",
+                "Server was awake {:.0}% of the last {}s, averaging {:.1}% CPU and {} MiB memory",
+                resources.awake_percent(),
+                interval.as_secs(),
+                cpu,
+                memory / 1024 / 1024,
+            );
+        }
+
+        // Report how long server starts take
+        if let Some(estimate) = server.start_estimate() {
+            info!(
+                target: "lazymc::analytics",
+                "Server starts take {}s, median of the last {} starts",
+                estimate.as_secs(),
+                server.start_durations().len(),
+            );
+        }
+    }
+}
+
+/// Current UNIX timestamp in seconds.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::BytesMut;
+use tokio::io::{self, AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::Config;
+use crate::crypto::{self, CryptReader, CryptWriter};
+use crate::join::kick;
+use crate::mc::session::{self, Profile};
+use crate::proto::{self, Client, ClientState};
+use crate::protocol::Protocol;
+use crate::server::{self, ServerState};
+use crate::wake;
+
+/// Perform encryption exchange with client, and enable encryption on the client stream.
+///
+/// This is the encryption part of the login flow of an online-mode server. Returns the shared
+/// secret. Gives up if the client doesn't respond within the timeout.
+pub async fn encrypt<R, W>(
+    client: &Client,
+    reader: &mut CryptReader<R>,
+    writer: &mut CryptWriter<W>,
+    buf: &mut BytesMut,
+    timeout: Duration,
+) -> Result<Vec<u8>, ()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    trace!(target: "lazymc", "Sending encryption request to client");
+    let protocol = Protocol::of(client);
+    let key_pair = crypto::key_pair().await?;
+    let verify_token = crypto::verify_token();
+    let request = protocol
+        .encode_encryption_request(key_pair.public_der(), &verify_token)?
+        .encode(client)?;
+    writer.write_all(&request).await.map_err(|_| ())?;
+
+    // Wait for encryption response
+    let packet = loop {
+        let (packet, _raw) = match proto::read_packet_timeout(client, buf, reader, timeout).await {
+            Ok(Some(packet)) => packet,
+            Ok(None) | Err(_) => {
+                debug!(target: "lazymc", "Client closed connection during encryption");
+                return Err(());
+            }
+        };
+        if packet.id == proto::LOGIN_PACKET_ID_ENCRYPTION_RESPONSE {
+            break packet;
+        }
+        debug!(target: "lazymc", "Ignoring unhandled login packet from client: {}", packet.id);
+    };
+    let response = protocol.decode_encryption_response(&packet.data)?;
+
+    // Client must echo our verify token
+    let token = match response.verify_token {
+        Some(token) => key_pair.decrypt(&token)?,
+        None => {
+            warn!(target: "lazymc", "Client sent encryption signature instead of verify token, which is not supported");
+            return Err(());
+        }
+    };
+    if token != verify_token {
+        warn!(target: "lazymc", "Client sent invalid verify token, disconnecting");
+        return Err(());
+    }
+
+    let shared_secret = key_pair.decrypt(&response.shared_secret)?;
+    reader.enable(&shared_secret)?;
+    writer.enable(&shared_secret)?;
+
+    Ok(shared_secret)
+}
+
+/// Authenticate client with the session server.
+///
+/// Performs the encryption exchange first, the client stream is encrypted afterwards. Returns the
+/// player profile if authenticated, `None` if not.
+pub async fn authenticate<R, W>(
+    client: &Client,
+    reader: &mut CryptReader<R>,
+    writer: &mut CryptWriter<W>,
+    buf: &mut BytesMut,
+    timeout: Duration,
+) -> Result<Option<Profile>, ()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let username = client.username().ok_or(())?;
+    let shared_secret = encrypt(client, reader, writer, buf, timeout).await?;
+
+    let key_pair = crypto::key_pair().await?;
+    let hash = session::server_hash("", &shared_secret, key_pair.public_der());
+    session::has_joined(&username, &hash).await
+}
+
+/// Authenticate a client before waking the server.
+///
+/// Starts the server only if the client is authenticated with the session server and may wake it.
+/// The client is kicked afterwards, with the starting message if the server is started.
+pub async fn wake(
+    client: Client,
+    config: Arc<Config>,
+    server: Arc<ServerState>,
+    mut inbound: TcpStream,
+) -> Result<(), ()> {
+    let (reader, writer) = inbound.split();
+    let (mut reader, mut writer) = (CryptReader::new(reader), CryptWriter::new(writer));
+    let mut buf = BytesMut::new();
+
+    let timeout = config.limits.timeout(ClientState::Login);
+    let message = match authenticate(&client, &mut reader, &mut writer, &mut buf, timeout).await? {
+        Some(profile) => {
+            info!(target: "lazymc", "Player {} ({}) authenticated", profile.name, profile.id);
+            match wake::check(&config, &server, &client, Some(&profile)) {
+                Ok(()) => {
+                    server::start_server(config.clone(), server.clone());
+                    kick::starting_message(&client, &config, &server, None)
+                }
+                Err(message) => message,
+            }
+        }
+        None => {
+            warn!(target: "lazymc", "Player {} failed to authenticate, not waking server", client.username().unwrap_or_default());
+            config.wake.message_unauthenticated.clone()
+        }
+    };
+    kick::kick(&client, &message, &mut writer).await?;
+
+    // Gracefully close connection
+    match writer.shutdown().await {
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotConnected => {}
+        Err(_) => return Err(()),
+    }
+
+    Ok(())
+}
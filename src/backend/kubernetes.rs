@@ -0,0 +1,183 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use serde_json::{json, Value};
+
+use crate::backend::POLL_INTERVAL;
+use crate::config::{Config, Kubernetes};
+use crate::server::ServerState;
+
+/// Kubernetes API request timeout.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Namespace file of the service account lazymc runs with.
+const NAMESPACE_FILE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+
+/// Start server by scaling the workload up, and wait for it to be scaled down again.
+///
+/// Reports the server online once a pod is ready. Returns `true` if it stopped cleanly.
+pub async fn run(config: &Config, state: &ServerState) -> bool {
+    let client = match Client::new(&config.kubernetes) {
+        Ok(client) => client,
+        Err(_) => return false,
+    };
+
+    info!(target: "lazymc", "Starting server, scaling up {}...", client.workload);
+    if client.scale(1).await.is_err() {
+        return false;
+    }
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let workload = match client.get().await {
+            Ok(workload) => workload,
+            Err(_) => continue,
+        };
+        let desired = replicas(&workload, "/spec/replicas");
+        let current = replicas(&workload, "/status/replicas");
+        let ready = replicas(&workload, "/status/readyReplicas");
+
+        // Server stopped once scaled down and all pods are gone
+        if desired == 0 {
+            if current == 0 {
+                info!(target: "lazymc", "Server stopped, scaled down {}\n", client.workload);
+                return true;
+            }
+            continue;
+        }
+
+        if ready > 0 {
+            state.set_ready(config);
+        }
+
+        // Scale down if server doesn't come online in time
+        if state.check_start_timeout(config) {
+            let _ = client.scale(0).await;
+        }
+    }
+}
+
+/// Stop server by scaling the workload down.
+pub async fn stop(config: &Config) -> bool {
+    let client = match Client::new(&config.kubernetes) {
+        Ok(client) => client,
+        Err(_) => return false,
+    };
+
+    info!(target: "lazymc", "Stopping server, scaling down {}...", client.workload);
+    client.scale(0).await.is_ok()
+}
+
+/// Get number of replicas at the given JSON pointer in a workload, 0 if not set.
+fn replicas(workload: &Value, pointer: &str) -> u64 {
+    workload
+        .pointer(pointer)
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+}
+
+/// Kubernetes API client for the configured workload.
+struct Client {
+    /// HTTP agent trusting the API server CA.
+    agent: ureq::Agent,
+
+    /// Workload API URL.
+    url: String,
+
+    /// Workload description, such as `deployments/minecraft`.
+    workload: String,
+
+    /// Service account token file.
+    token_file: Arc<Path>,
+}
+
+impl Client {
+    /// Construct client for the configured workload.
+    fn new(config: &Kubernetes) -> Result<Self, ()> {
+        let certs = CertificateDer::pem_file_iter(&config.ca_file)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| {
+                error!(target: "lazymc", "Failed to load Kubernetes CA certificate: {}", err);
+            })?;
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add_parsable_certificates(certs);
+        let tls = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let agent = ureq::AgentBuilder::new()
+            .tls_config(Arc::new(tls))
+            .timeout(REQUEST_TIMEOUT)
+            .build();
+
+        // Use namespace lazymc runs in if not configured
+        let namespace = match &config.namespace {
+            Some(namespace) => namespace.clone(),
+            None => fs::read_to_string(NAMESPACE_FILE)
+                .map(|namespace| namespace.trim().to_string())
+                .unwrap_or_else(|_| "default".into()),
+        };
+
+        let workload = format!("{}/{}", config.kind.resource(), config.name);
+        let url = format!(
+            "{}/apis/apps/v1/namespaces/{}/{}",
+            config.api_url.trim_end_matches('/'),
+            namespace,
+            workload,
+        );
+
+        Ok(Self {
+            agent,
+            url,
+            workload,
+            token_file: config.token_file.as_path().into(),
+        })
+    }
+
+    /// Get workload.
+    async fn get(&self) -> Result<Value, ()> {
+        self.request("GET", &self.url, None).await
+    }
+
+    /// Scale workload to number of replicas.
+    async fn scale(&self, replicas: u32) -> Result<(), ()> {
+        let url = format!("{}/scale", self.url);
+        let patch = json!({ "spec": { "replicas": replicas } });
+        self.request("PATCH", &url, Some(patch)).await.map(|_| ())
+    }
+
+    /// Send request to the Kubernetes API, authenticated with the service account token.
+    ///
+    /// The token is read on each request as it may be rotated.
+    async fn request(&self, method: &str, url: &str, body: Option<Value>) -> Result<Value, ()> {
+        let request = self.agent.request(method, url);
+        let token_file = self.token_file.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let token = fs::read_to_string(&token_file).map_err(|err| {
+                error!(target: "lazymc", "Failed to read Kubernetes token: {}", err);
+            })?;
+            let request = request.set("Authorization", &format!("Bearer {}", token.trim()));
+
+            let response = match body {
+                Some(body) => request
+                    .set("Content-Type", "application/merge-patch+json")
+                    .send_json(body),
+                None => request.call(),
+            };
+            let response = response.map_err(|err| {
+                error!(target: "lazymc", "Kubernetes API request failed: {}", err);
+            })?;
+
+            response.into_json().map_err(|err| {
+                error!(target: "lazymc", "Failed to parse Kubernetes API response: {}", err);
+            })
+        })
+        .await
+        .map_err(|_| ())?
+    }
+}
@@ -0,0 +1,11 @@
+#[cfg(feature = "kubernetes")]
+pub mod kubernetes;
+pub mod pterodactyl;
+pub mod ssh;
+pub mod systemd;
+pub mod wol;
+
+use std::time::Duration;
+
+/// Interval to poll server state from backends.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
@@ -0,0 +1,134 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::backend::POLL_INTERVAL;
+use crate::config::Config;
+use crate::server::ServerState;
+
+/// Server resource usage and state, as reported by the panel.
+#[derive(Debug, Deserialize)]
+struct Resources {
+    /// Server state, such as `starting`, `running` or `offline`.
+    current_state: String,
+
+    /// Resource usage.
+    resources: Usage,
+}
+
+/// Server resource usage.
+#[derive(Debug, Deserialize)]
+struct Usage {
+    /// Memory usage in bytes.
+    memory_bytes: u64,
+
+    /// CPU usage in percent, may exceed 100 with multiple cores.
+    cpu_absolute: f64,
+}
+
+/// Resources response wrapper.
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    attributes: T,
+}
+
+/// Start server through the panel, and wait for it to go offline.
+///
+/// The server is online once the panel reports it is running. Returns `true` if it stopped.
+pub async fn run(config: &Config, state: &ServerState) -> bool {
+    info!(target: "lazymc", "Starting server through Pterodactyl...");
+    if !power(config, "start").await {
+        return false;
+    }
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let resources = match resources(config).await {
+            Ok(resources) => resources,
+            Err(_) => continue,
+        };
+        let usage = &resources.resources;
+        trace!(
+            target: "lazymc",
+            "Server is {}, using {} MiB memory, {:.1}% CPU",
+            resources.current_state, usage.memory_bytes / 1024 / 1024, usage.cpu_absolute,
+        );
+
+        match resources.current_state.as_str() {
+            "running" => state.set_ready(config),
+            "offline" => {
+                info!(target: "lazymc", "Server stopped, offline in Pterodactyl\n");
+                return true;
+            }
+            _ => {}
+        }
+
+        // Stop server if it doesn't come online in time
+        if state.check_start_timeout(config) {
+            power(config, "stop").await;
+        }
+    }
+}
+
+/// Stop server through the panel.
+pub async fn stop(config: &Config) -> bool {
+    info!(target: "lazymc", "Stopping server through Pterodactyl...");
+    power(config, "stop").await
+}
+
+/// Send power action to the server, such as `start` or `stop`.
+async fn power(config: &Config, signal: &str) -> bool {
+    let request = ureq::post(&url(config, "power"))
+        .set(
+            "Authorization",
+            &format!("Bearer {}", config.pterodactyl.api_key),
+        )
+        .set("Accept", "application/json");
+    let body = json!({ "signal": signal });
+
+    let signal = signal.to_string();
+
+    tokio::task::spawn_blocking(move || match request.send_json(body) {
+        Ok(_) => true,
+        Err(err) => {
+            error!(target: "lazymc", "Pterodactyl {} power action failed: {}", signal, err);
+            false
+        }
+    })
+    .await
+    .unwrap_or(false)
+}
+
+/// Fetch server state and resource usage.
+async fn resources(config: &Config) -> Result<Resources, ()> {
+    let request = ureq::get(&url(config, "resources"))
+        .set(
+            "Authorization",
+            &format!("Bearer {}", config.pterodactyl.api_key),
+        )
+        .set("Accept", "application/json");
+
+    tokio::task::spawn_blocking(move || {
+        let response = request.call().map_err(|err| {
+            error!(target: "lazymc", "Failed to fetch server state from Pterodactyl: {}", err);
+        })?;
+        response
+            .into_json::<Response<Resources>>()
+            .map(|response| response.attributes)
+            .map_err(|err| {
+                error!(target: "lazymc", "Failed to parse Pterodactyl response: {}", err);
+            })
+    })
+    .await
+    .map_err(|_| ())?
+}
+
+/// Build client API URL for the configured server.
+fn url(config: &Config, endpoint: &str) -> String {
+    format!(
+        "{}/api/client/servers/{}/{}",
+        config.pterodactyl.url.trim_end_matches('/'),
+        config.pterodactyl.server,
+        endpoint,
+    )
+}
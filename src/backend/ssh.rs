@@ -0,0 +1,78 @@
+use std::process::Output;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+use crate::config::Config;
+use crate::server::ServerState;
+
+/// Interval to check whether the remote server is running.
+const RUNNING_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// SSH client exit code if connecting or authenticating failed.
+const SSH_ERROR_CODE: i32 = 255;
+
+/// Start server on the remote host, and wait for it to stop.
+///
+/// Returns `true` if it stopped, `false` if it couldn't be started.
+pub async fn run(config: &Config, state: &ServerState) -> bool {
+    info!(target: "lazymc", "Starting server on {} through SSH...", config.ssh.host);
+    if !ssh_success(config, &config.ssh.start_command).await {
+        return false;
+    }
+
+    loop {
+        tokio::time::sleep(RUNNING_CHECK_INTERVAL).await;
+
+        // Remote server stopped if running command fails, keep waiting on connection errors
+        match ssh(config, &config.ssh.running_command).await {
+            Ok(output) if output.status.success() => {}
+            Ok(output) if output.status.code() != Some(SSH_ERROR_CODE) => {
+                info!(target: "lazymc", "Server stopped on {}\n", config.ssh.host);
+                return true;
+            }
+            Ok(_) | Err(_) => {
+                warn!(target: "lazymc", "Failed to check whether server is running through SSH");
+            }
+        }
+
+        // Stop server if it doesn't come online in time
+        if state.check_start_timeout(config) {
+            ssh_success(config, &config.ssh.stop_command).await;
+        }
+    }
+}
+
+/// Stop server on the remote host.
+pub async fn stop(config: &Config) -> bool {
+    info!(target: "lazymc", "Stopping server on {} through SSH...", config.ssh.host);
+    ssh_success(config, &config.ssh.stop_command).await
+}
+
+/// Run command on the remote host, returns `true` if it succeeded.
+async fn ssh_success(config: &Config, command: &str) -> bool {
+    match ssh(config, command).await {
+        Ok(output) if output.status.success() => true,
+        Ok(output) => {
+            let err = String::from_utf8_lossy(&output.stderr);
+            error!(target: "lazymc", "SSH command failed ({}): {}", output.status, err.trim());
+            false
+        }
+        Err(()) => false,
+    }
+}
+
+/// Run command on the remote host through the SSH client.
+async fn ssh(config: &Config, command: &str) -> Result<Output, ()> {
+    let mut cmd = Command::new("ssh");
+    cmd.args(["-o", "BatchMode=yes", "-p"])
+        .arg(config.ssh.port.to_string());
+    if let Some(ref identity_file) = config.ssh.identity_file {
+        cmd.arg("-i").arg(identity_file);
+    }
+    cmd.arg("--").arg(&config.ssh.host).arg(command);
+
+    cmd.output().await.map_err(|err| {
+        error!(target: "lazymc", "Failed to invoke ssh: {}", err);
+    })
+}
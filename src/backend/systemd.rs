@@ -0,0 +1,81 @@
+use tokio::process::Command;
+
+use crate::backend::POLL_INTERVAL;
+use crate::config::Config;
+use crate::server::ServerState;
+
+/// Start server by starting the systemd unit, and wait for the unit to stop.
+///
+/// Returns `true` if it stopped cleanly, `false` if the unit failed.
+pub async fn run(config: &Config, state: &ServerState) -> bool {
+    let unit = unit(config);
+
+    info!(target: "lazymc", "Starting server, starting unit {}...", unit);
+    if systemctl(config, &["start", unit]).await.is_err() {
+        return false;
+    }
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        // Get unit state, such as active, inactive or failed
+        let active_state =
+            match systemctl(config, &["show", "--value", "-p", "ActiveState", unit]).await {
+                Ok(active_state) => active_state,
+                Err(_) => continue,
+            };
+        match active_state.as_str() {
+            "active" | "activating" | "reloading" | "deactivating" => {}
+            "failed" => {
+                info!(target: "lazymc", "Server stopped, unit {} failed\n", unit);
+                return false;
+            }
+            _ => {
+                info!(target: "lazymc", "Server stopped, unit {} is inactive\n", unit);
+                return true;
+            }
+        }
+
+        // Stop unit if server doesn't come online in time
+        if state.check_start_timeout(config) {
+            let _ = systemctl(config, &["stop", "--no-block", unit]).await;
+        }
+    }
+}
+
+/// Stop server by stopping the systemd unit.
+///
+/// Doesn't wait for the unit to stop.
+pub async fn stop(config: &Config) -> bool {
+    let unit = unit(config);
+
+    info!(target: "lazymc", "Stopping server, stopping unit {}...", unit);
+    systemctl(config, &["stop", "--no-block", unit])
+        .await
+        .is_ok()
+}
+
+/// Get configured systemd unit.
+fn unit(config: &Config) -> &str {
+    config.server.systemd_unit.as_deref().unwrap_or_default()
+}
+
+/// Invoke systemctl with the given arguments, returns its trimmed output if it succeeded.
+async fn systemctl(config: &Config, args: &[&str]) -> Result<String, ()> {
+    let mut cmd = Command::new("systemctl");
+    if config.server.systemd_user {
+        cmd.arg("--user");
+    }
+    cmd.args(args);
+
+    let output = cmd.output().await.map_err(|err| {
+        error!(target: "lazymc", "Failed to invoke systemctl: {}", err);
+    })?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        error!(target: "lazymc", "systemctl {} failed: {}", args[0], err.trim());
+        return Err(());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().into())
+}
@@ -0,0 +1,93 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::backend::POLL_INTERVAL;
+use crate::config::{Config, MacAddress};
+use crate::hooks;
+#[cfg(feature = "rcon")]
+use crate::server;
+use crate::server::ServerState;
+
+/// Timeout to connect to the server to check whether it is reachable.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Wake server machine, and wait for the server to become unreachable again.
+///
+/// Keeps sending magic packets until the server is reachable. Returns `true` if it stopped.
+pub async fn run(config: &Config, state: &ServerState) -> bool {
+    let mac = match config.wol.mac {
+        Some(mac) => mac,
+        None => return false,
+    };
+
+    info!(target: "lazymc", "Starting server, waking machine with Wake-on-LAN...");
+    let mut reachable = false;
+    loop {
+        if !reachable {
+            if let Err(err) = send_magic_packet(mac, config.wol.broadcast).await {
+                error!(target: "lazymc", "Failed to send Wake-on-LAN packet: {}", err);
+            }
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        // Server stopped once it becomes unreachable
+        let up = is_reachable(config.server.address).await;
+        if reachable && !up {
+            info!(target: "lazymc", "Server stopped, {} is unreachable\n", config.server.address);
+            return true;
+        }
+        reachable = up;
+
+        // Give up if server doesn't come online in time
+        if state.check_start_timeout(config) {
+            if !reachable {
+                return false;
+            }
+            stop(config, state).await;
+        }
+    }
+}
+
+/// Stop server by shutting down its machine, or through RCON if no command is configured.
+#[allow(unused_variables)]
+pub async fn stop(config: &Config, state: &ServerState) -> bool {
+    if let Some(ref command) = config.wol.stop_command {
+        return hooks::run_command(config, "stop", command).await;
+    }
+
+    #[cfg(feature = "rcon")]
+    if server::stop_server_rcon(config, state).await {
+        return true;
+    }
+
+    false
+}
+
+/// Send Wake-on-LAN magic packet for the given MAC address.
+async fn send_magic_packet(mac: MacAddress, broadcast: SocketAddr) -> std::io::Result<()> {
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac.0);
+    }
+
+    let bind: SocketAddr = if broadcast.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.set_broadcast(true)?;
+    socket.send_to(&packet, broadcast).await?;
+    Ok(())
+}
+
+/// Check whether the server accepts connections.
+async fn is_reachable(addr: SocketAddr) -> bool {
+    matches!(
+        tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await,
+        Ok(Ok(_))
+    )
+}
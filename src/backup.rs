@@ -0,0 +1,142 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::Local;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::config::Config;
+#[cfg(feature = "rcon")]
+use crate::server;
+
+/// Backup file name prefix.
+const BACKUP_PREFIX: &str = "backup-";
+
+/// Backup file name extension.
+const BACKUP_EXTENSION: &str = ".tar.gz";
+
+/// Number of attempts to re-enable saving after a live backup.
+#[cfg(feature = "rcon")]
+const SAVE_ON_ATTEMPTS: u32 = 5;
+
+/// Back up configured world directories, then apply retention policy.
+///
+/// Runs on a blocking thread. Prints an error and returns false if the backup fails.
+pub async fn backup(config: &Config) -> bool {
+    let dir = config.server.dir().to_path_buf();
+    let backups = dir.join(&config.backups.directory);
+    let worlds = config.backups.worlds.clone();
+    let (keep_last, keep_days) = (config.backups.keep_last, config.backups.keep_days);
+
+    info!(target: "lazymc", "Backing up world...");
+    let result = tokio::task::spawn_blocking(move || {
+        let path = archive(&dir, &backups, &worlds)?;
+        info!(target: "lazymc", "Backed up world to {}", path.display());
+        prune(&backups, keep_last, keep_days)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => true,
+        Ok(Err(err)) => {
+            error!(target: "lazymc", "Failed to back up world: {}", err);
+            false
+        }
+        Err(err) => {
+            error!(target: "lazymc", "Failed to back up world: {}", err);
+            false
+        }
+    }
+}
+
+/// Back up configured world directories while the server is online.
+///
+/// Disables saving and flushes the world through RCON for a consistent copy. Saving is always
+/// re-enabled afterwards, even if the backup failed.
+#[cfg(feature = "rcon")]
+pub async fn backup_live(config: &Config) -> bool {
+    let backed_up = if !server::rcon_command(config, "save-off").await {
+        error!(target: "lazymc", "Failed to disable saving through RCON, skipping backup");
+        false
+    } else if !server::rcon_command(config, "save-all flush").await {
+        error!(target: "lazymc", "Failed to save world through RCON, skipping backup");
+        false
+    } else {
+        backup(config).await
+    };
+
+    // Always re-enable saving, retry as the world must not be left unsaved
+    for attempt in 1..=SAVE_ON_ATTEMPTS {
+        if server::rcon_command(config, "save-on").await {
+            return backed_up;
+        }
+        warn!(target: "lazymc", "Failed to re-enable saving through RCON (attempt {})", attempt);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    error!(target: "lazymc", "Failed to re-enable saving, run 'save-on' on the server manually");
+    false
+}
+
+/// Archive world directories into a new timestamped backup file.
+///
+/// World directories that don't exist are skipped.
+fn archive(dir: &Path, backups: &Path, worlds: &[String]) -> io::Result<PathBuf> {
+    fs::create_dir_all(backups)?;
+    let name = format!(
+        "{}{}{}",
+        BACKUP_PREFIX,
+        Local::now().format("%Y-%m-%d_%H-%M-%S"),
+        BACKUP_EXTENSION
+    );
+    let path = backups.join(name);
+
+    let encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    for world in worlds {
+        let world_dir = dir.join(world);
+        if world_dir.is_dir() {
+            archive.append_dir_all(world, world_dir)?;
+        }
+    }
+    archive.into_inner()?.finish()?;
+
+    Ok(path)
+}
+
+/// Remove old backups beyond the last number to keep, or older than number of days.
+///
+/// Limits are disabled if 0.
+fn prune(backups: &Path, keep_last: u32, keep_days: u32) -> io::Result<()> {
+    // Backup files, newest first as names are timestamped
+    let mut files: Vec<PathBuf> = fs::read_dir(backups)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_EXTENSION)
+                })
+        })
+        .collect();
+    files.sort_unstable_by(|a, b| b.cmp(a));
+
+    let max_age = Duration::from_secs(keep_days as u64 * 24 * 60 * 60);
+    for (i, file) in files.iter().enumerate() {
+        let too_many = keep_last > 0 && i >= keep_last as usize;
+        let too_old = keep_days > 0
+            && fs::metadata(file)
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > max_age);
+        if too_many || too_old {
+            debug!(target: "lazymc", "Removing old backup {}", file.display());
+            fs::remove_file(file)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::net::UdpSocket;
+
+use crate::config::Config;
+use crate::messages;
+use crate::proto::Client;
+use crate::server::{self, ServerState};
+use crate::wake;
+
+/// RakNet offline message magic.
+const MAGIC: [u8; 16] = [
+    0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
+];
+
+/// RakNet unconnected ping packet ID.
+const ID_UNCONNECTED_PING: u8 = 0x01;
+
+/// RakNet unconnected ping packet ID, only answered if there are open connections.
+const ID_UNCONNECTED_PING_OPEN_CONNECTIONS: u8 = 0x02;
+
+/// RakNet open connection request packet ID, sent when a client connects.
+const ID_OPEN_CONNECTION_REQUEST_1: u8 = 0x05;
+
+/// RakNet unconnected pong packet ID.
+const ID_UNCONNECTED_PONG: u8 = 0x1c;
+
+/// Maximum datagram size to receive.
+const MAX_PACKET_LEN: usize = 1500;
+
+/// Interval to check whether the server is starting, to release the socket.
+const STATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serve Bedrock unconnected pings on UDP while the server is sleeping.
+///
+/// The socket is released while the server runs, so Geyser can bind it.
+pub async fn serve(config: Arc<Config>, server: Arc<ServerState>) {
+    let guid: i64 = rand::thread_rng().gen();
+
+    loop {
+        // Wait for server to sleep, a frozen server keeps its socket
+        while server.starting() || server.frozen() {
+            tokio::time::sleep(STATE_INTERVAL).await;
+        }
+
+        let addr = config.bedrock.address;
+        let socket = match UdpSocket::bind(addr).await {
+            Ok(socket) => socket,
+            Err(err) => {
+                error!(target: "lazymc", "Failed to bind Bedrock socket on {}: {}", addr, err);
+                tokio::time::sleep(STATE_INTERVAL).await;
+                continue;
+            }
+        };
+        debug!(target: "lazymc", "Serving Bedrock pings on {}", addr);
+
+        serve_socket(&config, &server, &socket, guid).await;
+        debug!(target: "lazymc", "Server starting, releasing Bedrock socket");
+    }
+}
+
+/// Serve Bedrock pings on socket until the server is starting.
+async fn serve_socket(
+    config: &Arc<Config>,
+    server: &Arc<ServerState>,
+    socket: &UdpSocket,
+    guid: i64,
+) {
+    let mut buf = [0; MAX_PACKET_LEN];
+
+    while !server.starting() {
+        let (len, peer) =
+            match tokio::time::timeout(STATE_INTERVAL, socket.recv_from(&mut buf)).await {
+                Ok(Ok(result)) => result,
+                Ok(Err(_)) | Err(_) => continue,
+            };
+        let packet = &buf[..len];
+
+        match packet.first() {
+            Some(&ID_UNCONNECTED_PING) | Some(&ID_UNCONNECTED_PING_OPEN_CONNECTIONS) => {
+                let time = match packet.get(1..9) {
+                    Some(time) => time,
+                    None => continue,
+                };
+                let pong = pong(config, server, time, guid);
+                if let Err(err) = socket.send_to(&pong, peer).await {
+                    debug!(target: "lazymc", "Failed to send Bedrock pong to {}: {}", peer, err);
+                }
+            }
+            Some(&ID_OPEN_CONNECTION_REQUEST_1) if config.bedrock.wake => {
+                wake(config, server, peer);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Wake server for a Bedrock client connection attempt, if allowed.
+///
+/// Bedrock players can't be identified, so wake checks for players fail.
+fn wake(config: &Arc<Config>, server: &Arc<ServerState>, peer: SocketAddr) {
+    if config.wake.online_mode {
+        debug!(target: "lazymc", "Not waking server for Bedrock client, online mode is enabled");
+        return;
+    }
+    if let Err(message) = wake::check(config, server, &Client::new(peer), None) {
+        debug!(target: "lazymc", "Not waking server for Bedrock client {}: {}", peer, message);
+        return;
+    }
+
+    info!(target: "lazymc", "Bedrock client {} connecting, starting server", peer);
+    server::start_server(config.clone(), server.clone());
+}
+
+/// Build unconnected pong packet.
+fn pong(config: &Config, server: &ServerState, time: &[u8], guid: i64) -> Vec<u8> {
+    let motd = messages::render(&config.messages.motd_sleeping, config, server);
+    let (line1, line2) = motd.split_once('\n').unwrap_or((&motd, ""));
+    let max = server
+        .clone_status()
+        .map(|status| status.players.max)
+        .unwrap_or(0);
+    let port = config.bedrock.address.port();
+
+    let info = format!(
+        "MCPE;{};{};{};0;{};{};{};Survival;1;{};{};",
+        sanitize(line1),
+        config.bedrock.protocol,
+        config.bedrock.version,
+        max,
+        guid,
+        sanitize(line2),
+        port,
+        port,
+    );
+
+    let mut packet = vec![ID_UNCONNECTED_PONG];
+    packet.extend_from_slice(time);
+    packet.extend_from_slice(&guid.to_be_bytes());
+    packet.extend_from_slice(&MAGIC);
+    packet.extend_from_slice(&(info.len() as u16).to_be_bytes());
+    packet.extend_from_slice(info.as_bytes());
+    packet
+}
+
+/// Remove separator characters from text in server info.
+fn sanitize(text: &str) -> String {
+    text.replace(';', ",")
+}
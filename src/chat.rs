@@ -0,0 +1,104 @@
+use minecraft_protocol::data::chat::{Color, Message, Payload};
+
+/// Build chat message from text in config.
+///
+/// Parses a JSON chat component, or a JSON array of components, if the text is one. Otherwise
+/// formats legacy color and style codes prefixed with `§` or `&`.
+pub fn message(text: &str) -> Message {
+    json(text).unwrap_or_else(|| legacy(text))
+}
+
+/// Parse text as JSON chat component, or JSON array of components.
+fn json(text: &str) -> Option<Message> {
+    let trimmed = text.trim();
+    if trimmed.starts_with('{') {
+        return Message::from_json(trimmed).ok();
+    }
+    if trimmed.starts_with('[') {
+        let extra: Vec<Message> = serde_json::from_str(trimmed).ok()?;
+        let mut message = Message::new(Payload::text(""));
+        message.extra = extra;
+        return Some(message);
+    }
+    None
+}
+
+/// Format text with legacy color and style codes into chat message components.
+fn legacy(text: &str) -> Message {
+    let mut message = Message::new(Payload::text(""));
+    let mut current = Message::new(Payload::text(""));
+    let mut segment = String::new();
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        // Apply code if valid, keep character otherwise
+        let code = match chars.peek() {
+            Some(code) if (c == '§' || c == '&') && is_code(*code) => code.to_ascii_lowercase(),
+            _ => {
+                segment.push(c);
+                continue;
+            }
+        };
+        chars.next();
+
+        // Finish current segment
+        if !segment.is_empty() {
+            current.payload = Payload::text(&segment);
+            message.extra.push(current.clone());
+            segment.clear();
+        }
+
+        // Colors and reset clear styles, like the vanilla client does
+        match code {
+            'k' => current.obfuscated = Some(true),
+            'l' => current.bold = Some(true),
+            'm' => current.strikethrough = Some(true),
+            'n' => current.underlined = Some(true),
+            'o' => current.italic = Some(true),
+            'r' => current = Message::new(Payload::text("")),
+            _ => {
+                current = Message::new(Payload::text(""));
+                current.color = color(code);
+            }
+        }
+    }
+
+    // Plain text without codes
+    if message.extra.is_empty() {
+        return Message::new(Payload::text(&segment));
+    }
+
+    if !segment.is_empty() {
+        current.payload = Payload::text(&segment);
+        message.extra.push(current);
+    }
+    message
+}
+
+/// Check whether character is a legacy color or style code.
+fn is_code(c: char) -> bool {
+    matches!(c.to_ascii_lowercase(), '0'..='9' | 'a'..='f' | 'k'..='o' | 'r')
+}
+
+/// Get color for legacy color code.
+fn color(code: char) -> Option<Color> {
+    Some(match code {
+        '0' => Color::Black,
+        '1' => Color::DarkBlue,
+        '2' => Color::DarkGreen,
+        '3' => Color::DarkAqua,
+        '4' => Color::DarkRed,
+        '5' => Color::DarkPurple,
+        '6' => Color::Gold,
+        '7' => Color::Gray,
+        '8' => Color::DarkGray,
+        '9' => Color::Blue,
+        'a' => Color::Green,
+        'b' => Color::Aqua,
+        'c' => Color::Red,
+        'd' => Color::LightPurple,
+        'e' => Color::Yellow,
+        'f' => Color::White,
+        _ => return None,
+    })
+}
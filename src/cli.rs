@@ -2,7 +2,7 @@ use clap::{App, AppSettings, Arg};
 
 /// The clap app for CLI argument parsing.
 pub fn app() -> App<'static> {
-    App::new(crate_name!())
+    let app = App::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
@@ -29,5 +29,19 @@ pub fn app() -> App<'static> {
                 .default_value(crate::config::CONFIG_FILE)
                 .about("Use config file")
                 .takes_value(true),
-        )
+        );
+
+    #[cfg(feature = "rcon")]
+    let app = app.subcommand(
+        App::new("rcon")
+            .about("Invoke command through server RCON, prompt if none is given")
+            .setting(AppSettings::TrailingVarArg)
+            .arg(
+                Arg::new("COMMAND")
+                    .about("Command to invoke")
+                    .multiple_values(true),
+            ),
+    );
+
+    app
 }
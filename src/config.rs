@@ -1,11 +1,19 @@
+use std::collections::HashMap;
 use std::fs;
 use std::io;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
 use clap::ArgMatches;
+use ipnet::IpNet;
 use serde::Deserialize;
 
+use crate::locale;
+use crate::mc::server_properties;
+use crate::proto::{Client, ClientState};
 use crate::util::error::{quit_error, quit_error_msg, ErrorHintsBuilder};
 
 /// Default configuration file location.
@@ -66,6 +74,79 @@ pub struct Config {
     /// Time configuration.
     pub time: Time,
 
+    /// Monitor configuration.
+    #[serde(default)]
+    pub monitor: Monitor,
+
+    /// Join configuration.
+    #[serde(default)]
+    pub join: Join,
+
+    /// Wake configuration.
+    #[serde(default)]
+    pub wake: Wake,
+
+    /// Connection limits configuration.
+    #[serde(default)]
+    pub limits: Limits,
+
+    /// Socket options configuration.
+    #[serde(default)]
+    pub socket: Socket,
+
+    /// Status configuration.
+    #[serde(default)]
+    pub status: Status,
+
+    /// Query configuration.
+    #[serde(default)]
+    pub query: Query,
+
+    /// Bedrock configuration.
+    #[serde(default)]
+    pub bedrock: Bedrock,
+
+    /// Admin control configuration.
+    #[serde(default)]
+    pub admin: Admin,
+
+    /// In-game commands configuration.
+    #[serde(default)]
+    pub ingame: Ingame,
+
+    /// Analytics configuration.
+    #[serde(default)]
+    pub analytics: Analytics,
+
+    /// Schedule configuration.
+    #[serde(default)]
+    pub schedule: Schedule,
+
+    /// Event hooks configuration.
+    #[serde(default)]
+    pub hooks: Hooks,
+
+    /// Backups configuration.
+    #[serde(default)]
+    pub backups: Backups,
+
+    /// Kubernetes backend configuration.
+    #[serde(default)]
+    #[cfg_attr(not(feature = "kubernetes"), allow(dead_code))]
+    pub kubernetes: Kubernetes,
+
+    /// SSH backend configuration.
+    #[serde(default)]
+    pub ssh: Ssh,
+
+    /// Wake-on-LAN backend configuration.
+    #[serde(default)]
+    pub wol: Wol,
+
+    /// Pterodactyl backend configuration.
+    #[serde(default)]
+    pub pterodactyl: Pterodactyl,
+
     /// Messages, shown to the user.
     pub messages: Messages,
 
@@ -78,19 +159,124 @@ pub struct Config {
 
 impl Config {
     /// Load configuration from file.
+    ///
+    /// Messages that are not configured are taken from the configured language.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
         let data = fs::read(path)?;
-        let config = toml::from_slice(&data)?;
-        Ok(config)
+        let mut config: toml::Value = toml::from_slice(&data)?;
+        apply_server_properties(&mut config);
+        locale::apply(&mut config).map_err(|language| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown language: {}", language),
+            )
+        })?;
+        Ok(config.try_into()?)
+    }
+}
+
+/// Apply values from the server.properties file as defaults, for values that are not configured.
+fn apply_server_properties(config: &mut toml::Value) {
+    let dir = config
+        .get("server")
+        .and_then(|server| server.get("directory"))
+        .and_then(toml::Value::as_str)
+        .unwrap_or(".");
+    let properties = server_properties::load(Path::new(dir));
+    let property = |key: &str| properties.get(key).filter(|value| !value.is_empty());
+    let flag = |key: &str| property(key).and_then(|value| value.parse().ok());
+    let number = |key: &str| property(key).and_then(|value| value.parse::<u16>().ok());
+
+    let ip = property("server-ip")
+        .and_then(|ip| ip.parse().ok())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    let address = number("server-port").map(|port| SocketAddr::new(ip, port).to_string());
+
+    let defaults = [
+        (["server", "address"], address.map(toml::Value::String)),
+        (
+            ["rcon", "enabled"],
+            flag("enable-rcon").map(toml::Value::Boolean),
+        ),
+        (
+            ["rcon", "port"],
+            number("rcon.port").map(|port| toml::Value::Integer(port.into())),
+        ),
+        (
+            ["rcon", "password"],
+            property("rcon.password").cloned().map(toml::Value::String),
+        ),
+        (
+            ["status", "default_max_players"],
+            number("max-players").map(|max| toml::Value::Integer(max.into())),
+        ),
+    ];
+    for ([section, key], value) in defaults {
+        let (table, value) = match (config.as_table_mut(), value) {
+            (Some(table), Some(value)) => (table, value),
+            _ => continue,
+        };
+        if let Some(section) = table
+            .entry(section)
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+        {
+            section.entry(key).or_insert(value);
+        }
     }
 }
 
 /// Public configuration.
 #[derive(Debug, Deserialize)]
 pub struct Public {
-    /// Egress address.
+    /// Egress addresses.
     #[serde(alias = "address_egress")]
-    pub address: SocketAddr,
+    pub address: Addresses,
+
+    /// Only accept connections from these IP ranges, all if empty.
+    #[serde(default)]
+    pub allow: Vec<IpNet>,
+
+    /// Drop connections from these IP ranges.
+    #[serde(default)]
+    pub deny: Vec<IpNet>,
+
+    /// Accept PROXY protocol headers from these IP ranges, and require them.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+
+    /// Only accept clients connecting with these hostnames, all if empty.
+    #[serde(default)]
+    pub require_hostname: Vec<String>,
+
+    /// Message for clients connecting with other hostnames, drop silently if not set.
+    #[serde(default)]
+    pub message_wrong_hostname: Option<String>,
+}
+
+impl Public {
+    /// Whether the client connected with a required hostname.
+    ///
+    /// Hostnames starting with `*.` match any subdomain.
+    pub fn hostname_allowed(&self, client: &Client) -> bool {
+        if self.require_hostname.is_empty() {
+            return true;
+        }
+
+        let hostname = match client.hostname() {
+            Some(hostname) => hostname,
+            None => return false,
+        };
+        self.require_hostname.iter().any(|required| {
+            let required = required.trim_end_matches('.').to_lowercase();
+            match required.strip_prefix("*.") {
+                Some(domain) => hostname
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+                None => hostname == required,
+            }
+        })
+    }
 }
 
 /// Server configuration.
@@ -99,16 +285,313 @@ pub struct Server {
     /// Server directory.
     pub directory: Option<PathBuf>,
 
-    /// Start command.
-    pub command: String,
+    /// Start command, required for the process backend.
+    #[serde(default)]
+    pub command: Option<ServerCommand>,
+
+    /// Backend to run the server with.
+    #[serde(default)]
+    pub backend: Backend,
+
+    /// Systemd unit to start and stop, required for the systemd backend.
+    #[serde(default)]
+    pub systemd_unit: Option<String>,
+
+    /// Manage systemd unit of the user service manager.
+    #[serde(default)]
+    pub systemd_user: bool,
 
     /// Ingress address.
     #[serde(alias = "address_ingress")]
     pub address: SocketAddr,
 
+    /// Move server to a free internal port on start, rewriting the port in server.properties.
+    #[serde(default)]
+    pub auto_port: bool,
+
+    /// Send PROXY protocol v2 header to the server with the client address.
+    #[serde(default)]
+    pub send_proxy_v2: bool,
+
+    /// Local IP address to connect to the server from, chosen by the system if not set.
+    #[serde(default)]
+    pub bind_address: Option<IpAddr>,
+
+    /// Network interface to connect to the server through, Linux only.
+    #[serde(default)]
+    pub bind_interface: Option<String>,
+
     /// Immediately wake server when starting lazymc.
     #[serde(default)]
     pub wake_on_start: bool,
+
+    /// Briefly start server when starting lazymc to probe its status, then put it to sleep.
+    #[serde(default)]
+    pub probe_on_start: bool,
+
+    /// Method to put the server to sleep.
+    #[serde(default)]
+    pub sleep_method: SleepMethod,
+
+    /// Kill server if it doesn't quit within number of seconds after stopping, 0 to disable.
+    #[serde(default = "default_stop_timeout")]
+    pub stop_timeout: u32,
+
+    /// Methods to stop the server with, tried in order until one succeeds.
+    #[serde(default = "default_stop_methods")]
+    pub stop_methods: Vec<StopMethod>,
+
+    /// Kill server if it doesn't come online within number of seconds after starting, 0 to disable.
+    #[serde(default)]
+    pub start_timeout: u32,
+
+    /// Block waking the server for number of seconds after it failed to start in time.
+    #[serde(default = "default_start_failed_cooldown")]
+    pub start_failed_cooldown: u32,
+
+    /// Command to run before starting the server, the server isn't started if it fails.
+    #[serde(default)]
+    pub pre_start_command: Option<ServerCommand>,
+
+    /// Command to run after the server quit.
+    #[serde(default)]
+    pub post_stop_command: Option<ServerCommand>,
+
+    /// Restart server when it crashes.
+    #[serde(default)]
+    pub restart_on_crash: bool,
+
+    /// Seconds to wait before restarting a crashed server, doubled on each consecutive crash.
+    #[serde(default = "default_restart_delay")]
+    pub restart_delay: u32,
+
+    /// Stop restarting after number of consecutive crashes, 0 to always restart.
+    #[serde(default = "default_restart_max")]
+    pub restart_max: u32,
+
+    /// Accept the Minecraft EULA on behalf of the user before starting the server.
+    #[serde(default)]
+    pub accept_eula: bool,
+
+    /// Adopt server if it is already running when lazymc starts.
+    #[serde(default)]
+    pub adopt: bool,
+
+    /// Keep server running when lazymc quits, for a new instance to adopt.
+    #[serde(default)]
+    pub keep_running_on_quit: bool,
+
+    /// Don't start server if the host doesn't have enough memory available.
+    #[serde(default)]
+    pub memory_guard: bool,
+
+    /// Memory in MiB that must be available to start the server, `-Xmx` from command if not set.
+    #[serde(default)]
+    pub min_memory: Option<u32>,
+}
+
+impl Server {
+    /// Get server directory, the current directory if not configured.
+    pub fn dir(&self) -> &Path {
+        self.directory.as_deref().unwrap_or_else(|| Path::new("."))
+    }
+
+    /// Memory in bytes that must be available to start the server, if known.
+    ///
+    /// Uses the configured minimum, or the maximum heap size from the `-Xmx` command argument.
+    pub fn required_memory(&self) -> Option<u64> {
+        if let Some(min_memory) = self.min_memory {
+            return Some(min_memory as u64 * 1024 * 1024);
+        }
+
+        let size = self
+            .command
+            .as_ref()?
+            .args()
+            .iter()
+            .find_map(|arg| arg.strip_prefix("-Xmx"))?;
+        let number = size.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let multiplier: u64 = match size[number.len()..].to_ascii_lowercase().as_str() {
+            "" => 1,
+            "k" => 1024,
+            "m" => 1024 * 1024,
+            "g" => 1024 * 1024 * 1024,
+            "t" => 1024 * 1024 * 1024 * 1024,
+            _ => return None,
+        };
+        number.parse::<u64>().ok().map(|number| number * multiplier)
+    }
+}
+
+/// Default number of seconds to wait for the server to quit before killing it.
+fn default_stop_timeout() -> u32 {
+    60
+}
+
+/// Default methods to stop the server with.
+fn default_stop_methods() -> Vec<StopMethod> {
+    vec![StopMethod::Rcon, StopMethod::Signal, StopMethod::Stdin]
+}
+
+/// Default number of seconds to block waking the server after it failed to start.
+fn default_start_failed_cooldown() -> u32 {
+    60
+}
+
+/// Default number of seconds to wait before restarting a crashed server.
+fn default_restart_delay() -> u32 {
+    5
+}
+
+/// Default number of consecutive crashes to stop restarting after.
+fn default_restart_max() -> u32 {
+    5
+}
+
+/// Backend to run the server with.
+#[derive(Debug, Default, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Run the server command as child process.
+    #[default]
+    Process,
+
+    /// Start and stop a systemd unit.
+    Systemd,
+
+    /// Start and stop a server on a remote host through SSH.
+    Ssh,
+
+    /// Power on a remote machine running the server with Wake-on-LAN.
+    Wol,
+
+    /// Start and stop a server managed by Pterodactyl through its client API.
+    Pterodactyl,
+
+    /// Scale a Kubernetes workload between 0 and 1 replicas.
+    #[cfg(feature = "kubernetes")]
+    Kubernetes,
+}
+
+/// Method to put the server to sleep.
+#[derive(Debug, Default, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SleepMethod {
+    /// Stop the server process.
+    #[default]
+    Stop,
+
+    /// Freeze the server process, keeping it in memory.
+    Freeze,
+}
+
+/// Method to stop the server with.
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StopMethod {
+    /// Invoke the stop commands through RCON.
+    Rcon,
+
+    /// Send the stop command to the server console.
+    Stdin,
+
+    /// Send SIGTERM to the server process.
+    Signal,
+
+    /// Forcefully kill the server process with SIGKILL.
+    Kill,
+}
+
+/// Command, program followed by its arguments.
+///
+/// Configured as command line parsed like a shell would, or as list of arguments.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "CommandLine")]
+pub struct ServerCommand(Vec<String>);
+
+impl ServerCommand {
+    /// Program to invoke.
+    pub fn program(&self) -> &str {
+        &self.0[0]
+    }
+
+    /// Arguments to invoke program with.
+    pub fn args(&self) -> &[String] {
+        &self.0[1..]
+    }
+}
+
+/// Command as configured.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommandLine {
+    /// Command line.
+    Line(String),
+
+    /// List of arguments.
+    Args(Vec<String>),
+}
+
+impl TryFrom<CommandLine> for ServerCommand {
+    type Error = String;
+
+    fn try_from(command: CommandLine) -> Result<Self, Self::Error> {
+        let args = match command {
+            CommandLine::Line(line) => {
+                shlex::split(&line).ok_or_else(|| format!("invalid server command: {}", line))?
+            }
+            CommandLine::Args(args) => args,
+        };
+        if args.is_empty() {
+            return Err("server command is empty".into());
+        }
+        Ok(Self(args))
+    }
+}
+
+/// One or more socket addresses.
+///
+/// Configured as single address, or as list of addresses.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "AddressList")]
+pub struct Addresses(Vec<SocketAddr>);
+
+impl Addresses {
+    /// First address.
+    pub fn first(&self) -> SocketAddr {
+        self.0[0]
+    }
+
+    /// All addresses.
+    pub fn all(&self) -> &[SocketAddr] {
+        &self.0
+    }
+}
+
+/// Addresses as configured.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AddressList {
+    /// Single address.
+    One(SocketAddr),
+
+    /// List of addresses.
+    Many(Vec<SocketAddr>),
+}
+
+impl TryFrom<AddressList> for Addresses {
+    type Error = String;
+
+    fn try_from(addresses: AddressList) -> Result<Self, Self::Error> {
+        let addresses = match addresses {
+            AddressList::One(address) => vec![address],
+            AddressList::Many(addresses) => addresses,
+        };
+        if addresses.is_empty() {
+            return Err("address list is empty".into());
+        }
+        Ok(Self(addresses))
+    }
 }
 
 /// Time configuration.
@@ -120,40 +603,1270 @@ pub struct Time {
     /// Minimum time in seconds to stay online when server is started.
     #[serde(default, alias = "minimum_online_time")]
     pub min_online_time: u32,
+
+    /// Seconds before sleeping to warn online players at, if players were online.
+    #[serde(default)]
+    pub sleep_warnings: Vec<u32>,
+
+    /// Consider server idle while proxied clients send fewer bytes per second, 0 to disable.
+    #[serde(default)]
+    pub idle_throughput: u32,
 }
 
-/// Message configuration.
+/// Join configuration.
 #[derive(Debug, Deserialize)]
-pub struct Messages {
-    /// MOTD when server is sleeping.
-    pub motd_sleeping: String,
+#[serde(default)]
+pub struct Join {
+    /// Join methods, tried in order until one handles the client.
+    pub methods: Vec<Method>,
 
-    /// MOTD when server is starting.
-    pub motd_starting: String,
+    /// Hold configuration.
+    #[serde(default)]
+    pub hold: JoinHold,
 
-    /// Login message when server is starting.
-    pub login_starting: String,
+    /// Lobby configuration.
+    #[serde(default)]
+    pub lobby: JoinLobby,
+
+    /// Forward configuration.
+    #[serde(default)]
+    pub forward: JoinForward,
+
+    /// Queue configuration.
+    #[serde(default)]
+    pub queue: JoinQueue,
 }
 
-/// RCON configuration.
+impl Default for Join {
+    fn default() -> Self {
+        Self {
+            methods: vec![Method::Hold, Method::Kick],
+            hold: JoinHold::default(),
+            lobby: JoinLobby::default(),
+            forward: JoinForward::default(),
+            queue: JoinQueue::default(),
+        }
+    }
+}
+
+/// Join method.
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    /// Kick client with starting message.
+    Kick,
+
+    /// Hold client until server is online, then forward it.
+    Hold,
+
+    /// Forward client to fallback server.
+    Forward,
+
+    /// Keep client in lobby world until server is online, then transfer it.
+    Lobby,
+}
+
+/// Join hold configuration.
 #[derive(Debug, Deserialize)]
-pub struct Rcon {
-    /// Enable sleeping server through RCON.
+#[serde(default)]
+pub struct JoinHold {
+    /// Hold client for number of seconds while server starts, continue with next method afterwards.
+    pub timeout: u32,
+}
+
+impl Default for JoinHold {
+    fn default() -> Self {
+        Self { timeout: 25 }
+    }
+}
+
+/// Join lobby configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct JoinLobby {
+    /// Keep client in lobby for number of seconds while server starts, kick afterwards.
+    pub timeout: u32,
+
+    /// Message shown in lobby, first line is the title, second line the subtitle.
+    pub message: String,
+
+    /// Encrypt client connection like an online-mode server, lazymc keeps encrypting after
+    /// transfer.
+    pub encryption: bool,
+}
+
+impl Default for JoinLobby {
+    fn default() -> Self {
+        Self {
+            timeout: 10 * 60,
+            message: "§2Server is starting\n§7⌛ Please wait...".into(),
+            encryption: false,
+        }
+    }
+}
+
+/// Join forward configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct JoinForward {
+    /// Address of fallback server to forward clients to.
+    pub address: SocketAddr,
+}
+
+impl Default for JoinForward {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:25567".parse().unwrap(),
+        }
+    }
+}
+
+/// Join queue configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct JoinQueue {
+    /// Queue held and lobby clients while the server starts, and admit them in order.
     pub enabled: bool,
 
-    /// Server RCON port.
-    pub port: u16,
+    /// Number of queued clients to admit each monitor poll once the server is online.
+    pub admit: u32,
 
-    /// Server RCON password.
-    pub password: String,
+    /// Message showing the queue position, supports `{position}` and `{wait}` in seconds.
+    pub message: String,
+}
 
-    /// Randomize ingress server RCON password on each start.
-    pub randomize_password: bool,
+impl Default for JoinQueue {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            admit: 1,
+            message: "§7You are §e#{position}§7 in the queue, estimated wait §e{wait}s".into(),
+        }
+    }
 }
 
-/// Advanced configuration.
+/// Wake configuration.
 #[derive(Debug, Deserialize)]
-pub struct Advanced {
-    /// Rewrite server.properties.
-    pub rewrite_server_properties: bool,
+#[serde(default)]
+pub struct Wake {
+    /// Only wake server for players authenticated with the session server.
+    pub online_mode: bool,
+
+    /// Message to kick players with that failed to authenticate.
+    pub message_unauthenticated: String,
+
+    /// Only wake server for clients from these IP ranges, all if empty.
+    pub allow: Vec<IpNet>,
+
+    /// Message to kick clients with that may not wake the server from their IP.
+    pub message_address_not_allowed: String,
+
+    /// Don't wake server for players or IPs in the server ban lists.
+    pub bans: bool,
+
+    /// Message to kick banned players with, supports `{reason}`.
+    pub message_banned: String,
+
+    /// Only wake server for players in the server whitelist or operators list.
+    pub whitelist: bool,
+
+    /// Message to kick players with that are not whitelisted.
+    pub message_not_whitelisted: String,
+
+    /// Only wake server for operators in the server operators list or `operators`.
+    pub ops_only: bool,
+
+    /// Extra usernames or UUIDs allowed to wake the server in `ops_only` mode.
+    pub operators: Vec<String>,
+
+    /// Message to kick players with that are not an operator.
+    pub message_not_operator: String,
+
+    /// Seconds after the server stopped during which it won't wake again.
+    pub cooldown: u32,
+
+    /// Message to kick players with during the cooldown, supports `{minutes}`.
+    pub message_cooldown: String,
+
+    /// Message to kick players with while wakes are blocked after the server failed to start,
+    /// supports `{minutes}`.
+    pub message_start_failed: String,
+
+    /// Message to kick players with when the host doesn't have enough memory to start the server.
+    pub message_low_memory: String,
+
+    /// Maximum number of wakes per rate limit period, disabled if 0.
+    pub rate_limit: u32,
+
+    /// Rate limit period in seconds.
+    pub rate_limit_period: u32,
+
+    /// Message to kick players with when the rate limit is reached.
+    pub message_rate_limited: String,
+}
+
+impl Default for Wake {
+    fn default() -> Self {
+        Self {
+            online_mode: false,
+            message_unauthenticated: "Failed to verify your account, please try again.".into(),
+            allow: vec![],
+            message_address_not_allowed: "You may not start the server from your network.".into(),
+            bans: true,
+            message_banned: "You are banned from this server.\nReason: {reason}".into(),
+            whitelist: false,
+            message_not_whitelisted: "You are not whitelisted on this server.".into(),
+            ops_only: false,
+            operators: vec![],
+            message_not_operator: "Server is sleeping. Ask an operator to start the server.".into(),
+            cooldown: 0,
+            message_cooldown: "Server just stopped, try again in {minutes} minute(s).".into(),
+            message_start_failed: "Server failed to start, try again in {minutes} minute(s)."
+                .into(),
+            message_low_memory: "Server can't start right now, please try again later.".into(),
+            rate_limit: 0,
+            rate_limit_period: 3600,
+            message_rate_limited: "Server was started too often, please try again later.".into(),
+        }
+    }
+}
+
+/// Connection limits configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Maximum number of simultaneous client connections, unlimited if 0.
+    pub max_connections: u32,
+
+    /// Maximum number of simultaneous client connections from a single IP, unlimited if 0.
+    pub max_connections_per_ip: u32,
+
+    /// MOTD and kick message for clients connecting beyond the connection limit.
+    pub message_busy: String,
+
+    /// Seconds to wait for a client to send its handshake.
+    pub timeout_handshake: u32,
+
+    /// Seconds to wait for each packet from a client in status state.
+    pub timeout_status: u32,
+
+    /// Seconds to wait for each packet from a client in login state.
+    pub timeout_login: u32,
+
+    /// Seconds a client may be silent before its connection is closed, disabled if 0.
+    pub timeout_idle: u32,
+
+    /// Maximum length in bytes of packets from a client before it is playing, unlimited if 0.
+    pub max_packet_length: u32,
+
+    /// Maximum rate in bytes per second a proxied client may send, unlimited if 0.
+    pub max_upload_rate: u32,
+
+    /// Maximum rate in bytes per second a proxied client may receive, unlimited if 0.
+    pub max_download_rate: u32,
+}
+
+impl Limits {
+    /// Time to wait for a packet from a client in the given state.
+    pub fn timeout(&self, state: ClientState) -> Duration {
+        let secs = match state {
+            ClientState::Handshake => self.timeout_handshake,
+            ClientState::Status => self.timeout_status,
+            ClientState::Login => self.timeout_login,
+            ClientState::Play => return Duration::MAX,
+        };
+        Duration::from_secs(secs as u64)
+    }
+
+    /// Time a client may be silent before its connection is closed, if limited.
+    pub fn timeout_idle(&self) -> Option<Duration> {
+        Some(self.timeout_idle)
+            .filter(|secs| *secs > 0)
+            .map(|secs| Duration::from_secs(secs as u64))
+    }
+
+    /// Maximum length of packets from a client before it is playing, if limited.
+    pub fn max_packet_length(&self) -> Option<usize> {
+        Some(self.max_packet_length as usize).filter(|len| *len > 0)
+    }
+
+    /// Maximum rate in bytes per second a proxied client may send, if limited.
+    pub fn max_upload_rate(&self) -> Option<u32> {
+        Some(self.max_upload_rate).filter(|rate| *rate > 0)
+    }
+
+    /// Maximum rate in bytes per second a proxied client may receive, if limited.
+    pub fn max_download_rate(&self) -> Option<u32> {
+        Some(self.max_download_rate).filter(|rate| *rate > 0)
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_connections: 0,
+            max_connections_per_ip: 0,
+            message_busy: "Server is busy, please try again later.".into(),
+            timeout_handshake: 10,
+            timeout_status: 10,
+            timeout_login: 30,
+            timeout_idle: 120,
+            max_packet_length: 32 * 1024,
+            max_upload_rate: 0,
+            max_download_rate: 0,
+        }
+    }
+}
+
+/// Socket options configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Socket {
+    /// Disable Nagle's algorithm, to send small packets immediately.
+    pub nodelay: bool,
+
+    /// Seconds a connection is idle before sending TCP keepalive probes, and between probes,
+    /// disabled if 0.
+    pub keepalive: u32,
+
+    /// Maximum number of pending connections on the public address.
+    pub backlog: u32,
+
+    /// Socket send buffer size in bytes, system default if not set.
+    pub send_buffer_size: Option<u32>,
+
+    /// Socket receive buffer size in bytes, system default if not set.
+    pub recv_buffer_size: Option<u32>,
+
+    /// Allow other processes to listen on the public address too, to take over without downtime.
+    pub reuse_port: bool,
+}
+
+impl Socket {
+    /// Time a connection is idle before sending keepalive probes, if enabled.
+    pub fn keepalive(&self) -> Option<Duration> {
+        Some(self.keepalive)
+            .filter(|secs| *secs > 0)
+            .map(|secs| Duration::from_secs(secs as u64))
+    }
+}
+
+impl Default for Socket {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: 0,
+            backlog: 1024,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            reuse_port: false,
+        }
+    }
+}
+
+/// Status configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Status {
+    /// Player count to show while server is sleeping or starting.
+    pub players_while_sleeping: PlayerCount,
+
+    /// Max players to show before the server status is known.
+    pub default_max_players: Option<u32>,
+
+    /// Respond with the client protocol version, so the client never shows it as incompatible.
+    pub echo_protocol: bool,
+
+    /// Version name when server is sleeping, shown by clients with an incompatible version.
+    pub version_sleeping: Option<String>,
+
+    /// Version name when server is starting, shown by clients with an incompatible version.
+    pub version_starting: Option<String>,
+
+    /// Seconds to cache encoded status responses for, disabled if 0.
+    pub cache_ttl: u32,
+
+    /// Maximum number of status requests per minute per IP, unlimited if 0.
+    pub rate_limit: u32,
+
+    /// Status rewriting while server is online.
+    pub online: StatusOnline,
+
+    /// Favicon image file when server is sleeping.
+    pub favicon_sleeping: Option<PathBuf>,
+
+    /// Favicon image file when server is starting.
+    pub favicon_starting: Option<PathBuf>,
+
+    /// Show players that were last online in the player list hover.
+    pub sample_last_players: bool,
+
+    /// Custom lines to show in the player list hover, supports placeholders.
+    pub sample: Vec<String>,
+
+    /// JSON file to serve as status response when server is sleeping.
+    pub json_sleeping: Option<PathBuf>,
+
+    /// Replace placeholders in strings of the JSON status file.
+    pub json_placeholders: bool,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            players_while_sleeping: PlayerCount::default(),
+            default_max_players: None,
+            echo_protocol: false,
+            favicon_sleeping: None,
+            favicon_starting: None,
+            sample_last_players: false,
+            sample: vec![],
+            json_sleeping: None,
+            json_placeholders: false,
+            version_sleeping: None,
+            version_starting: None,
+            cache_ttl: 1,
+            rate_limit: 0,
+            online: StatusOnline::default(),
+        }
+    }
+}
+
+/// Status rewriting configuration while server is online.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct StatusOnline {
+    /// Intercept status requests while the server is online, to rewrite the response.
+    pub intercept: bool,
+
+    /// Text to append to the server MOTD.
+    pub motd_suffix: Option<String>,
+
+    /// Hide list of online players.
+    pub hide_players: bool,
+
+    /// Cap on shown max players.
+    pub max_players: Option<u32>,
+}
+
+/// Player count to show while server is sleeping or starting.
+#[derive(Debug, Default, Deserialize, Copy, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerCount {
+    /// No online players, with last known max players.
+    #[default]
+    Zero,
+
+    /// Last known online and max players.
+    LastKnown,
+
+    /// Custom online and max players.
+    Custom { online: u32, max: u32 },
+}
+
+/// GS4 query configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Query {
+    /// Enable query responder.
+    pub enabled: bool,
+
+    /// UDP address to serve query on.
+    pub address: SocketAddr,
+
+    /// World name to report.
+    pub map: String,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "0.0.0.0:25565".parse().unwrap(),
+            map: "world".into(),
+        }
+    }
+}
+
+/// Monitor configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Monitor {
+    /// Interval in seconds to poll the server status.
+    pub interval: u32,
+
+    /// Timeout in seconds for a single status poll.
+    pub timeout: u32,
+
+    /// Number of consecutive failed polls before considering an online server offline.
+    pub failures_before_offline: u32,
+
+    /// Maximum interval in seconds to poll the server status at while it is sleeping.
+    pub backoff_max: u32,
+
+    /// Don't poll the server status at all while it is sleeping.
+    pub pause_while_sleeping: bool,
+
+    /// Probe to fall back to if polling the server status fails.
+    pub probe: Probe,
+
+    /// Signals that the starting server is ready.
+    pub ready_signals: Vec<ReadySignal>,
+
+    /// Whether any or all readiness signals must be seen for the server to be ready.
+    pub ready_when: ReadyWhen,
+}
+
+impl Monitor {
+    /// Interval to poll the server status.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval.max(1) as u64)
+    }
+
+    /// Timeout for a single status poll.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.max(1) as u64)
+    }
+
+    /// Maximum interval to poll the server status at while it is sleeping.
+    pub fn backoff_max(&self) -> Duration {
+        self.interval()
+            .max(Duration::from_secs(self.backoff_max as u64))
+    }
+
+    /// Whether the starting server is ready, given the readiness signals seen.
+    pub fn ready(&self, seen: &[ReadySignal]) -> bool {
+        let mut signals = self.ready_signals.iter();
+        match self.ready_when {
+            ReadyWhen::Any => signals.any(|signal| seen.contains(signal)),
+            ReadyWhen::All => signals.all(|signal| seen.contains(signal)),
+        }
+    }
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self {
+            interval: 2,
+            timeout: 8,
+            failures_before_offline: 1,
+            backoff_max: 60,
+            pause_while_sleeping: false,
+            probe: Probe::default(),
+            ready_signals: vec![ReadySignal::Status, ReadySignal::Log],
+            ready_when: ReadyWhen::default(),
+        }
+    }
+}
+
+/// Probe to check whether the server is online with, if polling its status fails.
+#[derive(Debug, Default, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Probe {
+    /// Only poll the server status.
+    #[default]
+    Status,
+
+    /// Consider the server online if a TCP connection succeeds.
+    Tcp,
+
+    /// Consider the server online if it responds to a login attempt.
+    Login,
+}
+
+/// Signal that the starting server is ready.
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadySignal {
+    /// Server port accepts a TCP connection.
+    Port,
+
+    /// Server responds to a status poll, or to the configured probe.
+    Status,
+
+    /// Logging in to server RCON succeeds.
+    Rcon,
+
+    /// Server logs it is done starting, or its backend reports it ready.
+    Log,
+}
+
+/// Combination of readiness signals for the server to be ready.
+#[derive(Debug, Default, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadyWhen {
+    /// Ready once any signal is seen.
+    #[default]
+    Any,
+
+    /// Ready once all signals are seen.
+    All,
+}
+
+/// Admin control configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Admin {
+    /// Enable admin control interface.
+    pub enabled: bool,
+
+    /// TCP address to serve admin control on.
+    pub address: SocketAddr,
+
+    /// Password clients must authenticate with, none if empty.
+    pub password: String,
+}
+
+impl Default for Admin {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:25577".parse().unwrap(),
+            password: "".into(),
+        }
+    }
+}
+
+/// In-game commands configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Ingame {
+    /// Sniff chat of proxied players for lazymc commands.
+    pub enabled: bool,
+
+    /// Chat prefix of lazymc commands.
+    pub prefix: String,
+
+    /// Additional operators allowed to use commands, by username or UUID.
+    pub operators: Vec<String>,
+}
+
+impl Default for Ingame {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            prefix: "!lazymc".into(),
+            operators: vec![],
+        }
+    }
+}
+
+/// Bedrock configuration, for servers with Geyser.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Bedrock {
+    /// Enable Bedrock ping responder.
+    pub enabled: bool,
+
+    /// UDP address to serve Bedrock pings on, must be the Geyser address.
+    pub address: SocketAddr,
+
+    /// Wake server when a Bedrock client connects.
+    pub wake: bool,
+
+    /// Bedrock protocol version to report.
+    pub protocol: u32,
+
+    /// Bedrock version name to report.
+    pub version: String,
+}
+
+impl Default for Bedrock {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "0.0.0.0:19132".parse().unwrap(),
+            wake: false,
+            protocol: 589,
+            version: "1.20.0".into(),
+        }
+    }
+}
+
+/// Analytics configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Analytics {
+    /// Record status pings.
+    pub enabled: bool,
+
+    /// File to append recorded pings to, as JSON lines.
+    pub file: Option<PathBuf>,
+
+    /// Interval in seconds to log a summary of recorded pings at, disabled if 0.
+    pub summary_interval: u32,
+
+    /// Interval in seconds to sample server process resource usage at, disabled if 0.
+    pub resource_interval: u32,
+}
+
+impl Default for Analytics {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            file: None,
+            summary_interval: 3600,
+            resource_interval: 60,
+        }
+    }
+}
+
+/// Schedule configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    /// Restart running server at these cron expressions, in local time.
+    pub restart: Vec<CronSchedule>,
+
+    /// Seconds before a scheduled restart to warn online players at.
+    pub restart_warnings: Vec<u32>,
+
+    /// Message to warn online players with before a scheduled restart, supports `{time}`.
+    pub message_restart: String,
+
+    /// Time windows to keep the server awake in.
+    pub keep_awake: Vec<AwakeWindow>,
+}
+
+impl Schedule {
+    /// Whether the server should be kept awake now.
+    pub fn keep_awake(&self) -> bool {
+        let now = Local::now().naive_local();
+        self.keep_awake.iter().any(|window| window.contains(now))
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            restart: vec![],
+            restart_warnings: vec![300, 60, 10],
+            message_restart: "Server restarts in {time}".into(),
+            keep_awake: vec![],
+        }
+    }
+}
+
+/// Time window to keep the server awake in, in local time.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "AwakeWindowConfig")]
+pub struct AwakeWindow {
+    /// Days of the week the window starts on, all days if empty.
+    days: Vec<Weekday>,
+
+    /// Start time of the window.
+    from: NaiveTime,
+
+    /// End time of the window, on the next day if before the start time.
+    to: NaiveTime,
+}
+
+impl AwakeWindow {
+    /// Whether the given time is in this window.
+    fn contains(&self, time: NaiveDateTime) -> bool {
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let (day, time) = (time.weekday(), time.time());
+        if self.from <= self.to {
+            starts_on(day) && time >= self.from && time < self.to
+        } else {
+            (starts_on(day) && time >= self.from) || (starts_on(day.pred()) && time < self.to)
+        }
+    }
+}
+
+/// Time window to keep the server awake in, as configured.
+#[derive(Deserialize)]
+struct AwakeWindowConfig {
+    /// Days of the week, such as `Mon`.
+    #[serde(default)]
+    days: Vec<String>,
+
+    /// Start time, such as `17:00`.
+    from: String,
+
+    /// End time, such as `23:00`.
+    to: String,
+}
+
+impl TryFrom<AwakeWindowConfig> for AwakeWindow {
+    type Error = String;
+
+    fn try_from(window: AwakeWindowConfig) -> Result<Self, Self::Error> {
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| format!("invalid time: {}", time))
+        };
+        let days = window
+            .days
+            .iter()
+            .map(|day| Weekday::from_str(day).map_err(|_| format!("invalid day: {}", day)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            days,
+            from: time(&window.from)?,
+            to: time(&window.to)?,
+        })
+    }
+}
+
+/// Cron expression schedule.
+///
+/// Supports 5 fields from minutes to day of week, or 6 or 7 fields including seconds and years.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CronSchedule(pub cron::Schedule);
+
+impl TryFrom<String> for CronSchedule {
+    type Error = String;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        // Default to first second if seconds are not specified
+        let full = if expression.split_whitespace().count() == 5 {
+            format!("0 {}", expression)
+        } else {
+            expression.clone()
+        };
+        cron::Schedule::from_str(&full)
+            .map(Self)
+            .map_err(|err| format!("invalid cron expression '{}': {}", expression, err))
+    }
+}
+
+/// Backups configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Backups {
+    /// Back up worlds after the server is put to sleep.
+    pub enabled: bool,
+
+    /// Directory to store backups in, relative to the server directory.
+    pub directory: PathBuf,
+
+    /// World directories to back up, relative to the server directory.
+    pub worlds: Vec<String>,
+
+    /// Number of most recent backups to keep, all if 0.
+    pub keep_last: u32,
+
+    /// Number of days to keep backups for, forever if 0.
+    pub keep_days: u32,
+
+    /// Interval in seconds to back up worlds while the server is online, disabled if 0.
+    ///
+    /// Requires RCON.
+    pub live_interval: u32,
+}
+
+impl Default for Backups {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: "backups".into(),
+            worlds: vec![
+                "world".into(),
+                "world_nether".into(),
+                "world_the_end".into(),
+            ],
+            keep_last: 10,
+            keep_days: 0,
+            live_interval: 0,
+        }
+    }
+}
+
+/// Kubernetes backend configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Kubernetes {
+    /// Kind of workload to scale.
+    pub kind: WorkloadKind,
+
+    /// Name of workload to scale.
+    pub name: String,
+
+    /// Namespace of workload, the namespace lazymc runs in if not set.
+    pub namespace: Option<String>,
+
+    /// Kubernetes API server URL.
+    pub api_url: String,
+
+    /// Service account token file to authenticate with.
+    pub token_file: PathBuf,
+
+    /// CA certificate file to verify the API server with.
+    pub ca_file: PathBuf,
+}
+
+impl Default for Kubernetes {
+    fn default() -> Self {
+        Self {
+            kind: WorkloadKind::Deployment,
+            name: "minecraft".into(),
+            namespace: None,
+            api_url: "https://kubernetes.default.svc".into(),
+            token_file: "/var/run/secrets/kubernetes.io/serviceaccount/token".into(),
+            ca_file: "/var/run/secrets/kubernetes.io/serviceaccount/ca.crt".into(),
+        }
+    }
+}
+
+/// Kind of Kubernetes workload.
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkloadKind {
+    /// Deployment.
+    Deployment,
+
+    /// StatefulSet.
+    StatefulSet,
+}
+
+impl WorkloadKind {
+    /// Resource name in the Kubernetes API.
+    #[cfg_attr(not(feature = "kubernetes"), allow(dead_code))]
+    pub fn resource(self) -> &'static str {
+        match self {
+            Self::Deployment => "deployments",
+            Self::StatefulSet => "statefulsets",
+        }
+    }
+}
+
+/// SSH backend configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Ssh {
+    /// Remote host to connect to, optionally with user such as `user@host`.
+    pub host: String,
+
+    /// SSH port.
+    pub port: u16,
+
+    /// Private key file to authenticate with.
+    pub identity_file: Option<PathBuf>,
+
+    /// Remote command to start the server with, must not block.
+    pub start_command: String,
+
+    /// Remote command to stop the server with.
+    pub stop_command: String,
+
+    /// Remote command succeeding while the server is running.
+    pub running_command: String,
+}
+
+impl Default for Ssh {
+    fn default() -> Self {
+        Self {
+            host: "minecraft@localhost".into(),
+            port: 22,
+            identity_file: None,
+            start_command: "systemctl start minecraft".into(),
+            stop_command: "systemctl stop minecraft".into(),
+            running_command: "systemctl is-active --quiet minecraft".into(),
+        }
+    }
+}
+
+/// Wake-on-LAN backend configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Wol {
+    /// MAC address of the machine to wake.
+    pub mac: Option<MacAddress>,
+
+    /// Address to send the magic packet to.
+    pub broadcast: SocketAddr,
+
+    /// Command to shut down the machine with, such as through SSH.
+    ///
+    /// The server is stopped through RCON if not set.
+    pub stop_command: Option<ServerCommand>,
+}
+
+impl Default for Wol {
+    fn default() -> Self {
+        Self {
+            mac: None,
+            broadcast: "255.255.255.255:9".parse().unwrap(),
+            stop_command: None,
+        }
+    }
+}
+
+/// Pterodactyl backend configuration.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Pterodactyl {
+    /// Panel URL.
+    pub url: String,
+
+    /// Server identifier, as shown in the panel URL of the server.
+    pub server: String,
+
+    /// Client API key.
+    pub api_key: String,
+}
+
+/// MAC address, such as `01:23:45:67:89:ab`.
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct MacAddress(pub [u8; 6]);
+
+impl TryFrom<String> for MacAddress {
+    type Error = String;
+
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        let octets = address
+            .split([':', '-'])
+            .map(|octet| u8::from_str_radix(octet, 16))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .and_then(|octets| <[u8; 6]>::try_from(octets).ok())
+            .ok_or_else(|| format!("invalid MAC address '{}'", address))?;
+        Ok(Self(octets))
+    }
+}
+
+/// Event hooks configuration.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    /// Command to run when a player attempts to join while the server is sleeping.
+    pub on_join: Option<ServerCommand>,
+
+    /// Command to run when the server came online.
+    pub on_online: Option<ServerCommand>,
+
+    /// Command to run when the server was put to sleep.
+    pub on_sleep: Option<ServerCommand>,
+
+    /// Command to run when the server crashed.
+    pub on_crash: Option<ServerCommand>,
+}
+
+/// Message configuration.
+#[derive(Debug, Deserialize)]
+pub struct Messages {
+    /// MOTD when server is sleeping, supports placeholders.
+    pub motd_sleeping: String,
+
+    /// MOTD when server is starting, supports placeholders.
+    pub motd_starting: String,
+
+    /// MOTD when server crashed and is restarting, supports placeholders.
+    pub motd_crashed: String,
+
+    /// Login message when server is starting, supports placeholders.
+    pub login_starting: String,
+
+    /// Message to warn online players with before sleeping, supports `{time}`.
+    pub sleep_warning: String,
+
+    /// Message to kick remaining players with when stopping the server.
+    pub kick_stopping: String,
+
+    /// Text for the `{eta}` placeholder, supports `{seconds}`.
+    pub eta: String,
+
+    /// Text for the `{eta}` placeholder when the start time is unknown or overdue.
+    pub eta_unknown: String,
+
+    /// Text for the `{progress}` placeholder, supports `{percent}`.
+    pub progress: String,
+
+    /// Text for the `{progress}` placeholder when the start progress is unknown.
+    pub progress_unknown: String,
+
+    /// Text for a time in the past, supports `{time}`.
+    pub time_ago: String,
+
+    /// Text for a time that never happened.
+    pub time_never: String,
+
+    /// Text for an unknown time or value.
+    pub time_unknown: String,
+
+    /// Message overrides per hostname clients connect with.
+    #[serde(default)]
+    pub hosts: HashMap<String, HostMessages>,
+}
+
+impl Messages {
+    /// Get message overrides for hostname the client connected with, if any.
+    fn host(&self, client: &Client) -> Option<&HostMessages> {
+        self.hosts.get(client.hostname()?.as_str())
+    }
+
+    /// Hostname key of message overrides for the client, if any.
+    pub fn host_key(&self, client: &Client) -> Option<&str> {
+        self.hosts
+            .get_key_value(client.hostname()?.as_str())
+            .map(|(host, _)| host.as_str())
+    }
+
+    /// MOTD when server is sleeping, for the client hostname.
+    pub fn motd_sleeping(&self, client: &Client) -> &str {
+        self.host(client)
+            .and_then(|host| host.motd_sleeping.as_deref())
+            .unwrap_or(&self.motd_sleeping)
+    }
+
+    /// MOTD when server is starting, for the client hostname.
+    pub fn motd_starting(&self, client: &Client) -> &str {
+        self.host(client)
+            .and_then(|host| host.motd_starting.as_deref())
+            .unwrap_or(&self.motd_starting)
+    }
+
+    /// Login message when server is starting, for the client hostname.
+    pub fn login_starting(&self, client: &Client) -> &str {
+        self.host(client)
+            .and_then(|host| host.login_starting.as_deref())
+            .unwrap_or(&self.login_starting)
+    }
+}
+
+/// Message overrides for a hostname.
+#[derive(Debug, Deserialize)]
+pub struct HostMessages {
+    /// MOTD when server is sleeping.
+    pub motd_sleeping: Option<String>,
+
+    /// MOTD when server is starting.
+    pub motd_starting: Option<String>,
+
+    /// Login message when server is starting.
+    pub login_starting: Option<String>,
+}
+
+/// RCON configuration.
+#[derive(Debug, Deserialize)]
+pub struct Rcon {
+    /// Enable sleeping server through RCON.
+    pub enabled: bool,
+
+    /// Server RCON port.
+    pub port: u16,
+
+    /// Server RCON password.
+    pub password: String,
+
+    /// Randomize ingress server RCON password on each start.
+    pub randomize_password: bool,
+
+    /// Commands to stop the server with in order, `wait <seconds>` to wait in between.
+    #[serde(default = "default_stop_commands")]
+    pub stop_commands: Vec<String>,
+
+    /// Time in seconds to wait for connecting and logging in.
+    #[serde(default = "default_rcon_connect_timeout")]
+    pub connect_timeout: u32,
+
+    /// Time in seconds to wait for a command response.
+    #[serde(default = "default_rcon_command_timeout")]
+    pub command_timeout: u32,
+
+    /// Number of times to retry connecting if it failed.
+    #[serde(default = "default_rcon_connect_retries")]
+    pub connect_retries: u32,
+
+    /// Poll online players with the list command, instead of trusting the status response.
+    #[serde(default)]
+    pub poll_players: bool,
+
+    /// Commands to invoke once the server came online, `wait <seconds>` to wait in between.
+    #[serde(default)]
+    pub wake_commands: Vec<String>,
+
+    /// Commands to invoke before sleeping the server, `wait <seconds>` to wait in between.
+    #[serde(default)]
+    pub sleep_commands: Vec<String>,
+
+    /// Public RCON endpoint.
+    #[serde(default)]
+    pub public: RconPublic,
+}
+
+/// Default RCON commands to stop the server with.
+fn default_stop_commands() -> Vec<String> {
+    vec!["save-all".into(), "stop".into()]
+}
+
+/// Default RCON connect timeout in seconds.
+fn default_rcon_connect_timeout() -> u32 {
+    5
+}
+
+/// Default RCON command timeout in seconds.
+fn default_rcon_command_timeout() -> u32 {
+    10
+}
+
+/// Default number of RCON connect retries.
+fn default_rcon_connect_retries() -> u32 {
+    3
+}
+
+/// Public RCON endpoint configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RconPublic {
+    /// Expose an RCON endpoint, forwarding commands to the server.
+    pub enabled: bool,
+
+    /// Address to serve RCON on.
+    pub address: SocketAddr,
+
+    /// Password clients must log in with.
+    pub password: String,
+
+    /// Queue commands while the server is not online, instead of responding with an error.
+    pub queue: bool,
+
+    /// Maximum number of queued commands.
+    pub queue_size: u32,
+}
+
+impl Default for RconPublic {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "0.0.0.0:25576".parse().unwrap(),
+            password: "".into(),
+            queue: true,
+            queue_size: 32,
+        }
+    }
+}
+
+/// Advanced configuration.
+#[derive(Debug, Deserialize)]
+pub struct Advanced {
+    /// Rewrite server.properties.
+    pub rewrite_server_properties: bool,
+
+    /// Persist server state across lazymc restarts.
+    #[serde(default = "default_persist_state")]
+    pub persist_state: bool,
+
+    /// Seconds to wait for open client connections to close when quitting.
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout: u32,
+}
+
+impl Advanced {
+    /// Time to wait for open client connections to close when quitting.
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout as u64)
+    }
+}
+
+/// Default whether to persist server state across restarts.
+fn default_persist_state() -> bool {
+    true
+}
+
+/// Default seconds to wait for open client connections to close when quitting.
+fn default_drain_timeout() -> u32 {
+    60
 }
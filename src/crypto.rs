@@ -0,0 +1,224 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use aes::Aes128;
+use rand::RngCore;
+use rsa::pkcs8::EncodePublicKey;
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// RSA key size in bits, as used by the Notchian server.
+const KEY_BITS: usize = 1024;
+
+/// Verify token length in bytes.
+const VERIFY_TOKEN_LEN: usize = 4;
+
+/// Server key pair, generated once on first use.
+static KEY_PAIR: OnceLock<KeyPair> = OnceLock::new();
+
+/// Server RSA key pair, used for the encryption exchange during login.
+pub struct KeyPair {
+    /// Private key.
+    private: RsaPrivateKey,
+
+    /// Public key in DER format, as sent to clients.
+    public_der: Vec<u8>,
+}
+
+impl KeyPair {
+    /// Generate new key pair.
+    fn generate() -> Result<Self, ()> {
+        let private = RsaPrivateKey::new(&mut rand::thread_rng(), KEY_BITS).map_err(|err| {
+            error!(target: "lazymc", "Failed to generate server key pair: {}", err);
+        })?;
+        let public_der = private
+            .to_public_key()
+            .to_public_key_der()
+            .map_err(|_| ())?
+            .into_vec();
+        Ok(Self {
+            private,
+            public_der,
+        })
+    }
+
+    /// Public key in DER format.
+    pub fn public_der(&self) -> &[u8] {
+        &self.public_der
+    }
+
+    /// Decrypt data a client encrypted with our public key.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, ()> {
+        self.private.decrypt(Pkcs1v15Encrypt, data).map_err(|_| ())
+    }
+}
+
+/// Get server key pair.
+///
+/// Generates the key pair on first use, which may take a moment.
+pub async fn key_pair() -> Result<&'static KeyPair, ()> {
+    if let Some(key_pair) = KEY_PAIR.get() {
+        return Ok(key_pair);
+    }
+
+    debug!(target: "lazymc", "Generating server key pair for encryption");
+    let key_pair = tokio::task::spawn_blocking(KeyPair::generate)
+        .await
+        .map_err(|_| ())??;
+    Ok(KEY_PAIR.get_or_init(|| key_pair))
+}
+
+/// Generate random verify token for encryption request.
+pub fn verify_token() -> Vec<u8> {
+    let mut token = vec![0; VERIFY_TOKEN_LEN];
+    rand::thread_rng().fill_bytes(&mut token);
+    token
+}
+
+/// Reader decrypting an AES/CFB8 encrypted stream, once encryption is enabled.
+pub struct CryptReader<R> {
+    /// Inner reader.
+    inner: R,
+
+    /// Decryptor, if encryption is enabled.
+    cipher: Option<cfb8::Decryptor<Aes128>>,
+}
+
+impl<R> CryptReader<R> {
+    /// Construct new reader, without encryption.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            cipher: None,
+        }
+    }
+
+    /// Enable encryption with the given shared secret, which is used as both key and IV.
+    pub fn enable(&mut self, shared_secret: &[u8]) -> Result<(), ()> {
+        self.cipher =
+            Some(cfb8::Decryptor::new_from_slices(shared_secret, shared_secret).map_err(|_| ())?);
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CryptReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+
+        // Decrypt newly read bytes in place
+        if let Some(cipher) = this.cipher.as_mut() {
+            for byte in &mut buf.filled_mut()[filled..] {
+                cipher.decrypt_block_mut(GenericArray::from_mut_slice(std::slice::from_mut(byte)));
+            }
+        }
+
+        result
+    }
+}
+
+/// Writer encrypting into an AES/CFB8 encrypted stream, once encryption is enabled.
+pub struct CryptWriter<W> {
+    /// Inner writer.
+    inner: W,
+
+    /// Encryptor, if encryption is enabled.
+    cipher: Option<cfb8::Encryptor<Aes128>>,
+
+    /// Encrypted bytes not yet written to the inner writer.
+    pending: Vec<u8>,
+
+    /// Number of bytes of the last write that are encrypted, but not reported as written yet.
+    unreported: usize,
+}
+
+impl<W> CryptWriter<W> {
+    /// Construct new writer, without encryption.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            cipher: None,
+            pending: Vec::new(),
+            unreported: 0,
+        }
+    }
+
+    /// Enable encryption with the given shared secret, which is used as both key and IV.
+    pub fn enable(&mut self, shared_secret: &[u8]) -> Result<(), ()> {
+        self.cipher =
+            Some(cfb8::Encryptor::new_from_slices(shared_secret, shared_secret).map_err(|_| ())?);
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + Unpin> CryptWriter<W> {
+    /// Write pending encrypted bytes to the inner writer.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            match Pin::new(&mut self.inner).poll_write(cx, &self.pending) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => {
+                    self.pending.drain(..n);
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for CryptWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        let cipher = match this.cipher.as_mut() {
+            Some(cipher) => cipher,
+            None => return Pin::new(&mut this.inner).poll_write(cx, buf),
+        };
+
+        // Encrypt new data once, the cipher state advances on encrypt. A caller retries with the
+        // same data after a pending write, which is reported as written once it is flushed.
+        if this.unreported == 0 {
+            this.pending.extend_from_slice(buf);
+            for byte in &mut this.pending {
+                cipher.encrypt_block_mut(GenericArray::from_mut_slice(std::slice::from_mut(byte)));
+            }
+            this.unreported = buf.len();
+        }
+
+        if this.poll_pending(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(std::mem::take(&mut this.unreported)))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.poll_pending(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.poll_pending(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
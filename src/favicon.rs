@@ -0,0 +1,80 @@
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use image::imageops::FilterType;
+use image::ImageOutputFormat;
+
+use crate::config::Config;
+
+/// Favicon size in pixels, as required by the client.
+const FAVICON_SIZE: u32 = 64;
+
+/// Loaded custom favicons.
+static FAVICONS: Mutex<Option<Arc<Favicons>>> = Mutex::new(None);
+
+/// Custom favicons, as base64 encoded PNG data URIs.
+#[derive(Debug, Default)]
+pub struct Favicons {
+    /// Favicon when server is sleeping.
+    pub sleeping: Option<String>,
+
+    /// Favicon when server is starting.
+    pub starting: Option<String>,
+}
+
+/// Load custom favicons from configured image files.
+///
+/// Called on start, and again to reload them. Prints an error for images that fail to load.
+pub fn load(config: &Config) {
+    let favicons = Favicons {
+        sleeping: config
+            .status
+            .favicon_sleeping
+            .as_deref()
+            .and_then(load_file),
+        starting: config
+            .status
+            .favicon_starting
+            .as_deref()
+            .and_then(load_file),
+    };
+    FAVICONS.lock().unwrap().replace(Arc::new(favicons));
+}
+
+/// Get loaded custom favicons.
+pub fn favicons() -> Arc<Favicons> {
+    FAVICONS.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Load image file as favicon.
+///
+/// Resizes the image to the favicon size, and encodes it as PNG data URI.
+fn load_file(path: &Path) -> Option<String> {
+    match encode(path) {
+        Ok(favicon) => Some(favicon),
+        Err(err) => {
+            error!(target: "lazymc", "Failed to load favicon from {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Encode image file as favicon PNG data URI.
+fn encode(path: &Path) -> Result<String, image::ImageError> {
+    let image = image::open(path)?;
+    let image = if image.width() != FAVICON_SIZE || image.height() != FAVICON_SIZE {
+        image.resize_exact(FAVICON_SIZE, FAVICON_SIZE, FilterType::Lanczos3)
+    } else {
+        image
+    };
+
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageOutputFormat::Png)?;
+
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+    ))
+}
@@ -0,0 +1,124 @@
+use std::net::IpAddr;
+use std::process::ExitStatus;
+
+use tokio::process::Command;
+
+use crate::config::{Config, ServerCommand};
+
+/// Server lifecycle event to run hook commands on.
+#[derive(Debug)]
+pub enum Event {
+    /// Player attempted to join while the server is sleeping.
+    Join {
+        /// Player username.
+        username: Option<String>,
+
+        /// Player IP address.
+        ip: Option<IpAddr>,
+    },
+
+    /// Server came online.
+    Online,
+
+    /// Server was put to sleep.
+    Sleep,
+
+    /// Server crashed.
+    Crash {
+        /// Server process exit status, if run as process.
+        status: Option<ExitStatus>,
+    },
+}
+
+impl Event {
+    /// Event name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Join { .. } => "join",
+            Self::Online => "online",
+            Self::Sleep => "sleep",
+            Self::Crash { .. } => "crash",
+        }
+    }
+
+    /// Configured hook command for this event, if any.
+    fn command<'a>(&self, config: &'a Config) -> Option<&'a ServerCommand> {
+        match self {
+            Self::Join { .. } => config.hooks.on_join.as_ref(),
+            Self::Online => config.hooks.on_online.as_ref(),
+            Self::Sleep => config.hooks.on_sleep.as_ref(),
+            Self::Crash { .. } => config.hooks.on_crash.as_ref(),
+        }
+    }
+
+    /// Environment variables passing event details to the hook command.
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![("LAZYMC_EVENT", self.name().to_string())];
+        match self {
+            Self::Join { username, ip } => {
+                if let Some(username) = username {
+                    env.push(("LAZYMC_PLAYER", username.clone()));
+                }
+                if let Some(ip) = ip {
+                    env.push(("LAZYMC_PLAYER_IP", ip.to_string()));
+                }
+            }
+            Self::Crash { status } => {
+                if let Some(status) = status {
+                    env.push(("LAZYMC_EXIT_STATUS", status.to_string()));
+                }
+                if let Some(code) = status.and_then(|status| status.code()) {
+                    env.push(("LAZYMC_EXIT_CODE", code.to_string()));
+                }
+            }
+            Self::Online | Self::Sleep => {}
+        }
+        env
+    }
+}
+
+/// Fire event, running its hook command in the background if configured.
+pub fn fire(config: &Config, event: Event) {
+    let command = match event.command(config) {
+        Some(command) => command.clone(),
+        None => return,
+    };
+
+    let directory = config.server.directory.clone();
+    tokio::spawn(async move {
+        let mut cmd = Command::new(command.program());
+        cmd.args(command.args()).envs(event.env());
+        if let Some(dir) = directory {
+            cmd.current_dir(dir);
+        }
+        run(cmd, event.name()).await;
+    });
+}
+
+/// Run hook command in the server directory and wait for it to complete.
+///
+/// Returns `true` if it succeeded.
+pub async fn run_command(config: &Config, name: &str, command: &ServerCommand) -> bool {
+    let mut cmd = Command::new(command.program());
+    cmd.args(command.args());
+    if let Some(ref dir) = config.server.directory {
+        cmd.current_dir(dir);
+    }
+    run(cmd, name).await
+}
+
+/// Run command and wait for it to complete, returns `true` if it succeeded.
+async fn run(mut cmd: Command, name: &str) -> bool {
+    info!(target: "lazymc", "Running {} command...", name);
+    match cmd.status().await {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            error!(target: "lazymc", "{} command failed (status: {})", name, status);
+            false
+        }
+        Err(err) => {
+            error!(target: "lazymc", "Failed to run {} command: {}", name, err);
+            false
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use tokio::io::{self, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::Config;
+use crate::mc::whitelist::{Players, OPS_FILE};
+use crate::proto::{self, Client, ClientState};
+use crate::protocol::Protocol;
+use crate::proxy::{self, Metered, Throttled};
+use crate::server::ServerState;
+use crate::types;
+
+/// Proxy a logging in client to the online server, sniffing its chat for lazymc commands.
+///
+/// The handshake must already be read, `buf` holds what the client sent after it. Stops sniffing
+/// and proxies as-is once the server enables encryption.
+pub async fn proxy(
+    client: Client,
+    mut inbound: TcpStream,
+    handshake: &[u8],
+    mut buf: BytesMut,
+    config: Arc<Config>,
+    server: Arc<ServerState>,
+) -> Result<(), ()> {
+    let mut outbound = proxy::connect_to_server(&config, &client)
+        .await
+        .map_err(|_| ())?;
+    outbound.write_all(handshake).await.map_err(|_| ())?;
+
+    let protocol = Protocol::of(&client);
+    let chat_id = protocol.chat_packet_id();
+    let encrypted = AtomicBool::new(false);
+
+    let proxied = server.track_proxied();
+    let (ri, mut wi) = inbound.split();
+    let mut ri = Throttled::new(
+        Metered::new(ri, proxied.traffic()),
+        config.limits.max_upload_rate(),
+    );
+    let (ro, mut wo) = outbound.split();
+    let mut ro = Throttled::new(ro, config.limits.max_download_rate());
+
+    let client_to_server = async {
+        // Forward packets one by one while unencrypted, sniffing login and chat
+        while !encrypted.load(Ordering::Relaxed) {
+            let (packet, raw) = match proto::read_packet(&client, &mut buf, &mut ri).await? {
+                Some(packet) => packet,
+                None => return wo.shutdown().await.map_err(|_| ()),
+            };
+            wo.write_all(&raw).await.map_err(|_| ())?;
+
+            match client.state() {
+                ClientState::Login if packet.id == proto::LOGIN_PACKET_ID_LOGIN_START => {
+                    if let Ok(login_start) = protocol.decode_login_start(&packet.data) {
+                        client.set_username(login_start.name);
+                    }
+                }
+                ClientState::Play if Some(packet.id) == chat_id => {
+                    let message = protocol.decode_chat(&packet.data).unwrap_or_default();
+                    if let Some(args) = message.strip_prefix(&config.ingame.prefix) {
+                        let username = client.username().unwrap_or_default();
+                        tokio::spawn(invoke(
+                            config.clone(),
+                            server.clone(),
+                            username,
+                            args.trim().to_string(),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        wo.write_all(&buf).await.map_err(|_| ())?;
+        io::copy(&mut ri, &mut wo).await.map_err(|_| ())?;
+        wo.shutdown().await.map_err(|_| ())
+    };
+
+    let server_to_client = async {
+        // Track compression and state until the client is logged in
+        let mut server_buf = BytesMut::new();
+        while client.state() == ClientState::Login {
+            let (packet, raw) = match proto::read_packet(&client, &mut server_buf, &mut ro).await? {
+                Some(packet) => packet,
+                None => return wi.shutdown().await.map_err(|_| ()),
+            };
+
+            match packet.id {
+                proto::LOGIN_PACKET_ID_ENCRYPTION_REQUEST => {
+                    debug!(target: "lazymc", "Server enabled encryption, not sniffing commands");
+                    encrypted.store(true, Ordering::Relaxed);
+                    wi.write_all(&raw).await.map_err(|_| ())?;
+                    break;
+                }
+                proto::LOGIN_PACKET_ID_SET_COMPRESSION => {
+                    let (_, threshold) = types::read_var_int(&packet.data)?;
+                    wi.write_all(&raw).await.map_err(|_| ())?;
+                    client.set_compression(threshold);
+                }
+                proto::LOGIN_PACKET_ID_LOGIN_SUCCESS => {
+                    wi.write_all(&raw).await.map_err(|_| ())?;
+                    client.set_state(ClientState::Play);
+                }
+                _ => wi.write_all(&raw).await.map_err(|_| ())?,
+            }
+        }
+
+        wi.write_all(&server_buf).await.map_err(|_| ())?;
+        io::copy(&mut ro, &mut wi).await.map_err(|_| ())?;
+        wi.shutdown().await.map_err(|_| ())
+    };
+
+    tokio::try_join!(client_to_server, server_to_client)?;
+
+    Ok(())
+}
+
+/// Invoke a lazymc command sent in chat by the given player.
+///
+/// Ignores commands from players that aren't operators.
+async fn invoke(config: Arc<Config>, server: Arc<ServerState>, username: String, args: String) {
+    let mut operators = Players::load(config.server.dir(), &[OPS_FILE]);
+    operators.extend(&config.ingame.operators);
+    if !operators.contains(&username, None) {
+        debug!(target: "lazymc", "Ignoring in-game command from {}, not an operator", username);
+        return;
+    }
+
+    info!(target: "lazymc", "Player {} invoked in-game command: {}", username, args);
+    match args.as_str() {
+        "sleep" => {
+            reply(&config, &username, "§7Putting server to sleep...").await;
+            if !server.sleep_server(&config).await {
+                reply(&config, &username, "§cFailed to put server to sleep").await;
+            }
+        }
+        "status" => {
+            let players = server
+                .clone_status()
+                .map(|status| status.players.online)
+                .unwrap_or(0);
+            let message = format!(
+                "§7{} players online, woken {} times today",
+                players,
+                server.wakes_today(),
+            );
+            reply(&config, &username, &message).await;
+        }
+        _ => reply(&config, &username, "§7Commands: sleep, status").await,
+    }
+}
+
+/// Reply to player through RCON.
+#[allow(unused_variables)]
+async fn reply(config: &Config, username: &str, message: &str) {
+    #[cfg(feature = "rcon")]
+    if let Ok(json) = crate::chat::message(message).to_json() {
+        crate::server::rcon_command(config, &format!("tellraw {} {}", username, json)).await;
+    }
+}
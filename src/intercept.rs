@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use minecraft_protocol::decoder::Decoder;
+use minecraft_protocol::version::v1_14_4::handshake::Handshake;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use crate::chat;
+use crate::config::Config;
+use crate::ingame;
+use crate::proto::{self, Client, ClientState, RawPacket};
+use crate::proxy;
+use crate::server::ServerState;
+use crate::types;
+
+/// Proxy client to the online server, intercepting status requests to rewrite the response, and
+/// sniffing chat of logging in clients for in-game commands, if enabled.
+///
+/// Other clients are proxied as-is.
+pub async fn serve(
+    client: Client,
+    mut inbound: TcpStream,
+    config: Arc<Config>,
+    server: Arc<ServerState>,
+) -> Result<(), ()> {
+    let (mut reader, _) = inbound.split();
+    let mut buf = BytesMut::new();
+
+    // Read handshake to find next state
+    let timeout = config.limits.timeout(ClientState::Handshake);
+    let (packet, raw) =
+        match proto::read_packet_timeout(&client, &mut buf, &mut reader, timeout).await? {
+            Some(packet) => packet,
+            None => return Ok(()),
+        };
+    let handshake = Handshake::decode(&mut packet.data.as_slice()).map_err(|_| ())?;
+
+    // Sniff in-game commands of logging in clients
+    if handshake.next_state == ClientState::Login.to_id() && config.ingame.enabled {
+        client.set_state(ClientState::Login);
+        client.set_protocol(handshake.protocol_version);
+        client.set_hostname(&handshake.server_addr);
+        return ingame::proxy(client, inbound, &raw, buf, config, server).await;
+    }
+
+    // Proxy anything but status requests
+    let mut queue = raw;
+    queue.extend_from_slice(&buf);
+    if handshake.next_state != ClientState::Status.to_id() || !config.status.online.intercept {
+        let result = if handshake.next_state != ClientState::Status.to_id() {
+            let outbound = proxy::connect_to_server(&config, &client)
+                .await
+                .map_err(|_| ())?;
+            let proxied = server.track_proxied();
+            proxy::proxy_metered(inbound, outbound, &queue, proxied.traffic(), &config.limits).await
+        } else {
+            let outbound = proxy::connect_to_server(&config, &client)
+                .await
+                .map_err(|_| ())?;
+            proxy::proxy_inbound_outbound_with_queue(inbound, outbound, &queue, &[]).await
+        };
+        return result.map_err(|_| ());
+    }
+    client.set_state(ClientState::Status);
+    client.set_protocol(handshake.protocol_version);
+    client.set_hostname(&handshake.server_addr);
+
+    // Forward handshake and status request to server
+    let mut outbound = proxy::connect_to_server(&config, &client)
+        .await
+        .map_err(|_| ())?;
+    outbound.write_all(&queue).await.map_err(|_| ())?;
+
+    // Read status response from server, rewrite and send to client
+    let server_client = Client::default();
+    server_client.set_state(ClientState::Status);
+    let mut server_buf = BytesMut::new();
+    let (mut server_reader, _) = outbound.split();
+    let timeout = config.limits.timeout(ClientState::Status);
+    let (packet, raw) = match proto::read_packet_timeout(
+        &server_client,
+        &mut server_buf,
+        &mut server_reader,
+        timeout,
+    )
+    .await?
+    {
+        Some(packet) => packet,
+        None => return Ok(()),
+    };
+    let response = if packet.id == proto::STATUS_PACKET_ID_STATUS {
+        rewrite(&config, &packet.data)
+            .and_then(|data| RawPacket::new(packet.id, data).encode(&client))
+            .unwrap_or(raw)
+    } else {
+        raw
+    };
+    inbound.write_all(&response).await.map_err(|_| ())?;
+
+    // Proxy remaining ping and pong
+    proxy::proxy_inbound_outbound_with_queue(inbound, outbound, &[], &server_buf)
+        .await
+        .map_err(|_| ())
+}
+
+/// Rewrite status response packet data.
+fn rewrite(config: &Config, data: &[u8]) -> Result<Vec<u8>, ()> {
+    let (_, json) = types::read_string(data)?;
+    let mut status: Value = serde_json::from_str(&json).map_err(|_| ())?;
+    let rewrite = &config.status.online;
+
+    // Append suffix to description
+    if let Some(suffix) = rewrite.motd_suffix.as_deref().filter(|s| !s.is_empty()) {
+        if let Some(description) = status.get_mut("description") {
+            let suffix = serde_json::to_value(chat::message(suffix)).map_err(|_| ())?;
+            *description = serde_json::json!({
+                "text": "",
+                "extra": [description.take(), suffix],
+            });
+        }
+    }
+
+    if let Some(players) = status.get_mut("players").and_then(Value::as_object_mut) {
+        // Hide player list
+        if rewrite.hide_players {
+            players.remove("sample");
+        }
+
+        // Cap max players
+        if let Some(cap) = rewrite.max_players {
+            let max = players.get("max").and_then(Value::as_u64).unwrap_or(0);
+            players.insert("max".into(), max.min(cap as u64).into());
+        }
+    }
+
+    let json = serde_json::to_string(&status).map_err(|_| ())?;
+    types::encode_string(&json)
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use tokio::net::TcpStream;
+
+use super::MethodResult;
+use crate::config::Config;
+use crate::proto::Client;
+use crate::proxy;
+
+/// Forward a client to the fallback server while the server starts.
+pub async fn occupy(
+    client: &Client,
+    config: Arc<Config>,
+    inbound: TcpStream,
+    inbound_history: &[u8],
+) -> Result<MethodResult, ()> {
+    trace!(target: "lazymc", "Using forward method to occupy joining client");
+    debug!(target: "lazymc", "Forwarding client to fallback server {}", config.join.forward.address);
+
+    proxy::proxy_with_queue(
+        &config,
+        client,
+        inbound,
+        config.join.forward.address,
+        inbound_history,
+    )
+    .await
+    .map_err(|err| {
+        warn!(target: "lazymc", "Failed to forward client to fallback server: {}", err);
+    })?;
+
+    Ok(MethodResult::Complete)
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+
+use super::MethodResult;
+use crate::config::Config;
+use crate::proto::Client;
+use crate::proxy;
+use crate::queue::Ticket;
+use crate::server::ServerState;
+
+/// Interval to check queue admission in while holding a client.
+const HOLD_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Hold a client while the server starts, forward it once the server is online.
+///
+/// Continues with the next method if the server doesn't come online within the configured
+/// timeout.
+pub async fn occupy(
+    client: &Client,
+    config: Arc<Config>,
+    server: Arc<ServerState>,
+    inbound: TcpStream,
+    inbound_history: &[u8],
+) -> Result<MethodResult, ()> {
+    trace!(target: "lazymc", "Using hold method to occupy joining client");
+
+    // Wait for server to come online and to be admitted from queue, with timeout
+    // Client is silent while held, it must not be closed for being idle
+    let ticket = super::enqueue(&config, &server);
+    let timeout = Duration::from_secs(config.join.hold.timeout as u64);
+    let waiting = client.waiting.start();
+    let online = tokio::time::timeout(timeout, wait_until_admitted(&server, ticket.as_ref()))
+        .await
+        .is_ok();
+    drop(waiting);
+
+    if !online {
+        debug!(target: "lazymc", "Server did not come online in time, stop holding client");
+        return Ok(MethodResult::Continue(inbound));
+    }
+
+    // Forward client to server, replay queued packets
+    debug!(target: "lazymc", "Server is now online, forwarding held client");
+    let outbound = match proxy::connect_to_server(&config, client).await {
+        Ok(outbound) => outbound,
+        Err(err) => {
+            debug!(target: "lazymc", "Failed to connect to server, stop holding client: {}", err);
+            return Ok(MethodResult::Continue(inbound));
+        }
+    };
+    let proxied = server.track_proxied();
+    proxy::proxy_metered(
+        inbound,
+        outbound,
+        inbound_history,
+        proxied.traffic(),
+        &config.limits,
+    )
+    .await
+    .map_err(|err| {
+        warn!(target: "lazymc", "Failed to forward held client: {}", err);
+    })?;
+
+    Ok(MethodResult::Complete)
+}
+
+/// Wait until the server is online, and until the client is admitted if queued.
+///
+/// Reacts to the server coming online immediately, queue admission is polled.
+async fn wait_until_admitted(server: &ServerState, ticket: Option<&Ticket>) {
+    let mut state = server.subscribe();
+    while !server.online() || !ticket.map(|ticket| ticket.admitted()).unwrap_or(true) {
+        let _ = tokio::time::timeout(HOLD_POLL_INTERVAL, state.changed()).await;
+    }
+}
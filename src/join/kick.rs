@@ -0,0 +1,68 @@
+use tokio::io::{self, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::MethodResult;
+use crate::chat;
+use crate::config::Config;
+use crate::messages;
+use crate::proto::Client;
+use crate::protocol::Protocol;
+use crate::queue;
+use crate::server::ServerState;
+
+/// Kick client with the starting message.
+///
+/// Tells the client how many clients are waiting if the join queue is enabled.
+pub async fn occupy(
+    client: &Client,
+    config: &Config,
+    server: &ServerState,
+    mut inbound: TcpStream,
+) -> Result<MethodResult, ()> {
+    trace!(target: "lazymc", "Using kick method to occupy joining client");
+
+    let position = config.join.queue.enabled.then(|| server.queue.len() + 1);
+    kick(
+        client,
+        &starting_message(client, config, server, position),
+        &mut inbound,
+    )
+    .await?;
+
+    // Gracefully close connection
+    match inbound.shutdown().await {
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotConnected => {}
+        Err(_) => return Err(()),
+    }
+
+    Ok(MethodResult::Complete)
+}
+
+/// Build starting message to kick a client with, including its queue position if known.
+pub fn starting_message(
+    client: &Client,
+    config: &Config,
+    server: &ServerState,
+    position: Option<usize>,
+) -> String {
+    let message = messages::render(config.messages.login_starting(client), config, server);
+    match position {
+        Some(position) => format!("{}\n\n{}", message, queue::message(config, position)),
+        None => message,
+    }
+}
+
+/// Kick client with a message.
+///
+/// Replaces the `{username}` placeholder. Should close connection afterwards.
+pub async fn kick<W: AsyncWrite + Unpin>(
+    client: &Client,
+    msg: &str,
+    writer: &mut W,
+) -> Result<(), ()> {
+    let response = Protocol::of(client)
+        .encode_login_disconnect(chat::message(&messages::with_username(msg, client)))?
+        .encode(client)?;
+    writer.write_all(&response).await.map_err(|_| ())
+}
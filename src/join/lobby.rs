@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use tokio::net::TcpStream;
+
+use super::MethodResult;
+use crate::config::Config;
+use crate::lobby;
+use crate::proto::Client;
+use crate::server::ServerState;
+
+/// Keep a client in the lobby world while the server starts, transfer it once the server is
+/// online.
+///
+/// Continues with the next method if the client version is not supported by the lobby.
+pub async fn occupy(
+    client: &Client,
+    config: Arc<Config>,
+    server: Arc<ServerState>,
+    inbound: TcpStream,
+    inbound_history: &[u8],
+) -> Result<MethodResult, ()> {
+    trace!(target: "lazymc", "Using lobby method to occupy joining client");
+
+    if !lobby::supports(client) {
+        debug!(target: "lazymc", "Client version not supported by lobby, continuing with next method");
+        return Ok(MethodResult::Continue(inbound));
+    }
+
+    let ticket = super::enqueue(&config, &server);
+    lobby::serve(client, config, server, inbound, inbound_history, ticket).await?;
+
+    Ok(MethodResult::Complete)
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use tokio::net::TcpStream;
+
+use crate::config::*;
+use crate::proto::Client;
+use crate::queue::Ticket;
+use crate::server::ServerState;
+
+pub mod forward;
+pub mod hold;
+pub mod kick;
+pub mod lobby;
+
+/// A result returned by a join occupy method.
+pub enum MethodResult {
+    /// Client is done, stop.
+    Complete,
+
+    /// Method did not handle client, continue with the next method.
+    Continue(TcpStream),
+}
+
+/// Start occupying a client that is joining while the server is not online.
+///
+/// Tries the configured join methods in order, until one handles the client.
+pub async fn occupy(
+    client: Client,
+    config: Arc<Config>,
+    server: Arc<ServerState>,
+    mut inbound: TcpStream,
+    inbound_history: BytesMut,
+) -> Result<(), ()> {
+    trace!(target: "lazymc", "Using join methods to occupy joining client: {:?}", config.join.methods);
+
+    for method in &config.join.methods {
+        let result = match method {
+            Method::Kick => kick::occupy(&client, &config, &server, inbound).await?,
+            Method::Hold => {
+                hold::occupy(
+                    &client,
+                    config.clone(),
+                    server.clone(),
+                    inbound,
+                    &inbound_history,
+                )
+                .await?
+            }
+            Method::Forward => {
+                forward::occupy(&client, config.clone(), inbound, &inbound_history).await?
+            }
+            Method::Lobby => {
+                lobby::occupy(
+                    &client,
+                    config.clone(),
+                    server.clone(),
+                    inbound,
+                    &inbound_history,
+                )
+                .await?
+            }
+        };
+
+        match result {
+            MethodResult::Complete => return Ok(()),
+            MethodResult::Continue(stream) => inbound = stream,
+        }
+    }
+
+    debug!(target: "lazymc", "No join method handled client, disconnecting");
+    Ok(())
+}
+
+/// Enqueue client in join queue, if enabled.
+fn enqueue(config: &Config, server: &ServerState) -> Option<Ticket> {
+    config.join.queue.enabled.then(|| server.queue.enqueue())
+}
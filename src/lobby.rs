@@ -0,0 +1,617 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::BytesMut;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::{ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::time;
+
+use crate::auth;
+use crate::chat;
+use crate::config::Config;
+use crate::crypto::{CryptReader, CryptWriter};
+use crate::mc::{dimension, nbt, uuid};
+use crate::messages;
+use crate::proto::{self, Client, ClientState, RawPacket};
+use crate::protocol::Protocol;
+use crate::proxy;
+use crate::queue::{self, Ticket};
+use crate::server::ServerState;
+use crate::types;
+
+/// Minimum client protocol version supported by the lobby, Minecraft 1.16.2.
+const PROTO_LOBBY_MIN: i32 = 751;
+
+/// Maximum client protocol version supported by the lobby, Minecraft 1.16.5.
+const PROTO_LOBBY_MAX: i32 = 754;
+
+/// Play state packet IDs used by the lobby, for Minecraft 1.16.2 to 1.16.5.
+const CLIENT_DISCONNECT: i32 = 0x19;
+const CLIENT_KEEP_ALIVE: i32 = 0x1F;
+const CLIENT_JOIN_GAME: i32 = 0x24;
+const CLIENT_PLAYER_POS_LOOK: i32 = 0x34;
+const CLIENT_RESPAWN: i32 = 0x39;
+const CLIENT_TITLE: i32 = 0x4F;
+const SERVER_CLIENT_SETTINGS: i32 = 0x05;
+const SERVER_PLUGIN_MESSAGE: i32 = 0x0B;
+
+/// Title packet actions.
+const TITLE_ACTION_TITLE: i32 = 0;
+const TITLE_ACTION_SUBTITLE: i32 = 1;
+const TITLE_ACTION_ACTION_BAR: i32 = 2;
+const TITLE_ACTION_TIMES: i32 = 3;
+const TITLE_ACTION_HIDE: i32 = 4;
+
+/// Spectator game mode.
+const GAMEMODE_SPECTATOR: u8 = 3;
+
+/// Interval to send keep-alive packets at while client is in lobby.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interval to check queue admission at while client is in lobby.
+const SERVER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time to wait after the server came online before connecting to it.
+///
+/// Notchian servers are slow to accept logins right after starting.
+const SERVER_WARMUP: Duration = Duration::from_secs(1);
+
+/// Timeout for connecting to the server and completing login.
+const SERVER_CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Lobby title stay time in ticks, must outlast the keep-alive interval which refreshes it.
+const TITLE_STAY_TICKS: i32 = 20 * 20;
+
+/// Compression threshold for lobby clients, the Notchian default.
+///
+/// Packets are proxied as-is after transfer, so the server must use the same threshold.
+pub const COMPRESSION_THRESHOLD: i32 = 256;
+
+/// Message shown to client if transferring to the server failed.
+const TRANSFER_FAILED_MESSAGE: &str = "Failed to connect to server, please reconnect.";
+
+/// Client stream halves, encrypted once encryption is enabled.
+type ClientReader<'a> = CryptReader<ReadHalf<'a>>;
+type ClientWriter<'a> = CryptWriter<WriteHalf<'a>>;
+
+/// Check whether the lobby supports the given client.
+pub fn supports(client: &Client) -> bool {
+    client
+        .protocol()
+        .map(|protocol| (PROTO_LOBBY_MIN..=PROTO_LOBBY_MAX).contains(&protocol))
+        .unwrap_or(false)
+}
+
+/// Serve lobby to a client.
+///
+/// Completes the login for the client, places it in a void lobby world while the server starts,
+/// and transfers it to the server once it is online and the client is admitted from the queue. The
+/// client must be supported, see [`supports`].
+///
+/// The server must have `online-mode=false` and `network-compression-threshold` set to
+/// [`COMPRESSION_THRESHOLD`] for the transfer to succeed. If lobby encryption is enabled, the client connection is encrypted by
+/// lazymc, also after the transfer.
+pub async fn serve(
+    client: &Client,
+    config: Arc<Config>,
+    server: Arc<ServerState>,
+    mut inbound: TcpStream,
+    inbound_history: &[u8],
+    ticket: Option<Ticket>,
+) -> Result<(), ()> {
+    let username = client.username().ok_or(())?;
+    let (reader, writer) = inbound.split();
+    let (mut reader, mut writer) = (CryptReader::new(reader), CryptWriter::new(writer));
+    let mut buf = BytesMut::new();
+
+    // Encrypt client connection
+    if config.join.lobby.encryption {
+        let timeout = config.limits.timeout(ClientState::Login);
+        auth::encrypt(client, &mut reader, &mut writer, &mut buf, timeout).await?;
+    }
+
+    // Enable compression, matching the server
+    send_set_compression(client, &mut writer, COMPRESSION_THRESHOLD).await?;
+    client.set_compression(COMPRESSION_THRESHOLD);
+
+    // Complete login, join lobby world
+    trace!(target: "lazymc::lobby", "Sending login success and lobby world to client");
+    send_login_success(client, &mut writer, Protocol::of(client), &username).await?;
+    send_lobby_join_game(client, &mut writer).await?;
+    send_lobby_player_pos(client, &mut writer).await?;
+
+    // Wait in lobby until server is online
+    let mut client_queue = match stage_wait(
+        client,
+        &config,
+        &server,
+        &mut reader,
+        &mut writer,
+        &mut buf,
+        ticket.as_ref(),
+    )
+    .await?
+    {
+        Some(client_queue) => client_queue,
+        None => return Ok(()),
+    };
+
+    // Connect to server, complete login and grab its join game packet
+    time::sleep(SERVER_WARMUP).await;
+    let (server_client, mut outbound, mut server_buf) =
+        match connect_to_server(client, &config, &mut writer, inbound_history).await {
+            Ok(result) => result,
+            Err(_) => {
+                send_disconnect(client, &mut writer, TRANSFER_FAILED_MESSAGE).await?;
+                return Err(());
+            }
+        };
+    let (join_game, join_game_raw) =
+        match wait_for_server_join_game(&server_client, &mut outbound, &mut server_buf).await {
+            Ok(result) => result,
+            Err(_) => {
+                send_disconnect(client, &mut writer, TRANSFER_FAILED_MESSAGE).await?;
+                return Err(());
+            }
+        };
+
+    // Clear lobby title, transfer client to server world
+    debug!(target: "lazymc::lobby", "Server is online, transferring lobby client to server");
+    send_title_hide(client, &mut writer).await?;
+    send_respawn_from_join(client, &mut writer, &join_game).await?;
+    writer.write_all(&join_game_raw).await.map_err(|_| ())?;
+
+    // Client packets captured in lobby, followed by any partial packet still buffered
+    client_queue.extend_from_slice(&buf);
+
+    // Route all packets through proxy
+    let proxied = server.track_proxied();
+    let (server_reader, mut server_writer) = outbound.split();
+    server_writer
+        .write_all(&client_queue)
+        .await
+        .map_err(|_| ())?;
+    writer.write_all(&server_buf).await.map_err(|_| ())?;
+    let reader = proxy::Throttled::new(
+        proxy::Metered::new(reader, proxied.traffic()),
+        config.limits.max_upload_rate(),
+    );
+    let server_reader = proxy::Throttled::new(server_reader, config.limits.max_download_rate());
+    proxy::proxy_halves(reader, writer, server_reader, server_writer)
+        .await
+        .map_err(|err| {
+            warn!(target: "lazymc::lobby", "Failed to proxy lobby client: {}", err);
+        })
+}
+
+/// Keep client in lobby until the server is online, and until the client is admitted if queued.
+///
+/// Shows the queue position while queued.
+///
+/// Returns the client packets the server should receive after transfer. Returns `None` if the
+/// client should not be transferred, because it disconnected or because the server did not come
+/// online within the lobby timeout.
+async fn stage_wait(
+    client: &Client,
+    config: &Config,
+    server: &ServerState,
+    reader: &mut ClientReader<'_>,
+    writer: &mut ClientWriter<'_>,
+    buf: &mut BytesMut,
+    ticket: Option<&Ticket>,
+) -> Result<Option<BytesMut>, ()> {
+    let mut client_queue = BytesMut::new();
+    let mut keep_alive = time::interval(KEEP_ALIVE_INTERVAL);
+    let mut keep_alive_id: i64 = 0;
+    let mut poll = time::interval(SERVER_POLL_INTERVAL);
+    let mut state = server.subscribe();
+    let timeout = time::sleep(Duration::from_secs(config.join.lobby.timeout as u64));
+    tokio::pin!(timeout);
+
+    loop {
+        tokio::select! {
+            // Consume client packets, remember the ones the server needs
+            result = proto::read_packet(client, buf, reader) => {
+                let (packet, raw) = match result {
+                    Ok(Some(packet)) => packet,
+                    Ok(None) => {
+                        debug!(target: "lazymc::lobby", "Client left lobby");
+                        return Ok(None);
+                    }
+                    Err(_) => return Err(()),
+                };
+
+                if packet.id == SERVER_CLIENT_SETTINGS || packet.id == SERVER_PLUGIN_MESSAGE {
+                    client_queue.extend_from_slice(&raw);
+                }
+            }
+
+            // Keep client connection alive, refresh lobby title
+            _ = keep_alive.tick() => {
+                keep_alive_id += 1;
+                send_keep_alive(client, writer, keep_alive_id).await?;
+                let title = messages::render(&config.join.lobby.message, config, server);
+                let title = messages::with_username(&title, client);
+                send_lobby_title(client, writer, &title).await?;
+                if let Some(position) = ticket.and_then(|ticket| ticket.position()) {
+                    send_action_bar(client, writer, &queue::message(config, position)).await?;
+                }
+            }
+
+            // Stop waiting once server is online and client is admitted
+            _ = state.changed() => {
+                if server.online() && ticket.map(|ticket| ticket.admitted()).unwrap_or(true) {
+                    return Ok(Some(client_queue));
+                }
+            }
+            _ = poll.tick() => {
+                if server.online() && ticket.map(|ticket| ticket.admitted()).unwrap_or(true) {
+                    return Ok(Some(client_queue));
+                }
+            }
+
+            // Kick client when server doesn't come online in time
+            _ = &mut timeout => {
+                debug!(target: "lazymc::lobby", "Server did not come online in time, kicking lobby client");
+                send_disconnect(client, writer, &messages::render(config.messages.login_starting(client), config, server)).await?;
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Connect to the server and complete login for the client.
+///
+/// Replays the client handshake and login start packets to the server. Returns the server session,
+/// stream and its buffer of received data once login is complete.
+async fn connect_to_server(
+    client: &Client,
+    config: &Config,
+    client_writer: &mut ClientWriter<'_>,
+    inbound_history: &[u8],
+) -> Result<(Client, TcpStream, BytesMut), ()> {
+    time::timeout(
+        SERVER_CONNECT_TIMEOUT,
+        connect_to_server_no_timeout(client, config, client_writer, inbound_history),
+    )
+    .await
+    .map_err(|_| {
+        error!(target: "lazymc::lobby", "Connecting to server for lobby client timed out");
+    })?
+}
+
+/// Connect to the server and complete login for the client.
+///
+/// See [`connect_to_server`], without timeout.
+async fn connect_to_server_no_timeout(
+    client: &Client,
+    config: &Config,
+    client_writer: &mut ClientWriter<'_>,
+    inbound_history: &[u8],
+) -> Result<(Client, TcpStream, BytesMut), ()> {
+    let mut outbound = proxy::connect_to_server(config, client)
+        .await
+        .map_err(|err| {
+            error!(target: "lazymc::lobby", "Failed to connect to server for lobby client: {}", err);
+        })?;
+    let (mut reader, mut writer) = outbound.split();
+    let server_client = Client::default();
+
+    // Replay client handshake and login start
+    writer.write_all(inbound_history).await.map_err(|_| ())?;
+
+    let mut buf = BytesMut::new();
+    loop {
+        let (packet, _raw) = match proto::read_packet(&server_client, &mut buf, &mut reader).await {
+            Ok(Some(packet)) => packet,
+            Ok(None) | Err(_) => {
+                error!(target: "lazymc::lobby", "Server closed connection during lobby client login");
+                return Err(());
+            }
+        };
+
+        match packet.id {
+            proto::LOGIN_PACKET_ID_LOGIN_SUCCESS => break,
+            proto::LOGIN_PACKET_ID_LOGIN_PLUGIN_REQUEST => {
+                // Respond that we don't understand the request
+                let (_, message_id) = types::read_var_int(&packet.data)?;
+                let mut data = types::encode_var_int(message_id)?;
+                data.push(false as u8);
+                write_packet(
+                    &server_client,
+                    &mut writer,
+                    proto::LOGIN_PACKET_ID_LOGIN_PLUGIN_RESPONSE,
+                    data,
+                )
+                .await?;
+            }
+            proto::LOGIN_PACKET_ID_DISCONNECT => {
+                // Forward disconnect reason to client, which has the same format in play state
+                warn!(target: "lazymc::lobby", "Server disconnected lobby client during login");
+                write_packet(client, client_writer, CLIENT_DISCONNECT, packet.data).await?;
+                return Err(());
+            }
+            proto::LOGIN_PACKET_ID_ENCRYPTION_REQUEST => {
+                error!(target: "lazymc::lobby", "Server requested encryption, lobby requires online-mode=false on server");
+                return Err(());
+            }
+            proto::LOGIN_PACKET_ID_SET_COMPRESSION => {
+                // Packets are proxied as-is after transfer, compression must match
+                let (_, threshold) = types::read_var_int(&packet.data)?;
+                server_client.set_compression(threshold);
+                if server_client.compression() != client.compression() {
+                    error!(target: "lazymc::lobby", "Server compression threshold does not match, lobby requires network-compression-threshold={} on server", COMPRESSION_THRESHOLD);
+                    return Err(());
+                }
+            }
+            id => {
+                debug!(target: "lazymc::lobby", "Ignoring unhandled login packet from server: {}", id);
+            }
+        }
+    }
+
+    Ok((server_client, outbound, buf))
+}
+
+/// Wait for the join game packet from the server.
+///
+/// Returns the parsed packet and its raw data.
+async fn wait_for_server_join_game(
+    server_client: &Client,
+    outbound: &mut TcpStream,
+    buf: &mut BytesMut,
+) -> Result<(JoinGame, Vec<u8>), ()> {
+    let (mut reader, _) = outbound.split();
+
+    loop {
+        let (packet, raw) = match proto::read_packet(server_client, buf, &mut reader).await {
+            Ok(Some(packet)) => packet,
+            Ok(None) | Err(_) => {
+                error!(target: "lazymc::lobby", "Server closed connection before joining lobby client");
+                return Err(());
+            }
+        };
+
+        if packet.id == CLIENT_JOIN_GAME {
+            let join_game = JoinGame::decode(&packet.data).map_err(|_| {
+                error!(target: "lazymc::lobby", "Failed to decode join game packet from server");
+            })?;
+            return Ok((join_game, raw));
+        }
+
+        debug!(target: "lazymc::lobby", "Ignoring unhandled play packet from server before join game: {}", packet.id);
+    }
+}
+
+/// Send set compression packet to client.
+///
+/// Compression must be enabled on the client session after sending.
+async fn send_set_compression(
+    client: &Client,
+    writer: &mut ClientWriter<'_>,
+    threshold: i32,
+) -> Result<(), ()> {
+    let data = types::encode_var_int(threshold)?;
+    write_packet(client, writer, proto::LOGIN_PACKET_ID_SET_COMPRESSION, data).await
+}
+
+/// Send login success packet to client.
+async fn send_login_success(
+    client: &Client,
+    writer: &mut ClientWriter<'_>,
+    protocol: Protocol,
+    username: &str,
+) -> Result<(), ()> {
+    let packet = protocol.encode_login_success(uuid::offline_player_uuid(username), username)?;
+    write_packet(client, writer, packet.id, packet.data).await
+}
+
+/// Send join game packet for lobby world to client.
+async fn send_lobby_join_game(client: &Client, writer: &mut ClientWriter<'_>) -> Result<(), ()> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0i32.to_be_bytes()); // Entity ID
+    data.push(false as u8); // Is hardcore
+    data.push(GAMEMODE_SPECTATOR);
+    data.push(-1i8 as u8); // Previous game mode
+    data.extend(types::encode_var_int(1)?); // World count
+    data.extend(types::encode_string(dimension::LOBBY_WORLD)?);
+    data.extend(dimension::lobby_dimension_codec().encode());
+    data.extend(dimension::lobby_dimension().encode());
+    data.extend(types::encode_string(dimension::LOBBY_WORLD)?);
+    data.extend_from_slice(&0i64.to_be_bytes()); // Hashed seed
+    data.extend(types::encode_var_int(0)?); // Max players
+    data.extend(types::encode_var_int(2)?); // View distance
+    data.push(false as u8); // Reduced debug info
+    data.push(false as u8); // Enable respawn screen
+    data.push(false as u8); // Is debug
+    data.push(true as u8); // Is flat
+    write_packet(client, writer, CLIENT_JOIN_GAME, data).await
+}
+
+/// Send lobby player position to client, which closes its terrain loading screen.
+async fn send_lobby_player_pos(client: &Client, writer: &mut ClientWriter<'_>) -> Result<(), ()> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0f64.to_be_bytes()); // X
+    data.extend_from_slice(&0f64.to_be_bytes()); // Y
+    data.extend_from_slice(&0f64.to_be_bytes()); // Z
+    data.extend_from_slice(&0f32.to_be_bytes()); // Yaw
+    data.extend_from_slice(&90f32.to_be_bytes()); // Pitch
+    data.push(0); // Flags, all absolute
+    data.extend(types::encode_var_int(0)?); // Teleport ID
+    write_packet(client, writer, CLIENT_PLAYER_POS_LOOK, data).await
+}
+
+/// Send keep-alive packet to client.
+async fn send_keep_alive(
+    client: &Client,
+    writer: &mut ClientWriter<'_>,
+    id: i64,
+) -> Result<(), ()> {
+    write_packet(client, writer, CLIENT_KEEP_ALIVE, id.to_be_bytes().to_vec()).await
+}
+
+/// Send lobby title to client.
+///
+/// The first line of the message is used as title, the rest as subtitle.
+async fn send_lobby_title(
+    client: &Client,
+    writer: &mut ClientWriter<'_>,
+    message: &str,
+) -> Result<(), ()> {
+    let (title, subtitle) = message.split_once('\n').unwrap_or((message, ""));
+
+    let mut data = types::encode_var_int(TITLE_ACTION_TIMES)?;
+    data.extend_from_slice(&0i32.to_be_bytes()); // Fade in
+    data.extend_from_slice(&TITLE_STAY_TICKS.to_be_bytes()); // Stay
+    data.extend_from_slice(&0i32.to_be_bytes()); // Fade out
+    write_packet(client, writer, CLIENT_TITLE, data).await?;
+
+    let mut data = types::encode_var_int(TITLE_ACTION_SUBTITLE)?;
+    data.extend(encode_chat(subtitle)?);
+    write_packet(client, writer, CLIENT_TITLE, data).await?;
+
+    let mut data = types::encode_var_int(TITLE_ACTION_TITLE)?;
+    data.extend(encode_chat(title)?);
+    write_packet(client, writer, CLIENT_TITLE, data).await
+}
+
+/// Send action bar message to client.
+async fn send_action_bar(
+    client: &Client,
+    writer: &mut ClientWriter<'_>,
+    message: &str,
+) -> Result<(), ()> {
+    let mut data = types::encode_var_int(TITLE_ACTION_ACTION_BAR)?;
+    data.extend(encode_chat(message)?);
+    write_packet(client, writer, CLIENT_TITLE, data).await
+}
+
+/// Send packet to client to hide its title.
+async fn send_title_hide(client: &Client, writer: &mut ClientWriter<'_>) -> Result<(), ()> {
+    let data = types::encode_var_int(TITLE_ACTION_HIDE)?;
+    write_packet(client, writer, CLIENT_TITLE, data).await
+}
+
+/// Send respawn packet to client, based on the join game packet from the server.
+///
+/// This moves the client out of the lobby world into the server world.
+async fn send_respawn_from_join(
+    client: &Client,
+    writer: &mut ClientWriter<'_>,
+    join_game: &JoinGame,
+) -> Result<(), ()> {
+    let mut data = join_game.dimension.clone();
+    data.extend(types::encode_string(&join_game.world_name)?);
+    data.extend_from_slice(&join_game.hashed_seed.to_be_bytes());
+    data.push(join_game.gamemode);
+    data.push(join_game.previous_gamemode as u8);
+    data.push(join_game.is_debug as u8);
+    data.push(join_game.is_flat as u8);
+    data.push(false as u8); // Copy metadata
+    write_packet(client, writer, CLIENT_RESPAWN, data).await
+}
+
+/// Send play state disconnect packet to client.
+///
+/// Replaces the `{username}` placeholder.
+async fn send_disconnect(
+    client: &Client,
+    writer: &mut ClientWriter<'_>,
+    message: &str,
+) -> Result<(), ()> {
+    let message = messages::with_username(message, client);
+    write_packet(client, writer, CLIENT_DISCONNECT, encode_chat(&message)?).await
+}
+
+/// Encode text as chat message.
+fn encode_chat(text: &str) -> Result<Vec<u8>, ()> {
+    let json = chat::message(text).to_json().map_err(|_| ())?;
+    types::encode_string(&json)
+}
+
+/// Write raw packet.
+async fn write_packet<W: AsyncWrite + Unpin>(
+    client: &Client,
+    writer: &mut W,
+    id: i32,
+    data: Vec<u8>,
+) -> Result<(), ()> {
+    let response = RawPacket::new(id, data).encode(client)?;
+    writer.write_all(&response).await.map_err(|_| ())
+}
+
+/// Server join game packet, with the fields required to respawn a client into its world.
+struct JoinGame {
+    /// Game mode.
+    gamemode: u8,
+
+    /// Previous game mode.
+    previous_gamemode: i8,
+
+    /// Raw NBT dimension type.
+    dimension: Vec<u8>,
+
+    /// World name.
+    world_name: String,
+
+    /// Hashed world seed.
+    hashed_seed: i64,
+
+    /// Whether the world is a debug world.
+    is_debug: bool,
+
+    /// Whether the world is a superflat world.
+    is_flat: bool,
+}
+
+impl JoinGame {
+    /// Decode join game packet data.
+    fn decode(mut buf: &[u8]) -> Result<Self, ()> {
+        // Skip entity ID and hardcore flag, read game modes
+        buf = buf.get(5..).ok_or(())?;
+        let gamemode = *buf.first().ok_or(())?;
+        let previous_gamemode = *buf.get(1).ok_or(())? as i8;
+        buf = &buf[2..];
+
+        // Skip world names
+        let (read, count) = types::read_var_int(buf)?;
+        buf = &buf[read..];
+        for _ in 0..count {
+            let (read, _) = types::read_string(buf)?;
+            buf = &buf[read..];
+        }
+
+        // Skip dimension codec, take dimension type
+        let len = nbt::root_len(buf)?;
+        buf = &buf[len..];
+        let len = nbt::root_len(buf)?;
+        let dimension = buf[..len].to_vec();
+        buf = &buf[len..];
+
+        // World name and hashed seed
+        let (read, world_name) = types::read_string(buf)?;
+        buf = &buf[read..];
+        let hashed_seed = i64::from_be_bytes(buf.get(..8).ok_or(())?.try_into().unwrap());
+        buf = &buf[8..];
+
+        // Skip max players and view distance
+        for _ in 0..2 {
+            let (read, _) = types::read_var_int(buf)?;
+            buf = &buf[read..];
+        }
+
+        // Reduced debug info, enable respawn screen, is debug and is flat flags
+        let flags = buf.get(..4).ok_or(())?;
+
+        Ok(Self {
+            gamemode,
+            previous_gamemode,
+            dimension,
+            world_name,
+            hashed_seed,
+            is_debug: flags[2] != 0,
+            is_flat: flags[3] != 0,
+        })
+    }
+}
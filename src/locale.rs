@@ -0,0 +1,56 @@
+use toml::Value;
+
+/// Language to use if none is configured, also the fallback for missing messages.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Built-in locales, with messages as partial configuration.
+const LOCALES: &[(&str, &str)] = &[
+    ("de", include_str!("../res/locales/de.toml")),
+    ("en", include_str!("../res/locales/en.toml")),
+    ("fr", include_str!("../res/locales/fr.toml")),
+    ("nl", include_str!("../res/locales/nl.toml")),
+];
+
+/// Get messages for built-in locale, as partial configuration.
+fn get(language: &str) -> Option<Value> {
+    LOCALES
+        .iter()
+        .find(|(lang, _)| lang.eq_ignore_ascii_case(language))
+        .map(|(_, locale)| toml::from_str(locale).expect("invalid built-in locale"))
+}
+
+/// Apply locale messages to configuration, for messages that are not configured.
+///
+/// Uses the language set in `messages.language`. Messages missing in the locale fall back to the
+/// default language. Returns the unknown language on error.
+pub fn apply(config: &mut Value) -> Result<(), String> {
+    let language = config
+        .get("messages")
+        .and_then(|messages| messages.get("language"))
+        .and_then(Value::as_str)
+        .unwrap_or(DEFAULT_LANGUAGE)
+        .to_string();
+
+    let mut locale = get(DEFAULT_LANGUAGE).unwrap();
+    merge(&mut locale, get(&language).ok_or(language)?);
+    merge(&mut locale, config.clone());
+    *config = locale;
+    Ok(())
+}
+
+/// Merge tables in overlay into base recursively, values in overlay take precedence.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
@@ -8,18 +8,43 @@ extern crate derive_builder;
 extern crate log;
 
 pub(crate) mod action;
+pub(crate) mod admin;
+pub(crate) mod analytics;
+pub(crate) mod auth;
+pub(crate) mod backend;
+pub(crate) mod backup;
+pub(crate) mod bedrock;
+pub(crate) mod chat;
 pub(crate) mod cli;
 pub(crate) mod config;
+pub(crate) mod crypto;
+pub(crate) mod favicon;
+pub(crate) mod hooks;
+pub(crate) mod ingame;
+pub(crate) mod intercept;
+pub(crate) mod join;
+pub(crate) mod lobby;
+pub(crate) mod locale;
 pub(crate) mod mc;
+pub(crate) mod messages;
 pub(crate) mod monitor;
 pub(crate) mod os;
+pub(crate) mod persist;
 pub(crate) mod proto;
+pub(crate) mod protocol;
 pub(crate) mod proxy;
+pub(crate) mod proxy_protocol;
+pub(crate) mod query;
+pub(crate) mod queue;
+#[cfg(feature = "rcon")]
+pub(crate) mod rcon;
 pub(crate) mod server;
 pub(crate) mod service;
+pub(crate) mod session;
 pub(crate) mod status;
 pub(crate) mod types;
 pub(crate) mod util;
+pub(crate) mod wake;
 
 use std::env;
 
@@ -72,6 +97,12 @@ async fn invoke_action<'a>(app: App<'a>) -> Result<(), ()> {
         unimplemented!("Config logic here!");
     }
 
+    // RCON command
+    #[cfg(feature = "rcon")]
+    if let Some(matches) = matches.subcommand_matches("rcon") {
+        return action::rcon::invoke(matches).await;
+    }
+
     // Start server
     action::start::invoke(&matches).await
 }
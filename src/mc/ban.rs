@@ -0,0 +1,94 @@
+use std::net::IpAddr;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::mc::list;
+use crate::mc::whitelist::normalize_uuid;
+
+/// Banned players file name.
+pub const BANNED_PLAYERS_FILE: &str = "banned-players.json";
+
+/// Banned IPs file name.
+pub const BANNED_IPS_FILE: &str = "banned-ips.json";
+
+/// Ban expiry value for permanent bans.
+const EXPIRES_FOREVER: &str = "forever";
+
+/// Date format used in ban files.
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+/// Ban entry in banned players or banned IPs file.
+#[derive(Debug, Deserialize)]
+pub struct Ban {
+    /// Banned player UUID, hyphenated. Not set for IP bans.
+    #[serde(default)]
+    uuid: String,
+
+    /// Banned player username. Not set for IP bans.
+    #[serde(default)]
+    name: String,
+
+    /// Banned IP. Not set for player bans.
+    #[serde(default)]
+    ip: String,
+
+    /// Ban expiry date, or `forever`.
+    #[serde(default)]
+    expires: Option<String>,
+
+    /// Ban reason.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl Ban {
+    /// Check whether this ban has expired.
+    ///
+    /// Bans with an unknown expiry date never expire.
+    fn expired(&self) -> bool {
+        match self.expires.as_deref() {
+            None | Some(EXPIRES_FOREVER) => false,
+            Some(expires) => DateTime::parse_from_str(expires, DATE_FORMAT)
+                .map(|expires| expires < Utc::now())
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Server bans.
+#[derive(Debug)]
+pub struct Bans {
+    /// Player bans.
+    players: Vec<Ban>,
+
+    /// IP bans.
+    ips: Vec<Ban>,
+}
+
+impl Bans {
+    /// Load bans from ban files in dir.
+    pub fn load(dir: &Path) -> Self {
+        Self {
+            players: list::load(dir, BANNED_PLAYERS_FILE),
+            ips: list::load(dir, BANNED_IPS_FILE),
+        }
+    }
+
+    /// Find active ban for player with given username or UUID, or IP.
+    ///
+    /// Only matches players by UUID if given, as usernames can be spoofed in offline mode.
+    pub fn find(&self, name: &str, uuid: Option<&str>, ip: Option<IpAddr>) -> Option<&Ban> {
+        let player = self.players.iter().find(|ban| match uuid {
+            Some(uuid) => normalize_uuid(&ban.uuid) == normalize_uuid(uuid),
+            None => ban.name.eq_ignore_ascii_case(name),
+        });
+        let ip = ip.and_then(|ip| {
+            self.ips
+                .iter()
+                .find(|ban| ban.ip.parse::<IpAddr>().map(|b| b == ip).unwrap_or(false))
+        });
+        player.into_iter().chain(ip).find(|ban| !ban.expired())
+    }
+}
@@ -0,0 +1,90 @@
+use crate::mc::nbt::Tag;
+
+/// Lobby world name.
+pub const LOBBY_WORLD: &str = "lazymc:lobby";
+
+/// Lobby dimension type name.
+const LOBBY_DIMENSION_TYPE: &str = "minecraft:the_end";
+
+/// Lobby biome name.
+const LOBBY_BIOME: &str = "minecraft:plains";
+
+/// Build lobby dimension type.
+///
+/// A dark void dimension without sky light, based on the vanilla end.
+pub fn lobby_dimension() -> Tag {
+    Tag::compound(vec![
+        ("piglin_safe", Tag::bool(false)),
+        ("natural", Tag::bool(false)),
+        ("ambient_light", Tag::Float(0.0)),
+        ("fixed_time", Tag::Long(6000)),
+        ("infiniburn", Tag::string("minecraft:infiniburn_end")),
+        ("respawn_anchor_works", Tag::bool(false)),
+        ("has_skylight", Tag::bool(false)),
+        ("bed_works", Tag::bool(false)),
+        ("effects", Tag::string("minecraft:the_end")),
+        ("has_raids", Tag::bool(false)),
+        ("logical_height", Tag::Int(256)),
+        ("coordinate_scale", Tag::Double(1.0)),
+        ("ultrawarm", Tag::bool(false)),
+        ("has_ceiling", Tag::bool(false)),
+    ])
+}
+
+/// Build lobby dimension codec.
+///
+/// Holds the minimal registries a client requires to join a world: a single dimension type and a
+/// single biome.
+pub fn lobby_dimension_codec() -> Tag {
+    Tag::compound(vec![
+        (
+            "minecraft:dimension_type",
+            Tag::compound(vec![
+                ("type", Tag::string("minecraft:dimension_type")),
+                (
+                    "value",
+                    Tag::List(vec![Tag::compound(vec![
+                        ("name", Tag::string(LOBBY_DIMENSION_TYPE)),
+                        ("id", Tag::Int(0)),
+                        ("element", lobby_dimension()),
+                    ])]),
+                ),
+            ]),
+        ),
+        (
+            "minecraft:worldgen/biome",
+            Tag::compound(vec![
+                ("type", Tag::string("minecraft:worldgen/biome")),
+                (
+                    "value",
+                    Tag::List(vec![Tag::compound(vec![
+                        ("name", Tag::string(LOBBY_BIOME)),
+                        ("id", Tag::Int(0)),
+                        ("element", lobby_biome()),
+                    ])]),
+                ),
+            ]),
+        ),
+    ])
+}
+
+/// Build lobby biome.
+fn lobby_biome() -> Tag {
+    Tag::compound(vec![
+        ("precipitation", Tag::string("none")),
+        (
+            "effects",
+            Tag::compound(vec![
+                ("sky_color", Tag::Int(0)),
+                ("water_fog_color", Tag::Int(329011)),
+                ("fog_color", Tag::Int(0)),
+                ("water_color", Tag::Int(4159204)),
+            ]),
+        ),
+        ("depth", Tag::Float(0.1)),
+        ("temperature", Tag::Float(0.5)),
+        ("scale", Tag::Float(0.2)),
+        ("downfall", Tag::Float(0.5)),
+        ("category", Tag::string("none")),
+    ])
+}
@@ -0,0 +1,28 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// File name.
+pub const FILE: &str = "eula.txt";
+
+/// Contents of accepted EULA file.
+const ACCEPTED: &str = "# Accepted by lazymc, see https://aka.ms/MinecraftEULA\neula=true\n";
+
+/// Whether the Minecraft EULA is accepted in the server directory.
+///
+/// Not accepted if the file is missing.
+pub fn accepted(dir: &Path) -> bool {
+    fs::read_to_string(dir.join(FILE))
+        .map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .any(|(key, value)| key.trim() == "eula" && value.trim() == "true")
+        })
+        .unwrap_or(false)
+}
+
+/// Accept the Minecraft EULA in the server directory.
+pub fn accept(dir: &Path) -> io::Result<()> {
+    fs::write(dir.join(FILE), ACCEPTED)
+}
@@ -0,0 +1,25 @@
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+/// Load entries from a server JSON list file in dir, such as `whitelist.json`.
+///
+/// Returns no entries if the file doesn't exist. Prints an error if the file fails to load.
+pub fn load<T: DeserializeOwned>(dir: &Path, file: &str) -> Vec<T> {
+    let path = dir.join(file);
+    if !path.is_file() {
+        return vec![];
+    }
+
+    let result = fs::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|data| serde_json::from_slice(&data).map_err(|err| err.to_string()));
+    match result {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!(target: "lazymc", "Failed to load {}: {}", file, err);
+            vec![]
+        }
+    }
+}
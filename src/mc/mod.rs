@@ -1,3 +1,11 @@
+pub mod ban;
+pub mod dimension;
+pub mod eula;
+pub mod list;
+pub mod nbt;
 #[cfg(feature = "rcon")]
 pub mod rcon;
 pub mod server_properties;
+pub mod session;
+pub mod uuid;
+pub mod whitelist;
@@ -0,0 +1,191 @@
+/// NBT tag type IDs.
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// Maximum NBT nesting depth we accept when reading.
+const MAX_DEPTH: usize = 512;
+
+/// A minimal NBT tag.
+///
+/// Only supports the tag types lazymc needs to write.
+#[derive(Debug, Clone)]
+pub enum Tag {
+    Byte(i8),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    List(Vec<Tag>),
+    Compound(Vec<(String, Tag)>),
+}
+
+impl Tag {
+    /// Build compound tag from list of named tags.
+    pub fn compound(tags: Vec<(&str, Tag)>) -> Self {
+        Self::Compound(
+            tags.into_iter()
+                .map(|(name, tag)| (name.into(), tag))
+                .collect(),
+        )
+    }
+
+    /// Build string tag.
+    pub fn string(value: &str) -> Self {
+        Self::String(value.into())
+    }
+
+    /// Build boolean tag, which is a byte in NBT.
+    pub fn bool(value: bool) -> Self {
+        Self::Byte(value as i8)
+    }
+
+    /// Get tag type ID.
+    fn id(&self) -> u8 {
+        match self {
+            Self::Byte(_) => TAG_BYTE,
+            Self::Int(_) => TAG_INT,
+            Self::Long(_) => TAG_LONG,
+            Self::Float(_) => TAG_FLOAT,
+            Self::Double(_) => TAG_DOUBLE,
+            Self::String(_) => TAG_STRING,
+            Self::List(_) => TAG_LIST,
+            Self::Compound(_) => TAG_COMPOUND,
+        }
+    }
+
+    /// Encode as root tag, with an empty name.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.id());
+        write_string(&mut buf, "");
+        self.encode_payload(&mut buf);
+        buf
+    }
+
+    /// Encode tag payload, without type ID and name.
+    fn encode_payload(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Byte(value) => buf.extend_from_slice(&value.to_be_bytes()),
+            Self::Int(value) => buf.extend_from_slice(&value.to_be_bytes()),
+            Self::Long(value) => buf.extend_from_slice(&value.to_be_bytes()),
+            Self::Float(value) => buf.extend_from_slice(&value.to_be_bytes()),
+            Self::Double(value) => buf.extend_from_slice(&value.to_be_bytes()),
+            Self::String(value) => write_string(buf, value),
+            Self::List(tags) => {
+                buf.push(tags.first().map(|tag| tag.id()).unwrap_or(TAG_END));
+                buf.extend_from_slice(&(tags.len() as i32).to_be_bytes());
+                tags.iter().for_each(|tag| tag.encode_payload(buf));
+            }
+            Self::Compound(tags) => {
+                for (name, tag) in tags {
+                    buf.push(tag.id());
+                    write_string(buf, name);
+                    tag.encode_payload(buf);
+                }
+                buf.push(TAG_END);
+            }
+        }
+    }
+}
+
+/// Write NBT string, prefixed with its length.
+fn write_string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Get the byte length of the root NBT tag at the start of the buffer.
+///
+/// This allows skipping over or copying NBT data without decoding it.
+pub fn root_len(buf: &[u8]) -> Result<usize, ()> {
+    let id = *buf.first().ok_or(())?;
+    if id == TAG_END {
+        return Ok(1);
+    }
+
+    let name_len = string_len(&buf[1..])?;
+    let payload_len = payload_len(id, buf.get(1 + name_len..).ok_or(())?, 0)?;
+    Ok(1 + name_len + payload_len)
+}
+
+/// Get the byte length of an NBT string, including its length prefix.
+fn string_len(buf: &[u8]) -> Result<usize, ()> {
+    let len = u16::from_be_bytes(read_array(buf)?) as usize;
+    if buf.len() < 2 + len {
+        return Err(());
+    }
+    Ok(2 + len)
+}
+
+/// Get the byte length of an NBT tag payload of the given type.
+fn payload_len(id: u8, buf: &[u8], depth: usize) -> Result<usize, ()> {
+    if depth > MAX_DEPTH {
+        return Err(());
+    }
+
+    let len = match id {
+        TAG_END => 0,
+        TAG_BYTE => 1,
+        TAG_SHORT => 2,
+        TAG_INT | TAG_FLOAT => 4,
+        TAG_LONG | TAG_DOUBLE => 8,
+        TAG_BYTE_ARRAY => 4 + array_len(buf)?,
+        TAG_INT_ARRAY => 4 + array_len(buf)? * 4,
+        TAG_LONG_ARRAY => 4 + array_len(buf)? * 8,
+        TAG_STRING => string_len(buf)?,
+        TAG_LIST => {
+            let item_id = *buf.first().ok_or(())?;
+            let count = array_len(buf.get(1..).ok_or(())?)?;
+            let mut len = 5;
+            for _ in 0..count {
+                len += payload_len(item_id, buf.get(len..).ok_or(())?, depth + 1)?;
+            }
+            len
+        }
+        TAG_COMPOUND => {
+            let mut len = 0;
+            loop {
+                let item_id = *buf.get(len).ok_or(())?;
+                len += 1;
+                if item_id == TAG_END {
+                    break;
+                }
+                len += string_len(buf.get(len..).ok_or(())?)?;
+                len += payload_len(item_id, buf.get(len..).ok_or(())?, depth + 1)?;
+            }
+            len
+        }
+        _ => return Err(()),
+    };
+
+    if buf.len() < len {
+        return Err(());
+    }
+    Ok(len)
+}
+
+/// Read the length of an NBT array or list, which is a signed 32-bit integer.
+fn array_len(buf: &[u8]) -> Result<usize, ()> {
+    let len = i32::from_be_bytes(read_array(buf)?);
+    if len < 0 {
+        return Ok(0);
+    }
+    Ok(len as usize)
+}
+
+/// Read fixed size array from start of buffer.
+fn read_array<const N: usize>(buf: &[u8]) -> Result<[u8; N], ()> {
+    buf.get(..N).ok_or(())?.try_into().map_err(|_| ())
+}
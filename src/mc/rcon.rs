@@ -1,29 +1,133 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
 use rust_rcon::{Connection, Error as RconError};
+use thiserror::Error;
+
+/// Pooled RCON connection, reused for commands while the server is online.
+static POOL: Mutex<Option<Rcon>> = Mutex::new(None);
+
+/// RCON error.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Server didn't respond in time.
+    #[error("timed out")]
+    Timeout,
+
+    /// Server refused the password, or the connection failed.
+    #[error("{0}")]
+    Rcon(#[from] RconError),
+}
+
+impl Error {
+    /// Whether the server refused the password.
+    pub fn is_auth(&self) -> bool {
+        matches!(self, Self::Rcon(RconError::Auth))
+    }
+}
 
 /// An RCON client.
 pub struct Rcon {
     con: Connection,
+
+    /// Time to wait for a command response.
+    timeout: Duration,
 }
 
 impl Rcon {
-    /// Connect to a host.
-    pub async fn connect(addr: &str, pass: &str) -> Result<Self, ()> {
-        // Start connection
-        let con = Connection::builder()
+    /// Connect to a host, with a timeout for connecting and logging in, and invoking commands.
+    pub async fn connect(
+        addr: &str,
+        pass: &str,
+        connect_timeout: Duration,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        let connect = Connection::builder()
             .enable_minecraft_quirks(true)
-            .connect(addr, pass)
+            .connect(addr, pass);
+        let con = tokio::time::timeout(connect_timeout, connect)
             .await
-            .map_err(|err| {
-                dbg!(err);
-                ()
-            })?;
+            .map_err(|_| Error::Timeout)??;
+
+        Ok(Self { con, timeout })
+    }
+
+    /// Take the pooled connection, if there is any.
+    ///
+    /// Pooled connections may have gone stale.
+    pub fn take_pooled() -> Option<Self> {
+        POOL.lock().unwrap().take()
+    }
+
+    /// Return connection to the pool for reuse.
+    pub fn release(self) {
+        POOL.lock().unwrap().replace(self);
+    }
 
-        Ok(Self { con })
+    /// Drop the pooled connection, such as when the server stopped.
+    pub fn clear_pool() {
+        POOL.lock().unwrap().take();
+    }
+
+    /// Check health of the pooled connection, keeping it alive.
+    ///
+    /// Drops the pooled connection if it failed, so the next command reconnects.
+    pub async fn keepalive() {
+        if let Some(mut rcon) = Self::take_pooled() {
+            match rcon.cmd("list").await {
+                Ok(_) => rcon.release(),
+                Err(err) => {
+                    debug!(target: "lazymc::rcon", "Dropping stale RCON connection: {}", err)
+                }
+            }
+        }
     }
 
     /// Send command over RCON.
-    pub async fn cmd(&mut self, cmd: &str) -> Result<String, RconError> {
+    ///
+    /// Responses fragmented over multiple packets are joined. The connection must not be reused if
+    /// this fails, as a late response may still arrive.
+    pub async fn cmd(&mut self, cmd: &str) -> Result<String, Error> {
         debug!(target: "lazymc::rcon", "Sending RCON: {}", cmd);
-        self.con.cmd(cmd).await
+        tokio::time::timeout(self.timeout, self.con.cmd(cmd))
+            .await
+            .map_err(|_| Error::Timeout)?
+            .map_err(Error::from)
     }
+
+    /// Get names of online players.
+    pub async fn players(&mut self) -> Result<Vec<String>, Error> {
+        Ok(parse_players(&self.cmd("list").await?))
+    }
+}
+
+/// Parse online player count and names from list command response.
+///
+/// Falls back to the number of names if the count is missing.
+pub fn parse_list(response: &str) -> (u32, Vec<String>) {
+    let names = parse_players(response);
+    let online = response
+        .split(':')
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .find_map(|word| word.split('/').next()?.parse().ok())
+        .unwrap_or(names.len() as u32);
+    (online, names)
+}
+
+/// Parse player names from list command response.
+///
+/// Such as: `There are 2 of a max of 20 players online: Steve, Alex`
+fn parse_players(response: &str) -> Vec<String> {
+    let names = match response.split_once(':') {
+        Some((_, names)) => names,
+        None => return vec![],
+    };
+    names
+        .split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| name.into())
+        .collect()
 }
@@ -8,6 +8,24 @@ pub const FILE: &str = "server.properties";
 /// EOL in server.properties file.
 const EOL: &str = "\r\n";
 
+/// Load properties from server.properties file in dir.
+///
+/// Returns no properties if the file doesn't exist or can't be read.
+pub fn load(dir: &Path) -> HashMap<String, String> {
+    let contents = match fs::read_to_string(dir.join(FILE)) {
+        Ok(contents) => contents,
+        Err(_) => return HashMap::new(),
+    };
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && !line.is_empty())
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_string()))
+        .collect()
+}
+
 /// Try to rewrite changes in server.properties file in dir.
 ///
 /// Prints an error and stops on failure.
@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+/// Session server endpoint to verify a joining player.
+const HAS_JOINED_URL: &str = "https://sessionserver.mojang.com/session/minecraft/hasJoined";
+
+/// Authenticated player profile, as returned by the session server.
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    /// Player UUID, hexadecimal without hyphens.
+    pub id: String,
+
+    /// Player username.
+    pub name: String,
+}
+
+/// Compute server hash sent to the session server for the encryption exchange.
+///
+/// This is a SHA-1 digest in Minecraft's signed hexadecimal format.
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(server_id.as_bytes());
+    hasher.update(shared_secret);
+    hasher.update(public_key);
+    let mut digest: [u8; 20] = hasher.finalize().into();
+
+    // Digest is a signed big-endian number, take two's complement if negative
+    let negative = digest[0] & 0x80 != 0;
+    if negative {
+        let mut carry = true;
+        for byte in digest.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                let (value, overflow) = byte.overflowing_add(1);
+                *byte = value;
+                carry = overflow;
+            }
+        }
+    }
+
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    let hex = hex.trim_start_matches('0');
+    format!("{}{}", if negative { "-" } else { "" }, hex)
+}
+
+/// Check with the session server whether the player joined with the given server hash.
+///
+/// Returns the player profile if authenticated, `None` if not.
+pub async fn has_joined(username: &str, server_hash: &str) -> Result<Option<Profile>, ()> {
+    let request = ureq::get(HAS_JOINED_URL)
+        .query("username", username)
+        .query("serverId", server_hash);
+
+    tokio::task::spawn_blocking(move || {
+        let response = request.call().map_err(|err| {
+            error!(target: "lazymc", "Failed to reach session server: {}", err);
+        })?;
+
+        // Session server responds with no content if not authenticated
+        if response.status() != 200 {
+            return Ok(None);
+        }
+
+        response.into_json().map(Some).map_err(|err| {
+            error!(target: "lazymc", "Failed to parse session server response: {}", err);
+        })
+    })
+    .await
+    .map_err(|_| ())?
+}
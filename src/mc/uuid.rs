@@ -0,0 +1,29 @@
+/// Offline player namespace.
+const OFFLINE_PLAYER_NAMESPACE: &str = "OfflinePlayer:";
+
+/// Get offline player UUID from username.
+///
+/// This is a version 3 UUID derived from the username, matching what a server in offline mode
+/// assigns to the player.
+pub fn offline_player_uuid(username: &str) -> [u8; 16] {
+    let mut uuid = md5::compute(format!("{}{}", OFFLINE_PLAYER_NAMESPACE, username)).0;
+
+    // Set version 3 and IETF variant
+    uuid[6] = (uuid[6] & 0x0f) | 0x30;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+
+    uuid
+}
+
+/// Format UUID as hyphenated hexadecimal string.
+pub fn to_hyphenated(uuid: [u8; 16]) -> String {
+    let hex: String = uuid.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::mc::list;
+
+/// Whitelist file name.
+pub const WHITELIST_FILE: &str = "whitelist.json";
+
+/// Operators file name.
+pub const OPS_FILE: &str = "ops.json";
+
+/// Player entry in whitelist or operators file.
+#[derive(Debug, Deserialize)]
+struct Entry {
+    /// Player UUID, hyphenated.
+    #[serde(default)]
+    uuid: String,
+
+    /// Player username.
+    #[serde(default)]
+    name: String,
+}
+
+/// Set of players, matched by username or UUID.
+#[derive(Debug, Default)]
+pub struct Players {
+    /// Lowercase usernames.
+    names: HashSet<String>,
+
+    /// Lowercase UUIDs without hyphens.
+    uuids: HashSet<String>,
+}
+
+impl Players {
+    /// Load players from given JSON list files in dir.
+    ///
+    /// Files that don't exist are skipped. Prints an error for files that fail to load.
+    pub fn load(dir: &Path, files: &[&str]) -> Self {
+        let mut players = Self::default();
+        for entry in files.iter().flat_map(|file| list::load::<Entry>(dir, file)) {
+            players.names.insert(entry.name.to_lowercase());
+            players.uuids.insert(normalize_uuid(&entry.uuid));
+        }
+        players
+    }
+
+    /// Add players by username or UUID.
+    pub fn extend<'a>(&mut self, players: impl IntoIterator<Item = &'a String>) {
+        for player in players {
+            self.names.insert(player.to_lowercase());
+            self.uuids.insert(normalize_uuid(player));
+        }
+    }
+
+    /// Check whether the player with the given username or UUID is in the set.
+    ///
+    /// Only matches by UUID if given, as usernames can be spoofed in offline mode.
+    pub fn contains(&self, name: &str, uuid: Option<&str>) -> bool {
+        match uuid {
+            Some(uuid) => self.uuids.contains(&normalize_uuid(uuid)),
+            None => self.names.contains(&name.to_lowercase()),
+        }
+    }
+}
+
+/// Normalize UUID for comparison, lowercase without hyphens.
+pub fn normalize_uuid(uuid: &str) -> String {
+    uuid.replace('-', "").to_lowercase()
+}
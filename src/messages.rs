@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::proto::Client;
+use crate::server::ServerState;
+
+/// Replace placeholders in a message with values from the server state.
+///
+/// Placeholder texts are taken from the configured messages.
+///
+/// Supports:
+/// - `{eta}`: estimated time until the server is online, such as `in ~45s`
+/// - `{progress}`: start progress reported by the server, such as `47%`
+/// - `{last_online}`: time since players were last online, such as `3h 12m ago`
+/// - `{time_asleep}`: time since the server went to sleep, such as `3h 12m`
+/// - `{version}`: last known server version name
+/// - `{wake_count}`: number of times the server was started by lazymc
+/// - `{wakes_today}`: number of times the server was started today
+pub fn render(message: &str, config: &Config, server: &ServerState) -> String {
+    if !message.contains('{') {
+        return message.into();
+    }
+
+    let messages = &config.messages;
+    let mut message = with_eta(message, config, server);
+    if message.contains("{progress}") {
+        let progress = match server.start_progress() {
+            Some(percent) => messages.progress.replace("{percent}", &percent.to_string()),
+            None => messages.progress_unknown.clone(),
+        };
+        message = message.replace("{progress}", &progress);
+    }
+    if message.contains("{last_online}") {
+        let last_online = match server.last_active_time() {
+            Some(time) => messages
+                .time_ago
+                .replace("{time}", &format_duration(time.elapsed())),
+            None => messages.time_never.clone(),
+        };
+        message = message.replace("{last_online}", &last_online);
+    }
+    if message.contains("{time_asleep}") {
+        let time_asleep = match server.stop_time() {
+            Some(time) if !server.starting() => format_duration(time.elapsed()),
+            _ => messages.time_unknown.clone(),
+        };
+        message = message.replace("{time_asleep}", &time_asleep);
+    }
+    if message.contains("{version}") {
+        let version = server
+            .clone_status()
+            .map(|status| status.version.name)
+            .unwrap_or_else(|| messages.time_unknown.clone());
+        message = message.replace("{version}", &version);
+    }
+    message
+        .replace("{wake_count}", &server.wake_count().to_string())
+        .replace("{wakes_today}", &server.wakes_today().to_string())
+}
+
+/// Replace the `{username}` placeholder in a message with the client username, once known.
+pub fn with_username(message: &str, client: &Client) -> String {
+    if !message.contains("{username}") {
+        return message.into();
+    }
+
+    message.replace("{username}", &client.username().unwrap_or_default())
+}
+
+/// Replace the `{eta}` placeholder in a message with the estimated time until the server is
+/// online, such as `in ~45s`.
+pub fn with_eta(message: &str, config: &Config, server: &ServerState) -> String {
+    if !message.contains("{eta}") {
+        return message.into();
+    }
+
+    let eta = match server.start_eta() {
+        Some(eta) if eta.as_secs() > 0 => config
+            .messages
+            .eta
+            .replace("{seconds}", &eta.as_secs().to_string()),
+        _ => config.messages.eta_unknown.clone(),
+    };
+    message.replace("{eta}", &eta)
+}
+
+/// Format duration in a short human readable form, with its two most significant units.
+///
+/// For example `2d 3h`, `3h 12m`, `5m 2s` or `42s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, mins, secs) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    match (days, hours, mins) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, _) => format!("{}m {}s", mins, secs),
+        (0, _, _) => format!("{}h {}m", hours, mins),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
@@ -2,81 +2,418 @@
 
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use bytes::BytesMut;
-use minecraft_protocol::data::server_status::ServerStatus;
-use minecraft_protocol::decoder::Decoder;
+use minecraft_protocol::data::server_status::{OnlinePlayers, ServerStatus, ServerVersion};
 use minecraft_protocol::encoder::Encoder;
 use minecraft_protocol::version::v1_14_4::handshake::Handshake;
-use minecraft_protocol::version::v1_14_4::status::StatusResponse;
 use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
+use tokio::sync::watch;
 
-use crate::config::Config;
-use crate::proto::{self, ClientState, RawPacket, PROTO_DEFAULT_PROTOCOL};
-use crate::server::ServerState;
+use crate::chat;
+use crate::config::{Config, Probe, ReadySignal};
+use crate::messages;
+use crate::persist;
+use crate::proto::{
+    self, Client, ClientState, RawPacket, PROTO_DEFAULT_PROTOCOL, PROTO_DEFAULT_VERSION,
+};
+use crate::proxy;
+use crate::server::{self, ServerState, SleepCountdown, State};
+use crate::types;
 
-/// Monitor ping inverval in seconds.
-const MONITOR_PING_INTERVAL: u64 = 2;
+/// Interval to save server state to persist across restarts.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(60);
 
-/// Status request timeout in seconds.
-const STATUS_TIMEOUT: u64 = 8;
+/// Interval to check health of the pooled RCON connection.
+#[cfg(feature = "rcon")]
+const RCON_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
 
 /// Monitor server.
 pub async fn monitor_server(config: Arc<Config>, state: Arc<ServerState>) {
     // Server address
     let addr = config.server.address;
 
+    #[cfg(feature = "rcon")]
+    let mut last_keepalive = Instant::now();
+
+    // Number of consecutive failed polls
+    let mut failures = 0;
+
+    // Server state transitions, to react to without waiting for the next poll
+    let mut state_changes = state.subscribe();
+
+    // Time state was last saved at
+    let mut last_persist = Instant::now();
+
+    // Polling backoff while the server is sleeping
+    let mut backoff = config.monitor.interval();
+    let mut next_poll = Instant::now();
+
     loop {
+        // Start server within keep awake schedule, unless it crashed or failed to start
+        if config.schedule.keep_awake()
+            && (!state.starting() || state.frozen())
+            && state.crashes() == 0
+            && state.start_failed_cooldown(&config).is_none()
+        {
+            info!(target: "lazymc::monitor", "Starting server to keep it awake as scheduled");
+            server::start_server(config.clone(), state.clone());
+        }
+
+        // Periodically save state to persist across restarts
+        if last_persist.elapsed() >= PERSIST_INTERVAL {
+            persist::save(&config, &state.persisted());
+            last_persist = Instant::now();
+        }
+
+        // Don't poll frozen server, it won't respond
+        if state.frozen() {
+            wait(&config, &mut state_changes).await;
+            continue;
+        }
+
+        // Back off polling while the server is sleeping, poll at full rate once it starts
+        if !state.online() && !state.starting() {
+            if config.monitor.pause_while_sleeping || Instant::now() < next_poll {
+                wait(&config, &mut state_changes).await;
+                continue;
+            }
+            next_poll = Instant::now() + backoff;
+            backoff = (backoff * 2).min(config.monitor.backoff_max());
+        } else {
+            backoff = config.monitor.interval();
+        }
+
         // Poll server state and update internal status
         trace!(target: "lazymc::monitor", "Fetching status for {} ... ", addr);
-        let status = poll_server(addr).await;
-        state.update_status(&config, status);
+        let status = poll_server(&config).await;
+        let status = match status {
+            None if config.monitor.probe != Probe::Status => {
+                probe_fallback(&config, &state, addr).await
+            }
+            status => status,
+        };
+        #[cfg(feature = "rcon")]
+        let status = match status {
+            Some(status) if config.rcon.poll_players => {
+                Some(poll_players_rcon(&config, status).await)
+            }
+            status => status,
+        };
+        if let Some(favicon) = status.as_ref().and_then(|status| status.favicon.clone()) {
+            state.set_favicon(favicon);
+        }
+        #[cfg(feature = "rcon")]
+        let was_online = state.online();
+
+        // Check readiness signals of the starting server
+        if state.state() == State::Starting {
+            poll_ready(&config, &state).await;
+        }
+
+        // Tolerate failed polls of an online server, until the configured number is reached
+        if status.is_some() {
+            failures = 0;
+        } else {
+            failures += 1;
+        }
+        if status.is_none()
+            && state.online()
+            && !state.stopping()
+            && failures < config.monitor.failures_before_offline
+        {
+            debug!(
+                target: "lazymc::monitor",
+                "Failed to poll server status ({}/{}), still considering it online",
+                failures,
+                config.monitor.failures_before_offline,
+            );
+        } else {
+            state.update_status(&config, status.map(|status| status.server_status));
+        }
+
+        // Invoke RCON wake commands once the server came online
+        #[cfg(feature = "rcon")]
+        if !was_online && state.online() && !config.rcon.wake_commands.is_empty() {
+            debug!(target: "lazymc::monitor", "Invoking RCON wake commands");
+            server::rcon_commands(&config, &config.rcon.wake_commands).await;
+        }
 
-        // Sleep server when it's bedtime
+        // Admit queued clients once server is online
+        if config.join.queue.enabled && state.online() {
+            let admitted = state.queue.admit(config.join.queue.admit as usize);
+            if admitted > 0 {
+                debug!(target: "lazymc::monitor", "Admitted {} queued clients", admitted);
+            }
+        }
+
+        // Sleep server when it's bedtime, after warning players with a countdown
         if state.should_sleep(&config) {
-            info!(target: "lazymc::montior", "Server has been idle, sleeping...");
-            if !state.kill_server(&config).await {
-                warn!(target: "lazymc", "Failed to stop server");
+            match state.sleep_countdown(&config) {
+                SleepCountdown::Sleep => {
+                    info!(target: "lazymc::montior", "Server has been idle, sleeping...");
+                    #[cfg(feature = "rcon")]
+                    server::rcon_commands(&config, &config.rcon.sleep_commands).await;
+                    if !state.sleep_server(&config).await {
+                        warn!(target: "lazymc", "Failed to stop server");
+                    }
+                }
+                SleepCountdown::Warn(seconds) => {
+                    debug!(target: "lazymc::monitor", "Sleeping server in {}s", seconds);
+                    let time = messages::format_duration(Duration::from_secs(seconds as u64));
+                    let message = config.messages.sleep_warning.replace("{time}", &time);
+                    server::broadcast(&config, &message).await;
+                }
+                SleepCountdown::Wait => {}
             }
+        } else if state.cancel_sleep_countdown() {
+            debug!(target: "lazymc::monitor", "Server no longer idle, cancelled sleep countdown");
+        }
+
+        // Invoke RCON commands queued while the server was not online
+        #[cfg(feature = "rcon")]
+        if state.online() {
+            crate::rcon::invoke_queued(&config, &state).await;
+        }
+
+        // Keep pooled RCON connection alive while online
+        #[cfg(feature = "rcon")]
+        if state.online() && last_keepalive.elapsed() >= RCON_KEEPALIVE_INTERVAL {
+            crate::mc::rcon::Rcon::keepalive().await;
+            last_keepalive = Instant::now();
         }
 
         // TODO: use interval instead, for a more reliable polling interval?
-        tokio::time::sleep(Duration::from_secs(MONITOR_PING_INTERVAL)).await;
+        wait(&config, &mut state_changes).await;
     }
 }
 
+/// Wait for the monitor interval, or until the server state changes.
+async fn wait(config: &Config, state_changes: &mut watch::Receiver<State>) {
+    let _ = tokio::time::timeout(config.monitor.interval(), state_changes.changed()).await;
+}
+
+/// Server status, as fetched from the server.
+#[derive(Debug)]
+pub struct Status {
+    /// Server status.
+    pub server_status: ServerStatus,
+
+    /// Server favicon, as base64 encoded PNG data URI.
+    pub favicon: Option<String>,
+}
+
 /// Poll server state.
 ///
 /// Returns server status if connection succeeded.
-pub async fn poll_server(addr: SocketAddr) -> Option<ServerStatus> {
-    fetch_status(addr).await.ok()
+pub async fn poll_server(config: &Config) -> Option<Status> {
+    fetch_status(config).await.ok()
+}
+
+/// Poll the readiness signals of the starting server that aren't reported elsewhere.
+async fn poll_ready(config: &Config, state: &ServerState) {
+    let signals = &config.monitor.ready_signals;
+    if signals.contains(&ReadySignal::Port) {
+        let open = poll_port(config.server.address, config.monitor.timeout()).await;
+        state.signal_ready(config, ReadySignal::Port, open);
+    }
+
+    #[cfg(feature = "rcon")]
+    if signals.contains(&ReadySignal::Rcon) {
+        let login = poll_rcon(config).await;
+        state.signal_ready(config, ReadySignal::Rcon, login);
+    }
+
+    state.check_ready(config);
+}
+
+/// Check whether the server port accepts a TCP connection.
+async fn poll_port(addr: SocketAddr, timeout: Duration) -> bool {
+    tokio::time::timeout(timeout, TcpStream::connect(addr))
+        .await
+        .map(|stream| stream.is_ok())
+        .unwrap_or(false)
+}
+
+/// Check whether logging in to server RCON succeeds.
+///
+/// Keeps the connection pooled for later use if it does.
+#[cfg(feature = "rcon")]
+async fn poll_rcon(config: &Config) -> bool {
+    use crate::mc::rcon::Rcon;
+
+    if !config.rcon.enabled {
+        return false;
+    }
+
+    let mut addr = config.server.address;
+    addr.set_port(config.rcon.port);
+    let connect_timeout = config.monitor.timeout();
+    let timeout = Duration::from_secs(config.rcon.command_timeout as u64);
+    match Rcon::connect(
+        &addr.to_string(),
+        &config.rcon.password,
+        connect_timeout,
+        timeout,
+    )
+    .await
+    {
+        Ok(rcon) => {
+            rcon.release();
+            true
+        }
+        Err(err) => {
+            trace!(target: "lazymc::monitor", "RCON not ready: {}", err);
+            false
+        }
+    }
+}
+
+/// Replace online players in the status with players listed through RCON.
+///
+/// Keeps the status as-is if listing players failed.
+#[cfg(feature = "rcon")]
+async fn poll_players_rcon(config: &Config, mut status: Status) -> Status {
+    let response = match server::rcon_query(config, "list").await {
+        Ok(response) => response,
+        Err(_) => return status,
+    };
+    let (online, names) = crate::mc::rcon::parse_list(&response);
+    trace!(target: "lazymc::monitor", "Listed {} players through RCON", online);
+
+    let players = &mut status.server_status.players;
+    players.online = online;
+    players.sample = names
+        .into_iter()
+        .filter_map(|name| {
+            let id = crate::mc::uuid::to_hyphenated(crate::mc::uuid::offline_player_uuid(&name));
+            serde_json::from_value(serde_json::json!({ "name": name, "id": id })).ok()
+        })
+        .collect();
+    status
 }
 
 /// Attemp to fetch status from server.
-async fn fetch_status(addr: SocketAddr) -> Result<ServerStatus, ()> {
-    let mut stream = TcpStream::connect(addr).await.map_err(|_| ())?;
+async fn fetch_status(config: &Config) -> Result<Status, ()> {
+    let mut stream = proxy::connect_to_server_local(config)
+        .await
+        .map_err(|_| ())?;
+    let client = Client::default();
 
-    send_handshake(&mut stream, addr).await?;
-    request_status(&mut stream).await?;
-    wait_for_status_timeout(&mut stream).await
+    send_handshake(
+        &client,
+        &mut stream,
+        config.server.address,
+        PROTO_DEFAULT_PROTOCOL as i32,
+        ClientState::Status,
+    )
+    .await?;
+    request_status(&client, &mut stream).await?;
+    wait_for_status_timeout(&client, &mut stream, config.monitor.timeout()).await
+}
+
+/// Probe whether the server is online with the configured fallback, after polling its status
+/// failed.
+///
+/// Returns a status without players if online, with the version and description of the last known
+/// status.
+async fn probe_fallback(config: &Config, state: &ServerState, addr: SocketAddr) -> Option<Status> {
+    let timeout = config.monitor.timeout();
+    let online = match config.monitor.probe {
+        Probe::Status => false,
+        Probe::Tcp => poll_port(addr, timeout).await,
+        Probe::Login => tokio::time::timeout(timeout, probe_login(config))
+            .await
+            .map(|result| result.is_ok())
+            .unwrap_or(false),
+    };
+    if !online {
+        return None;
+    }
+    trace!(target: "lazymc::monitor", "Status poll failed, but server responded to probe");
+
+    let server_status = match state.clone_status() {
+        Some(mut status) => {
+            status.players.online = 0;
+            status.players.sample.clear();
+            status
+        }
+        None => ServerStatus {
+            version: ServerVersion {
+                name: String::from(PROTO_DEFAULT_VERSION),
+                protocol: PROTO_DEFAULT_PROTOCOL,
+            },
+            description: chat::message(""),
+            players: OnlinePlayers {
+                online: 0,
+                max: 0,
+                sample: vec![],
+            },
+        },
+    };
+    Some(Status {
+        server_status,
+        favicon: None,
+    })
+}
+
+/// Probe server by starting to log in with an unsupported protocol version.
+///
+/// Succeeds if the server responds with any login packet, which is a disconnect for the outdated
+/// client. The server never lets the probe join.
+async fn probe_login(config: &Config) -> Result<(), ()> {
+    let mut stream = proxy::connect_to_server_local(config)
+        .await
+        .map_err(|_| ())?;
+    let client = Client::default();
+
+    send_handshake(
+        &client,
+        &mut stream,
+        config.server.address,
+        0,
+        ClientState::Login,
+    )
+    .await?;
+    client.set_state(ClientState::Login);
+
+    let raw = RawPacket::new(
+        proto::LOGIN_PACKET_ID_LOGIN_START,
+        types::encode_string("lazymc")?,
+    )
+    .encode(&client)
+    .map_err(|_| ())?;
+    stream.write_all(&raw).await.map_err(|_| ())?;
+
+    let mut buf = BytesMut::new();
+    match proto::read_packet(&client, &mut buf, &mut stream).await? {
+        Some(_) => Ok(()),
+        None => Err(()),
+    }
 }
 
 /// Send handshake.
-async fn send_handshake(stream: &mut TcpStream, addr: SocketAddr) -> Result<(), ()> {
+async fn send_handshake(
+    client: &Client,
+    stream: &mut TcpStream,
+    addr: SocketAddr,
+    protocol_version: i32,
+    next_state: ClientState,
+) -> Result<(), ()> {
     let handshake = Handshake {
-        protocol_version: PROTO_DEFAULT_PROTOCOL as i32,
+        protocol_version,
         server_addr: addr.ip().to_string(),
         server_port: addr.port(),
-        next_state: ClientState::Status.to_id(),
+        next_state: next_state.to_id(),
     };
 
     let mut packet = Vec::new();
     handshake.encode(&mut packet).map_err(|_| ())?;
 
     let raw = RawPacket::new(proto::HANDSHAKE_PACKET_ID_HANDSHAKE, packet)
-        .encode()
+        .encode(client)
         .map_err(|_| ())?;
     stream.write_all(&raw).await.map_err(|_| ())?;
 
@@ -84,23 +421,23 @@ async fn send_handshake(stream: &mut TcpStream, addr: SocketAddr) -> Result<(),
 }
 
 /// Send status request.
-async fn request_status(stream: &mut TcpStream) -> Result<(), ()> {
+async fn request_status(client: &Client, stream: &mut TcpStream) -> Result<(), ()> {
     let raw = RawPacket::new(proto::STATUS_PACKET_ID_STATUS, vec![])
-        .encode()
+        .encode(client)
         .map_err(|_| ())?;
     stream.write_all(&raw).await.map_err(|_| ())?;
     Ok(())
 }
 
 /// Wait for a status response.
-async fn wait_for_status(stream: &mut TcpStream) -> Result<ServerStatus, ()> {
+async fn wait_for_status(client: &Client, stream: &mut TcpStream) -> Result<Status, ()> {
     // Get stream reader, set up buffer
     let (mut reader, mut _writer) = stream.split();
     let mut buf = BytesMut::new();
 
     loop {
         // Read packet from stream
-        let (packet, _raw) = match proto::read_packet(&mut buf, &mut reader).await {
+        let (packet, _raw) = match proto::read_packet(client, &mut buf, &mut reader).await {
             Ok(Some(packet)) => packet,
             Ok(None) => break,
             Err(_) => continue,
@@ -108,8 +445,7 @@ async fn wait_for_status(stream: &mut TcpStream) -> Result<ServerStatus, ()> {
 
         // Catch status response
         if packet.id == proto::STATUS_PACKET_ID_STATUS {
-            let status = StatusResponse::decode(&mut packet.data.as_slice()).map_err(|_| ())?;
-            return Ok(status.server_status);
+            return decode_status(&packet.data);
         }
     }
 
@@ -117,10 +453,33 @@ async fn wait_for_status(stream: &mut TcpStream) -> Result<ServerStatus, ()> {
     Err(())
 }
 
+/// Decode status response packet data.
+///
+/// Decodes the JSON status manually to also capture the favicon.
+fn decode_status(buf: &[u8]) -> Result<Status, ()> {
+    let (_, json) = types::read_string(buf)?;
+    let mut status: serde_json::Value = serde_json::from_str(&json).map_err(|_| ())?;
+
+    let favicon = status
+        .as_object_mut()
+        .and_then(|status| status.remove("favicon"))
+        .and_then(|favicon| favicon.as_str().map(|favicon| favicon.into()));
+    let server_status = serde_json::from_value(status).map_err(|_| ())?;
+
+    Ok(Status {
+        server_status,
+        favicon,
+    })
+}
+
 /// Wait for a status response.
-async fn wait_for_status_timeout(stream: &mut TcpStream) -> Result<ServerStatus, ()> {
-    let status = wait_for_status(stream);
-    tokio::time::timeout(Duration::from_secs(STATUS_TIMEOUT), status)
+async fn wait_for_status_timeout(
+    client: &Client,
+    stream: &mut TcpStream,
+    timeout: Duration,
+) -> Result<Status, ()> {
+    let status = wait_for_status(client, stream);
+    tokio::time::timeout(timeout, status)
         .await
         .map_err(|_| ())?
 }
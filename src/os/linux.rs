@@ -0,0 +1,154 @@
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::time::Duration;
+
+use super::Usage;
+
+/// TCP socket state for listening sockets in procfs.
+const TCP_LISTEN: &str = "0A";
+
+/// Find PID of the process listening on the given TCP port on Linux from procfs.
+///
+/// Only finds processes of the same user, unless running as root.
+pub fn find_listener(port: u16) -> Option<u32> {
+    // Find inode of listening socket
+    let port = format!("{:04X}", port);
+    let inode = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|table| {
+            table
+                .lines()
+                .skip(1)
+                .map(|line| {
+                    line.split_whitespace()
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .find(|fields| {
+            fields.len() > 9
+                && fields[1].rsplit_once(':').is_some_and(|(_, p)| p == port)
+                && fields[3] == TCP_LISTEN
+        })
+        .map(|fields| format!("socket:[{}]", fields[9]))?;
+
+    // Find process having the socket open
+    fs::read_dir("/proc").ok()?.flatten().find_map(|entry| {
+        let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+        let has_socket = fs::read_dir(entry.path().join("fd"))
+            .ok()?
+            .flatten()
+            .any(|fd| {
+                fs::read_link(fd.path()).is_ok_and(|link| link.as_os_str() == inode.as_str())
+            });
+        has_socket.then_some(pid)
+    })
+}
+
+/// Get available memory on Linux from procfs.
+pub fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let available = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?;
+    let kib: u64 = available.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Get process resource usage on Linux from procfs.
+pub fn usage(pid: u32) -> Option<Usage> {
+    // CPU time fields follow the process name, which may contain spaces
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+    let mut fields = fields.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+
+    let statm = fs::read_to_string(format!("/proc/{}/statm", pid)).ok()?;
+    let resident: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+
+    let (ticks, page_size) = unsafe {
+        (
+            libc::sysconf(libc::_SC_CLK_TCK),
+            libc::sysconf(libc::_SC_PAGESIZE),
+        )
+    };
+    if ticks <= 0 || page_size <= 0 {
+        return None;
+    }
+
+    Some(Usage {
+        cpu_time: Duration::from_secs_f64((utime + stime) as f64 / ticks as f64),
+        memory: resident * page_size as u64,
+    })
+}
+
+/// Get time since data was last received on a TCP socket on Linux from `TCP_INFO`.
+pub fn tcp_idle(fd: RawFd) -> Option<Duration> {
+    let mut info: libc::tcp_info = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut libc::tcp_info as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return None;
+    }
+    Some(Duration::from_millis(info.tcpi_last_data_recv as u64))
+}
+
+/// Pipe to move data between sockets within the kernel with `splice(2)`.
+pub struct Pipe {
+    read: OwnedFd,
+    write: OwnedFd,
+}
+
+impl Pipe {
+    /// Create non-blocking pipe.
+    pub fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Safety: file descriptors were just created, and are owned by nothing else
+        unsafe {
+            Ok(Self {
+                read: OwnedFd::from_raw_fd(fds[0]),
+                write: OwnedFd::from_raw_fd(fds[1]),
+            })
+        }
+    }
+
+    /// Move up to `len` bytes from the given file descriptor into the pipe.
+    ///
+    /// Returns 0 if the file descriptor reached end of file.
+    pub fn splice_from(&self, fd: RawFd, len: usize) -> io::Result<usize> {
+        splice(fd, self.write.as_raw_fd(), len)
+    }
+
+    /// Move up to `len` bytes from the pipe into the given file descriptor.
+    pub fn splice_to(&self, fd: RawFd, len: usize) -> io::Result<usize> {
+        splice(self.read.as_raw_fd(), fd, len)
+    }
+}
+
+/// Move up to `len` bytes between file descriptors without blocking, one of which must be a pipe.
+fn splice(from: RawFd, to: RawFd, len: usize) -> io::Result<usize> {
+    let flags = libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK;
+    let result = unsafe { libc::splice(from, ptr::null_mut(), to, ptr::null_mut(), len, flags) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result as usize)
+}
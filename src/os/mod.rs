@@ -1,6 +1,24 @@
+#[cfg(target_os = "linux")]
+pub mod linux;
 #[cfg(unix)]
 pub mod unix;
 
+use std::io;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+/// Process resource usage.
+#[derive(Debug, Copy, Clone)]
+pub struct Usage {
+    /// Total CPU time used.
+    pub cpu_time: Duration,
+
+    /// Resident memory in bytes.
+    pub memory: u64,
+}
+
 /// Gracefully kill process.
 ///
 /// # Panics
@@ -18,3 +36,114 @@ pub fn kill_gracefully(pid: u32) {
         "gracefully killing Minecraft server process not implemented on non-Unix platforms"
     );
 }
+
+/// Forcefully kill process.
+///
+/// Returns `false` if not supported on this platform.
+#[allow(unreachable_code, unused_variables)]
+pub fn kill(pid: u32) -> bool {
+    #[cfg(unix)]
+    unsafe {
+        return unix::kill(pid);
+    }
+
+    false
+}
+
+/// Whether the process is still running.
+///
+/// Always `false` if not supported on this platform.
+#[allow(unreachable_code, unused_variables)]
+pub fn is_running(pid: u32) -> bool {
+    #[cfg(unix)]
+    unsafe {
+        return unix::is_running(pid);
+    }
+
+    false
+}
+
+/// Find PID of the process listening on the given TCP port.
+///
+/// Returns `None` if not found, or if not supported on this platform.
+#[allow(unreachable_code, unused_variables)]
+pub fn find_listener(port: u16) -> Option<u32> {
+    #[cfg(target_os = "linux")]
+    return linux::find_listener(port);
+
+    None
+}
+
+/// Environment variables a service manager passes listeners with, see [`listen_fds`].
+pub const LISTEN_ENV: [&str; 3] = ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"];
+
+/// Take TCP listeners passed by a service manager through socket activation.
+///
+/// Returns an empty list if none were passed, or if not supported on this platform.
+#[allow(unreachable_code)]
+pub fn listen_fds() -> io::Result<Vec<TcpListener>> {
+    #[cfg(unix)]
+    return unix::listen_fds();
+
+    Ok(vec![])
+}
+
+/// Get time since data was last received on a TCP socket.
+///
+/// Returns `None` if unknown, or if not supported on this platform.
+#[cfg(unix)]
+#[allow(unreachable_code, unused_variables)]
+pub fn tcp_idle(fd: RawFd) -> Option<Duration> {
+    #[cfg(target_os = "linux")]
+    return linux::tcp_idle(fd);
+
+    None
+}
+
+/// Freeze process, pausing it until it is unfrozen.
+///
+/// Returns `false` if not supported on this platform.
+#[allow(unreachable_code, unused_variables)]
+pub fn freeze(pid: u32) -> bool {
+    #[cfg(unix)]
+    unsafe {
+        return unix::freeze(pid);
+    }
+
+    false
+}
+
+/// Unfreeze process, resuming it after it was frozen.
+///
+/// Returns `false` if not supported on this platform.
+#[allow(unreachable_code, unused_variables)]
+pub fn unfreeze(pid: u32) -> bool {
+    #[cfg(unix)]
+    unsafe {
+        return unix::unfreeze(pid);
+    }
+
+    false
+}
+
+/// Get memory in bytes available for starting new processes.
+///
+/// Returns `None` if not supported on this platform.
+#[allow(unreachable_code)]
+pub fn available_memory() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    return linux::available_memory();
+
+    None
+}
+
+/// Get process resource usage.
+///
+/// Returns `None` if not supported on this platform.
+#[allow(unreachable_code, unused_variables)]
+pub fn usage(pid: u32) -> Option<Usage> {
+    #[cfg(target_os = "linux")]
+    return linux::usage(pid);
+
+    None
+}
@@ -9,3 +9,82 @@ pub unsafe fn kill_gracefully(pid: u32) {
     // TODO: send sigterm to childs as well?
     // TODO: handle error if result != 0
 }
+
+/// Forcefully kill process on Unix by sending SIGKILL.
+///
+/// This is unsafe because the PID isn't checked.
+pub unsafe fn kill(pid: u32) -> bool {
+    debug!(target: "lazymc", "Sending SIGKILL signal to {} to kill server", pid);
+    let result = libc::kill(pid as i32, libc::SIGKILL);
+    trace!(target: "lazymc", "SIGKILL result: {}", result);
+    result == 0
+}
+
+/// Check whether process is running on Unix by sending no signal.
+///
+/// This is unsafe because the PID isn't checked.
+pub unsafe fn is_running(pid: u32) -> bool {
+    libc::kill(pid as i32, 0) == 0
+        || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Freeze process on Unix by sending SIGSTOP.
+///
+/// This is unsafe because the PID isn't checked.
+pub unsafe fn freeze(pid: u32) -> bool {
+    debug!(target: "lazymc", "Sending SIGSTOP signal to {} to freeze server", pid);
+    let result = libc::kill(pid as i32, libc::SIGSTOP);
+    trace!(target: "lazymc", "SIGSTOP result: {}", result);
+    result == 0
+}
+
+/// Unfreeze process on Unix by sending SIGCONT.
+///
+/// This is unsafe because the PID isn't checked.
+pub unsafe fn unfreeze(pid: u32) -> bool {
+    debug!(target: "lazymc", "Sending SIGCONT signal to {} to unfreeze server", pid);
+    let result = libc::kill(pid as i32, libc::SIGCONT);
+    trace!(target: "lazymc", "SIGCONT result: {}", result);
+    result == 0
+}
+
+/// Take TCP listeners passed by a service manager through socket activation, such as systemd.
+///
+/// Follows the `sd_listen_fds(3)` protocol. Returns an empty list if none were passed to this
+/// process.
+pub fn listen_fds() -> std::io::Result<Vec<std::net::TcpListener>> {
+    use std::os::unix::io::FromRawFd;
+
+    /// First file descriptor passed by the service manager.
+    const LISTEN_FDS_START: i32 = 3;
+
+    let pid = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse().ok());
+    if pid != Some(std::process::id()) {
+        return Ok(vec![]);
+    }
+    let count: i32 = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0);
+
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            // The service manager hands these file descriptors to us
+            let socket = unsafe { socket2::Socket::from_raw_fd(fd) };
+            if socket.r#type()? != socket2::Type::STREAM
+                || socket.local_addr()?.as_socket().is_none()
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("passed file descriptor {} is not a TCP socket", fd),
+                ));
+            }
+
+            // Don't leak the listener to the server process
+            socket.set_cloexec(true)?;
+            Ok(socket.into())
+        })
+        .collect()
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use minecraft_protocol::data::server_status::ServerStatus;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// State file name, in the server directory.
+pub const FILE: &str = "lazymc.state.json";
+
+/// Server state persisted across lazymc restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// Last known server status.
+    pub status: Option<ServerStatus>,
+
+    /// Last known server favicon, as base64 encoded PNG data URI.
+    pub favicon: Option<String>,
+
+    /// Unix time in seconds the server was last active at.
+    pub last_active: Option<u64>,
+
+    /// Number of times the server was started.
+    pub wake_count: u32,
+
+    /// Local date as `YYYY-MM-DD`, and number of times the server was started on that day.
+    pub wakes_today: Option<(String, u32)>,
+
+    /// Durations of recent server starts in milliseconds, most recent last.
+    pub start_durations: Vec<u64>,
+}
+
+/// Load persisted state from the server directory.
+///
+/// Returns `None` if disabled, or if there is no valid state file.
+pub fn load(config: &Config) -> Option<State> {
+    if !config.advanced.persist_state {
+        return None;
+    }
+
+    let path = config.server.dir().join(FILE);
+    let contents = fs::read(&path).ok()?;
+    match serde_json::from_slice(&contents) {
+        Ok(state) => {
+            debug!(target: "lazymc", "Loaded persisted state from {}", path.display());
+            Some(state)
+        }
+        Err(err) => {
+            warn!(target: "lazymc", "Ignoring invalid state file {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Save state to the server directory, if enabled.
+///
+/// Writes to a temporary file first, so a partially written state is never loaded.
+pub fn save(config: &Config, state: &State) {
+    if !config.advanced.persist_state {
+        return;
+    }
+
+    let path = config.server.dir().join(FILE);
+    let tmp = path.with_extension("json.tmp");
+    let result = serde_json::to_vec(state)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| fs::write(&tmp, contents).map_err(|err| err.to_string()))
+        .and_then(|_| fs::rename(&tmp, &path).map_err(|err| err.to_string()));
+    match result {
+        Ok(_) => trace!(target: "lazymc", "Saved state to {}", path.display()),
+        Err(err) => error!(target: "lazymc", "Failed to save state to {}: {}", path.display(), err),
+    }
+}
+
+/// Get current Unix time in seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
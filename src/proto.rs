@@ -1,10 +1,17 @@
-use std::sync::Mutex;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use bytes::BytesMut;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use tokio::io;
-use tokio::io::AsyncReadExt;
-use tokio::net::tcp::ReadHalf;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::codec::{Decoder, Encoder};
 
+use crate::session::Waiting;
 use crate::types;
 
 /// Default minecraft protocol version name.
@@ -18,6 +25,18 @@ pub const PROTO_DEFAULT_VERSION: &str = "1.16.5";
 /// with server status polling requests.
 pub const PROTO_DEFAULT_PROTOCOL: u32 = 754;
 
+/// Maximum uncompressed packet data length, as enforced by the Notchian server.
+const MAX_PACKET_DATA_LEN: i32 = 8 * 1024 * 1024;
+
+/// Maximum packet length, the length var-int may be at most 3 bytes.
+const MAX_PACKET_LEN: usize = (1 << 21) - 1;
+
+/// Minimum free space to read packets into, per connection.
+pub const READ_BUF_SIZE: usize = 512;
+
+/// First byte of a legacy server list ping, sent by clients before Minecraft 1.7.
+const LEGACY_PING_PACKET_ID: u8 = 0xFE;
+
 /// Handshake state, handshake packet ID.
 pub const HANDSHAKE_PACKET_ID_HANDSHAKE: i32 = 0;
 
@@ -30,15 +49,68 @@ pub const STATUS_PACKET_ID_PING: i32 = 1;
 /// Login state, login start packet ID.
 pub const LOGIN_PACKET_ID_LOGIN_START: i32 = 0;
 
+/// Login state, encryption response packet ID.
+pub const LOGIN_PACKET_ID_ENCRYPTION_RESPONSE: i32 = 1;
+
+/// Login state, login plugin response packet ID.
+pub const LOGIN_PACKET_ID_LOGIN_PLUGIN_RESPONSE: i32 = 2;
+
+/// Login state, disconnect packet ID (client-bound).
+pub const LOGIN_PACKET_ID_DISCONNECT: i32 = 0;
+
+/// Login state, encryption request packet ID (client-bound).
+pub const LOGIN_PACKET_ID_ENCRYPTION_REQUEST: i32 = 1;
+
+/// Login state, login success packet ID (client-bound).
+pub const LOGIN_PACKET_ID_LOGIN_SUCCESS: i32 = 2;
+
+/// Login state, set compression packet ID (client-bound).
+pub const LOGIN_PACKET_ID_SET_COMPRESSION: i32 = 3;
+
+/// Login state, login plugin request packet ID (client-bound).
+pub const LOGIN_PACKET_ID_LOGIN_PLUGIN_REQUEST: i32 = 4;
+
 /// Client state.
-// TODO: add encryption/compression state?
+// TODO: add encryption state?
 #[derive(Debug, Default)]
 pub struct Client {
+    /// Client peer address, if known.
+    pub peer: Option<SocketAddr>,
+
+    /// Address the client connected to, if known.
+    pub local: Option<SocketAddr>,
+
     /// Current client state.
     pub state: Mutex<ClientState>,
+
+    /// Client protocol version, known after handshake.
+    pub protocol: Mutex<Option<i32>>,
+
+    /// Hostname the client connected with, known after handshake.
+    pub hostname: Mutex<Option<String>>,
+
+    /// Client username, known after login start.
+    pub username: Mutex<Option<String>>,
+
+    /// Compression threshold, if compression is enabled.
+    pub compression: Mutex<Option<i32>>,
+
+    /// Maximum length of packets from the client before it is playing, if limited.
+    pub max_packet_length: Option<usize>,
+
+    /// Whether the client is waiting on us, exempts it from the idle timeout.
+    pub waiting: Arc<Waiting>,
 }
 
 impl Client {
+    /// Construct client for connection from given peer address.
+    pub fn new(peer: SocketAddr) -> Self {
+        Self {
+            peer: Some(peer),
+            ..Default::default()
+        }
+    }
+
     /// Get client state.
     pub fn state(&self) -> ClientState {
         *self.state.lock().unwrap()
@@ -48,6 +120,58 @@ impl Client {
     pub fn set_state(&self, state: ClientState) {
         *self.state.lock().unwrap() = state;
     }
+
+    /// Get client protocol version, if known.
+    pub fn protocol(&self) -> Option<i32> {
+        *self.protocol.lock().unwrap()
+    }
+
+    /// Set client protocol version.
+    pub fn set_protocol(&self, protocol: i32) {
+        *self.protocol.lock().unwrap() = Some(protocol);
+    }
+
+    /// Get hostname the client connected with, if known.
+    pub fn hostname(&self) -> Option<String> {
+        self.hostname.lock().unwrap().clone()
+    }
+
+    /// Set hostname the client connected with, as sent in the handshake.
+    ///
+    /// Strips data some mod loaders append, and a trailing dot.
+    pub fn set_hostname(&self, hostname: &str) {
+        let hostname = hostname.split('\0').next().unwrap_or_default();
+        let hostname = hostname.trim_end_matches('.').to_lowercase();
+        *self.hostname.lock().unwrap() = Some(hostname);
+    }
+
+    /// Get client username, if known.
+    pub fn username(&self) -> Option<String> {
+        self.username.lock().unwrap().clone()
+    }
+
+    /// Set client username.
+    pub fn set_username(&self, username: String) {
+        *self.username.lock().unwrap() = Some(username);
+    }
+
+    /// Get compression threshold, if compression is enabled.
+    pub fn compression(&self) -> Option<i32> {
+        *self.compression.lock().unwrap()
+    }
+
+    /// Set compression threshold, a negative threshold disables compression.
+    pub fn set_compression(&self, threshold: i32) {
+        *self.compression.lock().unwrap() = Some(threshold).filter(|t| *t >= 0);
+    }
+
+    /// Maximum length of packets to accept in the current state.
+    pub fn packet_length_limit(&self) -> usize {
+        match (self.state(), self.max_packet_length) {
+            (ClientState::Play, _) | (_, None) => MAX_PACKET_LEN,
+            (_, Some(max)) => max.min(MAX_PACKET_LEN),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -112,11 +236,37 @@ impl RawPacket {
     }
 
     /// Decode packet from raw buffer.
-    pub fn decode(mut buf: &[u8]) -> Result<Self, ()> {
+    ///
+    /// Decompresses the packet if compression is enabled for the client.
+    pub fn decode(client: &Client, buf: &[u8]) -> Result<Self, ()> {
+        let decompressed;
+        let mut buf = buf;
+
         // Read length
         let (read, len) = types::read_var_int(buf)?;
         buf = &buf[read..][..len as usize];
 
+        // Decompress if compressed, data length is zero for uncompressed packets
+        if client.compression().is_some() {
+            let (read, data_len) = types::read_var_int(buf)?;
+            buf = &buf[read..];
+
+            if data_len > 0 {
+                if data_len > MAX_PACKET_DATA_LEN {
+                    error!(target: "lazymc", "Compressed packet is too large: {} bytes", data_len);
+                    return Err(());
+                }
+
+                let mut data = Vec::with_capacity(data_len as usize);
+                ZlibDecoder::new(buf)
+                    .take(data_len as u64)
+                    .read_to_end(&mut data)
+                    .map_err(|_| ())?;
+                decompressed = data;
+                buf = &decompressed;
+            }
+        }
+
         // Read packet ID, select buf
         let (read, packet_id) = types::read_var_int(buf)?;
         buf = &buf[read..];
@@ -125,10 +275,27 @@ impl RawPacket {
     }
 
     /// Encode packet to raw buffer.
-    pub fn encode(&self) -> Result<Vec<u8>, ()> {
+    ///
+    /// Compresses the packet if compression is enabled for the client.
+    pub fn encode(&self, client: &Client) -> Result<Vec<u8>, ()> {
         let mut data = types::encode_var_int(self.id)?;
         data.extend_from_slice(&self.data);
 
+        // Compress if enabled, only packets reaching the threshold are actually compressed
+        if let Some(threshold) = client.compression() {
+            let data_len = data.len() as i32;
+            if data_len >= threshold {
+                let mut encoder =
+                    ZlibEncoder::new(types::encode_var_int(data_len)?, Compression::default());
+                encoder.write_all(&data).map_err(|_| ())?;
+                data = encoder.finish().map_err(|_| ())?;
+            } else {
+                let mut uncompressed = types::encode_var_int(0)?;
+                uncompressed.append(&mut data);
+                data = uncompressed;
+            }
+        }
+
         let len = data.len() as i32;
         let mut packet = types::encode_var_int(len)?;
         packet.append(&mut data);
@@ -137,66 +304,176 @@ impl RawPacket {
     }
 }
 
-/// Read raw packet from stream.
+/// Frame read by the Minecraft codec.
+pub enum Frame {
+    /// Packet, and its raw bytes as received.
+    Packet(RawPacket, Vec<u8>),
+
+    /// Legacy server list ping, from a client before Minecraft 1.7.
+    LegacyPing,
+}
+
+/// Codec framing Minecraft packets by their var-int length prefix.
 ///
-/// Note: this does not support reading compressed/encrypted packets.
-/// We should never need this though, as we're done reading user packets before any of this is
-/// enabled. See: https://wiki.vg/Protocol#Packet_format
-pub async fn read_packet<'a>(
-    buf: &mut BytesMut,
-    stream: &mut ReadHalf<'a>,
-) -> Result<Option<(RawPacket, Vec<u8>)>, ()> {
-    // Keep reading until we have at least 2 bytes
-    while buf.len() < 2 {
-        // Read packet from socket
-        let mut tmp = Vec::with_capacity(64);
-        match stream.read_buf(&mut tmp).await {
-            Ok(_) => {}
-            Err(err) if err.kind() == io::ErrorKind::ConnectionReset => return Ok(None),
-            Err(err) => {
-                dbg!(err);
-                return Err(());
+/// Decompresses and compresses packets if compression is enabled for the client. See:
+/// https://wiki.vg/Protocol#Packet_format
+pub struct MinecraftCodec<'a> {
+    client: &'a Client,
+
+    /// Whether to detect legacy server list pings.
+    legacy_ping: bool,
+}
+
+impl<'a> MinecraftCodec<'a> {
+    /// Construct codec for the given client.
+    pub fn new(client: &'a Client) -> Self {
+        Self {
+            client,
+            legacy_ping: false,
+        }
+    }
+
+    /// Detect legacy server list pings in the handshake state.
+    ///
+    /// Only for connections from clients, as legacy pings aren't length prefixed and a regular
+    /// packet may start with the same byte.
+    pub fn with_legacy_ping(mut self) -> Self {
+        self.legacy_ping = true;
+        self
+    }
+}
+
+impl Decoder for MinecraftCodec<'_> {
+    type Item = Frame;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Frame>, io::Error> {
+        // Detect legacy ping by its first byte, the client waits for a response without sending more
+        if self.legacy_ping
+            && self.client.state() == ClientState::Handshake
+            && buf.first() == Some(&LEGACY_PING_PACKET_ID)
+        {
+            buf.clear();
+            return Ok(Some(Frame::LegacyPing));
+        }
+
+        // Wait for at least 2 bytes
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+
+        // Attempt to read packet length, wait for more bytes if the var-int is incomplete
+        let (consumed, len) = match types::read_var_int(buf) {
+            Ok((consumed, len)) if len >= 0 => (consumed, len as usize),
+            Err(_) if buf.len() < 5 && buf.iter().all(|b| b & 0x80 != 0) => return Ok(None),
+            _ => {
+                error!(target: "lazymc", "Malformed packet, could not read packet length");
+                return Err(malformed());
             }
+        };
+
+        // Refuse packets exceeding the limit, don't buffer them
+        let limit = self.client.packet_length_limit();
+        if len > limit {
+            debug!(
+                target: "lazymc",
+                "Closing connection, packet of {} bytes exceeds limit of {} bytes in {:?} state",
+                len,
+                limit,
+                self.client.state(),
+            );
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "packet too large",
+            ));
         }
 
-        if tmp.is_empty() {
+        // Wait for all packet bytes
+        if buf.len() < consumed + len {
             return Ok(None);
         }
-        buf.extend(tmp);
+
+        // Parse packet
+        let raw = buf.split_to(consumed + len);
+        let packet = RawPacket::decode(self.client, &raw).map_err(|_| malformed())?;
+
+        Ok(Some(Frame::Packet(packet, raw.to_vec())))
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Frame>, io::Error> {
+        // Ignore incomplete packet when the stream is closed
+        self.decode(buf)
     }
+}
+
+impl Encoder<RawPacket> for MinecraftCodec<'_> {
+    type Error = io::Error;
 
-    // Attempt to read packet length
-    let (consumed, len) = match types::read_var_int(&buf) {
+    fn encode(&mut self, packet: RawPacket, buf: &mut BytesMut) -> Result<(), io::Error> {
+        let raw = packet.encode(self.client).map_err(|_| malformed())?;
+        buf.extend_from_slice(&raw);
+        Ok(())
+    }
+}
+
+/// Error for malformed packets.
+fn malformed() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed packet")
+}
+
+/// Read raw packet from stream, giving up after the given timeout.
+///
+/// Returns `Ok(None)` on timeout, as if the stream was closed.
+pub async fn read_packet_timeout<R: AsyncRead + Unpin>(
+    client: &Client,
+    buf: &mut BytesMut,
+    stream: &mut R,
+    timeout: Duration,
+) -> Result<Option<(RawPacket, Vec<u8>)>, ()> {
+    match tokio::time::timeout(timeout, read_packet(client, buf, stream)).await {
         Ok(result) => result,
-        Err(err) => {
-            error!(target: "lazymc", "Malformed packet, could not read packet length");
-            return Err(err);
+        Err(_) => {
+            debug!(target: "lazymc", "Closing connection, client timed out in {:?} state", client.state());
+            Ok(None)
         }
-    };
-
-    // Keep reading until we have all packet bytes
-    while buf.len() < consumed + len as usize {
-        // Read packet from socket
-        let mut tmp = Vec::with_capacity(64);
-        match stream.read_buf(&mut tmp).await {
-            Ok(_) => {}
-            Err(err) if err.kind() == io::ErrorKind::ConnectionReset => return Ok(None),
-            Err(err) => {
-                dbg!(err);
-                return Err(());
-            }
+    }
+}
+
+/// Read raw packet from stream.
+///
+/// Decompresses packets if compression is enabled for the client. Encrypted packets can be read
+/// through a decrypting reader. Frames packets with `MinecraftCodec`.
+pub async fn read_packet<R: AsyncRead + Unpin>(
+    client: &Client,
+    buf: &mut BytesMut,
+    stream: &mut R,
+) -> Result<Option<(RawPacket, Vec<u8>)>, ()> {
+    let mut codec = MinecraftCodec::new(client);
+    loop {
+        match codec.decode(buf) {
+            Ok(Some(Frame::Packet(packet, raw))) => return Ok(Some((packet, raw))),
+            Ok(Some(Frame::LegacyPing)) => return Ok(None),
+            Ok(None) => {}
+            Err(_) => return Err(()),
         }
 
-        if tmp.is_empty() {
+        if !read_more(buf, stream).await? {
             return Ok(None);
         }
-
-        buf.extend(tmp);
     }
+}
 
-    // Parse packet
-    let raw = buf.split_to(consumed + len as usize);
-    let packet = RawPacket::decode(&raw)?;
-
-    Ok(Some((packet, raw.to_vec())))
+/// Read more bytes from the stream directly into the connection read buffer.
+///
+/// Reuses buffer capacity left by earlier packets. Returns `Ok(false)` if the stream was closed.
+async fn read_more<R: AsyncRead + Unpin>(buf: &mut BytesMut, stream: &mut R) -> Result<bool, ()> {
+    buf.reserve(READ_BUF_SIZE);
+    match stream.read_buf(buf).await {
+        Ok(read) => Ok(read > 0),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionReset => Ok(false),
+        Err(err) => {
+            debug!(target: "lazymc", "Failed to read from stream: {}", err);
+            Err(())
+        }
+    }
 }
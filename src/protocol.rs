@@ -0,0 +1,218 @@
+use minecraft_protocol::data::chat::Message;
+use minecraft_protocol::data::server_status::ServerStatus;
+use minecraft_protocol::encoder::Encoder;
+use minecraft_protocol::version::v1_14_4::login::LoginDisconnect;
+
+use crate::mc::uuid;
+use crate::proto::{self, Client, RawPacket, PROTO_DEFAULT_PROTOCOL};
+use crate::types;
+
+/// Minecraft 1.12.2, oldest version chat messages are sniffed for.
+pub const PROTO_1_12_2: i32 = 340;
+
+/// Minecraft 1.14, chat message packet ID changes.
+pub const PROTO_1_14: i32 = 477;
+
+/// Minecraft 1.16, login success sends UUID as binary instead of string.
+pub const PROTO_1_16: i32 = 735;
+
+/// Minecraft 1.19, login success has properties, encryption response may hold a signature.
+pub const PROTO_1_19: i32 = 759;
+
+/// Minecraft 1.19.1, chat message packet ID changes.
+pub const PROTO_1_19_1: i32 = 760;
+
+/// Minecraft 1.19.3, encryption response drops signature.
+pub const PROTO_1_19_3: i32 = 761;
+
+/// Minecraft 1.20.5, login success has strict error handling flag, encryption request has
+/// authenticate flag.
+pub const PROTO_1_20_5: i32 = 766;
+
+/// Minecraft 1.21.2, login success drops strict error handling flag.
+pub const PROTO_1_21_2: i32 = 768;
+
+/// Minecraft 1.21.4, newest version chat messages are sniffed for.
+pub const PROTO_1_21_4: i32 = 769;
+
+/// Protocol spoken by a client.
+///
+/// Selects packet IDs and encodings for the packets lazymc sends and parses itself, based on the
+/// protocol version from the client handshake.
+#[derive(Debug, Copy, Clone)]
+pub struct Protocol {
+    /// Protocol version.
+    version: i32,
+}
+
+impl Protocol {
+    /// Construct protocol for given protocol version.
+    pub fn new(version: i32) -> Self {
+        Self { version }
+    }
+
+    /// Protocol of the given client.
+    ///
+    /// Falls back to the default protocol if the client protocol is not known yet.
+    pub fn of(client: &Client) -> Self {
+        Self::new(client.protocol().unwrap_or(PROTO_DEFAULT_PROTOCOL as i32))
+    }
+
+    /// Decode login start packet data.
+    ///
+    /// Only the username is read, which leads the packet in all protocol versions.
+    pub fn decode_login_start(&self, buf: &[u8]) -> Result<LoginStart, ()> {
+        let (_, name) = types::read_string(buf)?;
+        Ok(LoginStart { name })
+    }
+
+    /// Encode login disconnect packet.
+    pub fn encode_login_disconnect(&self, reason: Message) -> Result<RawPacket, ()> {
+        let mut data = Vec::new();
+        LoginDisconnect { reason }
+            .encode(&mut data)
+            .map_err(|_| ())?;
+        Ok(RawPacket::new(proto::LOGIN_PACKET_ID_DISCONNECT, data))
+    }
+
+    /// Encode login success packet.
+    pub fn encode_login_success(&self, uuid: [u8; 16], username: &str) -> Result<RawPacket, ()> {
+        let mut data = if self.version >= PROTO_1_16 {
+            uuid.to_vec()
+        } else {
+            types::encode_string(&uuid::to_hyphenated(uuid))?
+        };
+        data.extend(types::encode_string(username)?);
+
+        // Empty properties list
+        if self.version >= PROTO_1_19 {
+            data.extend(types::encode_var_int(0)?);
+        }
+
+        // Strict error handling
+        if (PROTO_1_20_5..PROTO_1_21_2).contains(&self.version) {
+            data.push(false as u8);
+        }
+
+        Ok(RawPacket::new(proto::LOGIN_PACKET_ID_LOGIN_SUCCESS, data))
+    }
+
+    /// Encode encryption request packet.
+    pub fn encode_encryption_request(
+        &self,
+        public_key: &[u8],
+        verify_token: &[u8],
+    ) -> Result<RawPacket, ()> {
+        let mut data = types::encode_string("")?; // Server ID
+        data.extend(types::encode_var_int(public_key.len() as i32)?);
+        data.extend_from_slice(public_key);
+        data.extend(types::encode_var_int(verify_token.len() as i32)?);
+        data.extend_from_slice(verify_token);
+
+        // Should authenticate
+        if self.version >= PROTO_1_20_5 {
+            data.push(true as u8);
+        }
+
+        Ok(RawPacket::new(
+            proto::LOGIN_PACKET_ID_ENCRYPTION_REQUEST,
+            data,
+        ))
+    }
+
+    /// Decode encryption response packet data.
+    pub fn decode_encryption_response(&self, mut buf: &[u8]) -> Result<EncryptionResponse, ()> {
+        let shared_secret = read_byte_array(&mut buf)?;
+
+        // Between 1.19 and 1.19.3 clients may send a signature instead of the verify token
+        let verify_token =
+            if (PROTO_1_19..PROTO_1_19_3).contains(&self.version) && *buf.first().ok_or(())? == 0 {
+                None
+            } else {
+                if (PROTO_1_19..PROTO_1_19_3).contains(&self.version) {
+                    buf = &buf[1..];
+                }
+                Some(read_byte_array(&mut buf)?)
+            };
+
+        Ok(EncryptionResponse {
+            shared_secret,
+            verify_token,
+        })
+    }
+
+    /// Serverbound chat message packet ID in play state, if known for this protocol.
+    pub fn chat_packet_id(&self) -> Option<i32> {
+        match self.version {
+            v if v < PROTO_1_12_2 => None,
+            v if v < PROTO_1_14 => Some(0x02),
+            v if v < PROTO_1_19 => Some(0x03),
+            v if v < PROTO_1_19_1 => Some(0x04),
+            v if v < PROTO_1_20_5 => Some(0x05),
+            v if v < PROTO_1_21_2 => Some(0x06),
+            v if v <= PROTO_1_21_4 => Some(0x07),
+            _ => None,
+        }
+    }
+
+    /// Decode chat message packet data.
+    ///
+    /// Only the message is read, which leads the packet in all protocol versions.
+    pub fn decode_chat(&self, buf: &[u8]) -> Result<String, ()> {
+        let (_, message) = types::read_string(buf)?;
+        Ok(message)
+    }
+
+    /// Encode status response packet.
+    ///
+    /// The favicon must be a base64 encoded PNG data URI, and is included if given.
+    pub fn encode_status_response(
+        &self,
+        server_status: ServerStatus,
+        favicon: Option<&str>,
+    ) -> Result<RawPacket, ()> {
+        let mut status = serde_json::to_value(server_status).map_err(|_| ())?;
+        if let (Some(status), Some(favicon)) = (status.as_object_mut(), favicon) {
+            status.insert("favicon".into(), favicon.into());
+        }
+
+        let json = serde_json::to_string(&status).map_err(|_| ())?;
+        self.encode_status_json(&json)
+    }
+
+    /// Encode status response packet from raw JSON status.
+    pub fn encode_status_json(&self, json: &str) -> Result<RawPacket, ()> {
+        Ok(RawPacket::new(
+            proto::STATUS_PACKET_ID_STATUS,
+            types::encode_string(json)?,
+        ))
+    }
+}
+
+/// Login start packet.
+#[derive(Debug)]
+pub struct LoginStart {
+    /// Player username.
+    pub name: String,
+}
+
+/// Encryption response packet, with encrypted fields.
+#[derive(Debug)]
+pub struct EncryptionResponse {
+    /// Shared secret, encrypted with server public key.
+    pub shared_secret: Vec<u8>,
+
+    /// Verify token, encrypted with server public key. Not set if client sent a signature instead.
+    pub verify_token: Option<Vec<u8>>,
+}
+
+/// Read var-int length prefixed byte array from start of buffer, advance buffer.
+fn read_byte_array(buf: &mut &[u8]) -> Result<Vec<u8>, ()> {
+    let (read, len) = types::read_var_int(buf)?;
+    let data = buf
+        .get(read..read + len.max(0) as usize)
+        .ok_or(())?
+        .to_vec();
+    *buf = &buf[read + data.len()..];
+    Ok(data)
+}
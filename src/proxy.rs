@@ -1,19 +1,456 @@
 use std::error::Error;
+use std::future::Future;
+#[cfg(target_os = "linux")]
+use std::net::Shutdown;
 use std::net::SocketAddr;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
 
+use futures::ready;
 use tokio::io;
-use tokio::io::AsyncWriteExt;
+#[cfg(target_os = "linux")]
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::net::TcpStream;
+use tokio::time::{self, Instant, Sleep};
 
-/// Proxy the inbound stream to a target address.
-pub async fn proxy(mut inbound: TcpStream, addr_target: SocketAddr) -> Result<(), Box<dyn Error>> {
+use crate::config::{Config, Limits};
+#[cfg(target_os = "linux")]
+use crate::os::linux::Pipe;
+use crate::proto::Client;
+use crate::proxy_protocol;
+use crate::util::net;
+
+/// Maximum number of bytes to move through a pipe at once, the default pipe capacity on Linux.
+#[cfg(target_os = "linux")]
+const SPLICE_SIZE: usize = 64 * 1024;
+
+/// Minimum number of bytes to wait for before moving data through a throttle, to not move data in
+/// tiny chunks.
+const THROTTLE_CHUNK: usize = 1024;
+
+/// Number of attempts to connect to the server on behalf of a client.
+const CONNECT_ATTEMPTS: u32 = 5;
+
+/// Time to wait before retrying to connect to the server, doubled after each attempt.
+const CONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Reader counting the bytes read through it.
+pub struct Metered<'a, R> {
+    inner: R,
+    bytes: &'a AtomicU64,
+}
+
+impl<'a, R> Metered<'a, R> {
+    /// Wrap reader, adding the number of bytes read to the given counter.
+    pub fn new(inner: R, bytes: &'a AtomicU64) -> Self {
+        Self { inner, bytes }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Metered<'_, R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = buf.filled().len() - filled;
+        self.bytes.fetch_add(read as u64, Ordering::Relaxed);
+        result
+    }
+}
+
+/// Token bucket limiting the rate of bytes moved, allowing bursts of up to one second worth.
+#[derive(Debug)]
+pub struct Throttle {
+    /// Maximum rate in bytes per second.
+    rate: u32,
+
+    /// Number of bytes that may be moved right now.
+    tokens: f64,
+
+    /// Time tokens were last added.
+    updated: Instant,
+}
+
+impl Throttle {
+    /// Construct throttle for the given rate in bytes per second, starting with a full bucket.
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            tokens: rate as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Number of bytes that may be moved right now, up to `max`.
+    ///
+    /// Returns 0 until a reasonable chunk may be moved.
+    fn available(&mut self, max: usize) -> usize {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+        self.updated = now;
+
+        let tokens = self.tokens as usize;
+        if tokens < self.chunk(max) {
+            0
+        } else {
+            tokens.min(max)
+        }
+    }
+
+    /// Time until a reasonable chunk, up to `max` bytes, may be moved.
+    fn delay(&self, max: usize) -> Duration {
+        let missing = (self.chunk(max) as f64 - self.tokens).max(0.0);
+        Duration::from_secs_f64(missing / self.rate as f64)
+    }
+
+    /// Minimum number of bytes to move at once, up to `max`.
+    fn chunk(&self, max: usize) -> usize {
+        THROTTLE_CHUNK.min(self.rate as usize).min(max).max(1)
+    }
+
+    /// Wait until data may be moved, returning the number of bytes that may be moved up to `max`.
+    #[cfg(target_os = "linux")]
+    async fn reserve(&mut self, max: usize) -> usize {
+        loop {
+            match self.available(max) {
+                0 => time::sleep(self.delay(max)).await,
+                available => return available,
+            }
+        }
+    }
+
+    /// Take the given number of moved bytes from the bucket.
+    fn consume(&mut self, bytes: usize) {
+        self.tokens -= bytes as f64;
+    }
+}
+
+/// Reader limiting the rate of bytes read through it, if a throttle is given.
+pub struct Throttled<R> {
+    inner: R,
+    throttle: Option<Throttle>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<R> Throttled<R> {
+    /// Wrap reader, limiting reads to the given rate in bytes per second if set.
+    pub fn new(inner: R, rate: Option<u32>) -> Self {
+        Self {
+            inner,
+            throttle: rate.map(Throttle::new),
+            sleep: None,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Throttled<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let throttle = match &mut this.throttle {
+            Some(throttle) if buf.remaining() > 0 => throttle,
+            _ => return Pin::new(&mut this.inner).poll_read(cx, buf),
+        };
+
+        // Wait until we may read a chunk
+        let available = loop {
+            match throttle.available(buf.remaining()) {
+                0 => {
+                    let deadline = Instant::now() + throttle.delay(buf.remaining());
+                    let sleep = this
+                        .sleep
+                        .get_or_insert_with(|| Box::pin(time::sleep_until(deadline)));
+                    sleep.as_mut().reset(deadline);
+                    ready!(sleep.as_mut().poll(cx));
+                }
+                available => break available,
+            }
+        };
+
+        let mut limited = buf.take(available);
+        ready!(Pin::new(&mut this.inner).poll_read(cx, &mut limited))?;
+        let read = limited.filled().len();
+
+        // The inner reader initialized and filled these bytes of our buffer
+        unsafe {
+            buf.assume_init(read);
+        }
+        buf.advance(read);
+        throttle.consume(read);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Connect to the server on behalf of the client.
+///
+/// Sends a PROXY protocol v2 header with the client address first, if enabled. Sends a header for
+/// a local connection instead if the client address is unknown.
+///
+/// Retries a few times with backoff, as the server may not accept connections yet right after it
+/// was detected to be online.
+pub async fn connect_to_server(config: &Config, client: &Client) -> io::Result<TcpStream> {
+    connect_to(config, client, config.server.address).await
+}
+
+/// Connect to the given address on behalf of the client, such as a fallback server.
+///
+/// Connects like [`connect_to_server`], with the same socket options and PROXY protocol header.
+pub async fn connect_to(
+    config: &Config,
+    client: &Client,
+    addr: SocketAddr,
+) -> io::Result<TcpStream> {
+    let header = config
+        .server
+        .send_proxy_v2
+        .then(|| match (client.peer, client.local) {
+            (Some(peer), Some(local)) => proxy_protocol::encode_v2(peer, local),
+            _ => proxy_protocol::encode_v2_local(),
+        });
+
+    let mut backoff = CONNECT_BACKOFF;
+    for _ in 1..CONNECT_ATTEMPTS {
+        match connect_with_header(config, addr, header.clone()).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => {
+                debug!(target: "lazymc", "Failed to connect to server, retrying in {}ms: {}", backoff.as_millis(), err);
+                time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+    }
+    connect_with_header(config, addr, header).await
+}
+
+/// Connect to the server for lazymc itself, such as to poll its status.
+///
+/// Sends a PROXY protocol v2 header for a local connection first, if enabled.
+pub async fn connect_to_server_local(config: &Config) -> io::Result<TcpStream> {
+    let header = config
+        .server
+        .send_proxy_v2
+        .then(proxy_protocol::encode_v2_local);
+    connect_with_header(config, config.server.address, header).await
+}
+
+/// Connect to the given address, sending the header first if any.
+async fn connect_with_header(
+    config: &Config,
+    addr: SocketAddr,
+    header: Option<Vec<u8>>,
+) -> io::Result<TcpStream> {
+    let server = &config.server;
+    let mut stream = net::connect(
+        &config.socket,
+        addr,
+        server.bind_address,
+        server.bind_interface.as_deref(),
+    )
+    .await?;
+    if let Some(header) = header {
+        stream.write_all(&header).await?;
+    }
+    Ok(stream)
+}
+
+/// Proxy the inbound stream to the outbound stream, counting the bytes the client sends.
+///
+/// Send the queue to the outbound stream before proxying. Limits the client to the configured
+/// upload and download rates.
+pub async fn proxy_metered(
+    inbound: TcpStream,
+    mut outbound: TcpStream,
+    queue: &[u8],
+    traffic: &AtomicU64,
+    limits: &Limits,
+) -> Result<(), Box<dyn Error>> {
+    if !queue.is_empty() {
+        outbound.write_all(queue).await?;
+    }
+
+    proxy_streams(inbound, outbound, Some(traffic), Some(limits)).await
+}
+
+/// Proxy the inbound stream to a target address, on behalf of the client.
+///
+/// Send the queue to the target server before proxying.
+pub async fn proxy_with_queue(
+    config: &Config,
+    client: &Client,
+    inbound: TcpStream,
+    addr_target: SocketAddr,
+    queue: &[u8],
+) -> Result<(), Box<dyn Error>> {
     // Set up connection to server
     // TODO: on connect fail, ping server and redirect to serve_status if offline
-    let mut outbound = TcpStream::connect(addr_target).await?;
+    let outbound = connect_to(config, client, addr_target).await?;
 
-    let (mut ri, mut wi) = inbound.split();
-    let (mut ro, mut wo) = outbound.split();
+    proxy_inbound_outbound_with_queue(inbound, outbound, queue, &[]).await
+}
+
+/// Proxy the inbound stream to a target stream.
+///
+/// Send the inbound queue to the target server, and the outbound queue to the client, before
+/// proxying.
+pub async fn proxy_inbound_outbound_with_queue(
+    mut inbound: TcpStream,
+    mut outbound: TcpStream,
+    inbound_queue: &[u8],
+    outbound_queue: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    // Start with queues
+    if !inbound_queue.is_empty() {
+        outbound.write_all(inbound_queue).await?;
+    }
+    if !outbound_queue.is_empty() {
+        inbound.write_all(outbound_queue).await?;
+    }
+
+    proxy_streams(inbound, outbound, None, None).await
+}
+
+/// Proxy between inbound and outbound stream, until both directions are closed.
+///
+/// Counts the bytes the client sends if a counter is given, and limits the client to the upload
+/// and download rates if limits are given. On Linux, data is moved within the kernel with
+/// `splice(2)` instead of copying it through userspace.
+async fn proxy_streams(
+    mut inbound: TcpStream,
+    mut outbound: TcpStream,
+    traffic: Option<&AtomicU64>,
+    limits: Option<&Limits>,
+) -> Result<(), Box<dyn Error>> {
+    let upload = limits.and_then(Limits::max_upload_rate);
+    let download = limits.and_then(Limits::max_download_rate);
+
+    #[cfg(target_os = "linux")]
+    match Pipe::new().and_then(|pipe| Ok((pipe, Pipe::new()?))) {
+        Ok(pipes) => {
+            return proxy_splice(inbound, outbound, pipes, traffic, (upload, download)).await
+        }
+        Err(err) => debug!(target: "lazymc", "Failed to create pipes to splice, copying: {}", err),
+    }
+
+    let (ri, wi) = inbound.split();
+    let (ro, wo) = outbound.split();
+    let ro = Throttled::new(ro, download);
+
+    match traffic {
+        Some(traffic) => {
+            let ri = Throttled::new(Metered::new(ri, traffic), upload);
+            proxy_halves(ri, wi, ro, wo).await
+        }
+        None => proxy_halves(Throttled::new(ri, upload), wi, ro, wo).await,
+    }
+}
+
+/// Proxy between inbound and outbound stream within the kernel through the given pipes.
+///
+/// Limits the client to the given upload and download rates in bytes per second, if set.
+#[cfg(target_os = "linux")]
+async fn proxy_splice(
+    inbound: TcpStream,
+    outbound: TcpStream,
+    (inbound_pipe, outbound_pipe): (Pipe, Pipe),
+    traffic: Option<&AtomicU64>,
+    (upload, download): (Option<u32>, Option<u32>),
+) -> Result<(), Box<dyn Error>> {
+    let inbound = AsyncFd::new(inbound.into_std()?)?;
+    let outbound = AsyncFd::new(outbound.into_std()?)?;
+
+    tokio::try_join!(
+        splice_copy(
+            &inbound,
+            &outbound,
+            &inbound_pipe,
+            traffic,
+            upload.map(Throttle::new)
+        ),
+        splice_copy(
+            &outbound,
+            &inbound,
+            &outbound_pipe,
+            None,
+            download.map(Throttle::new)
+        ),
+    )?;
+
+    Ok(())
+}
+
+/// Move data from one socket to another through a pipe, until the source is closed.
+///
+/// Shuts down writing to the target socket once done.
+#[cfg(target_os = "linux")]
+async fn splice_copy(
+    from: &AsyncFd<std::net::TcpStream>,
+    to: &AsyncFd<std::net::TcpStream>,
+    pipe: &Pipe,
+    traffic: Option<&AtomicU64>,
+    mut throttle: Option<Throttle>,
+) -> io::Result<()> {
+    loop {
+        let len = match &mut throttle {
+            Some(throttle) => throttle.reserve(SPLICE_SIZE).await,
+            None => SPLICE_SIZE,
+        };
+
+        // Fill pipe from source, the pipe is always drained so this only blocks on the source
+        let read = loop {
+            let mut guard = from.readable().await?;
+            let result = guard.try_io(|from| pipe.splice_from(from.as_raw_fd(), len));
+            if let Ok(result) = result {
+                break result?;
+            }
+        };
+        if read == 0 {
+            break;
+        }
+        if let Some(traffic) = traffic {
+            traffic.fetch_add(read as u64, Ordering::Relaxed);
+        }
+        if let Some(throttle) = &mut throttle {
+            throttle.consume(read);
+        }
+
+        // Drain pipe into target
+        let mut pending = read;
+        while pending > 0 {
+            let mut guard = to.writable().await?;
+            if let Ok(result) = guard.try_io(|to| pipe.splice_to(to.as_raw_fd(), pending)) {
+                pending -= result?;
+            }
+        }
+    }
+
+    to.get_ref().shutdown(Shutdown::Write)
+}
 
+/// Proxy between inbound and outbound stream halves, until both directions are closed.
+pub async fn proxy_halves<RI, WI, RO, WO>(
+    mut ri: RI,
+    mut wi: WI,
+    mut ro: RO,
+    mut wo: WO,
+) -> Result<(), Box<dyn Error>>
+where
+    RI: AsyncRead + Unpin,
+    WI: AsyncWrite + Unpin,
+    RO: AsyncRead + Unpin,
+    WO: AsyncWrite + Unpin,
+{
     let client_to_server = async {
         io::copy(&mut ri, &mut wo).await?;
         wo.shutdown().await
@@ -0,0 +1,173 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// PROXY protocol v2 header signature.
+const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// PROXY protocol v1 header prefix.
+const V1_PREFIX: &[u8] = b"PROXY ";
+
+/// Maximum length of a PROXY protocol v1 header, including the line ending.
+const V1_MAX_LEN: usize = 107;
+
+/// Protocol version 2, with the local command.
+const VERSION_LOCAL: u8 = 0x20;
+
+/// Protocol version 2, with the proxy command.
+const VERSION_PROXY: u8 = 0x21;
+
+/// Unspecified address family and transport protocol.
+const FAMILY_UNSPEC: u8 = 0x00;
+
+/// TCP over IPv4.
+const FAMILY_TCP4: u8 = 0x11;
+
+/// TCP over IPv6.
+const FAMILY_TCP6: u8 = 0x21;
+
+/// UDP over IPv4.
+const FAMILY_UDP4: u8 = 0x12;
+
+/// UDP over IPv6.
+const FAMILY_UDP6: u8 = 0x22;
+
+/// Read a PROXY protocol v1 or v2 header from the stream.
+///
+/// Returns the source and destination address of the proxied connection, or `None` if the header
+/// has no addresses, such as for health checks by the proxy itself. Reads exactly the header,
+/// leaving the rest of the stream untouched.
+pub async fn read_header<R>(reader: &mut R) -> Result<Option<(SocketAddr, SocketAddr)>, ()>
+where
+    R: AsyncRead + Unpin,
+{
+    // Both versions are distinguishable by their first 12 bytes, and headers are never shorter
+    let mut header = vec![0; SIGNATURE.len()];
+    reader.read_exact(&mut header).await.map_err(|_| ())?;
+    if header == SIGNATURE {
+        read_v2(reader).await
+    } else if header.starts_with(V1_PREFIX) {
+        read_v1(reader, header).await
+    } else {
+        Err(())
+    }
+}
+
+/// Read the rest of a PROXY protocol v1 header, after the given start of it.
+async fn read_v1<R>(
+    reader: &mut R,
+    mut header: Vec<u8>,
+) -> Result<Option<(SocketAddr, SocketAddr)>, ()>
+where
+    R: AsyncRead + Unpin,
+{
+    // Read byte by byte up to the line ending, to not read beyond the header
+    while !header.ends_with(b"\r\n") {
+        if header.len() >= V1_MAX_LEN {
+            return Err(());
+        }
+        header.push(reader.read_u8().await.map_err(|_| ())?);
+    }
+
+    let header = std::str::from_utf8(&header[..header.len() - 2]).map_err(|_| ())?;
+    let fields: Vec<&str> = header.split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "TCP4" | "TCP6", source, destination, source_port, destination_port] => {
+            let source: IpAddr = source.parse().map_err(|_| ())?;
+            let destination: IpAddr = destination.parse().map_err(|_| ())?;
+            Ok(Some((
+                SocketAddr::new(source, source_port.parse().map_err(|_| ())?),
+                SocketAddr::new(destination, destination_port.parse().map_err(|_| ())?),
+            )))
+        }
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        _ => Err(()),
+    }
+}
+
+/// Read the rest of a PROXY protocol v2 header, after its signature.
+async fn read_v2<R>(reader: &mut R) -> Result<Option<(SocketAddr, SocketAddr)>, ()>
+where
+    R: AsyncRead + Unpin,
+{
+    let version = reader.read_u8().await.map_err(|_| ())?;
+    let family = reader.read_u8().await.map_err(|_| ())?;
+    let len = reader.read_u16().await.map_err(|_| ())?;
+    let mut addresses = vec![0; len as usize];
+    reader.read_exact(&mut addresses).await.map_err(|_| ())?;
+
+    match version {
+        VERSION_LOCAL => return Ok(None),
+        VERSION_PROXY => {}
+        _ => return Err(()),
+    }
+
+    // Parse addresses, followed by ports, ignoring any trailing TLVs
+    let port = |at: usize| u16::from_be_bytes([addresses[at], addresses[at + 1]]);
+    match family {
+        FAMILY_TCP4 | FAMILY_UDP4 if addresses.len() >= 12 => {
+            let ip = |at: usize| -> [u8; 4] { addresses[at..at + 4].try_into().unwrap() };
+            Ok(Some((
+                SocketAddr::new(Ipv4Addr::from(ip(0)).into(), port(8)),
+                SocketAddr::new(Ipv4Addr::from(ip(4)).into(), port(10)),
+            )))
+        }
+        FAMILY_TCP6 | FAMILY_UDP6 if addresses.len() >= 36 => {
+            let ip = |at: usize| -> [u8; 16] { addresses[at..at + 16].try_into().unwrap() };
+            Ok(Some((
+                SocketAddr::new(Ipv6Addr::from(ip(0)).into(), port(32)),
+                SocketAddr::new(Ipv6Addr::from(ip(16)).into(), port(34)),
+            )))
+        }
+        FAMILY_TCP4 | FAMILY_UDP4 | FAMILY_TCP6 | FAMILY_UDP6 => Err(()),
+        _ => Ok(None),
+    }
+}
+
+/// Encode PROXY protocol v2 header for a connection proxied from `source` to `destination`.
+///
+/// IPv4 addresses are mapped to IPv6 if the other address is IPv6.
+pub fn encode_v2(source: SocketAddr, destination: SocketAddr) -> Vec<u8> {
+    let mut addresses = Vec::with_capacity(36);
+    let family = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            addresses.extend_from_slice(&src.octets());
+            addresses.extend_from_slice(&dst.octets());
+            FAMILY_TCP4
+        }
+        (src, dst) => {
+            addresses.extend_from_slice(&to_ipv6(src).octets());
+            addresses.extend_from_slice(&to_ipv6(dst).octets());
+            FAMILY_TCP6
+        }
+    };
+    addresses.extend_from_slice(&source.port().to_be_bytes());
+    addresses.extend_from_slice(&destination.port().to_be_bytes());
+
+    header(VERSION_PROXY, family, &addresses)
+}
+
+/// Encode PROXY protocol v2 header for a connection made by the proxy itself, such as a health
+/// check.
+pub fn encode_v2_local() -> Vec<u8> {
+    header(VERSION_LOCAL, FAMILY_UNSPEC, &[])
+}
+
+/// Encode header with the given command, family and address block.
+fn header(version: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(16 + addresses.len());
+    header.extend_from_slice(&SIGNATURE);
+    header.push(version);
+    header.push(family);
+    header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    header.extend_from_slice(addresses);
+    header
+}
+
+/// Get address as IPv6, mapping IPv4 addresses.
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
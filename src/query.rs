@@ -0,0 +1,253 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use minecraft_protocol::data::chat::Payload;
+use rand::Rng;
+use tokio::net::UdpSocket;
+
+use crate::config::Config;
+use crate::messages;
+use crate::server::ServerState;
+
+/// Query packet magic, leading every request.
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+
+/// Query handshake packet type.
+const TYPE_HANDSHAKE: u8 = 9;
+
+/// Query stat packet type.
+const TYPE_STAT: u8 = 0;
+
+/// Padding before key/value section in full stat response.
+const FULL_STAT_PADDING_KV: &[u8] = b"splitnum\x00\x80\x00";
+
+/// Padding before player section in full stat response.
+const FULL_STAT_PADDING_PLAYERS: &[u8] = b"\x01player_\x00\x00";
+
+/// Time after which a challenge token is rotated, the previous token remains valid.
+const CHALLENGE_ROTATE: Duration = Duration::from_secs(30);
+
+/// Maximum query request size.
+const MAX_REQUEST_LEN: usize = 64;
+
+/// Challenge tokens handed out to query clients.
+struct Challenge {
+    /// Current token.
+    current: i32,
+
+    /// Previous token.
+    previous: i32,
+
+    /// Time the current token was generated at.
+    rotated: Instant,
+}
+
+impl Challenge {
+    /// Generate new challenge.
+    fn new() -> Self {
+        let mut rng = rand::thread_rng();
+        Self {
+            current: rng.gen(),
+            previous: rng.gen(),
+            rotated: Instant::now(),
+        }
+    }
+
+    /// Get current token, rotate if expired.
+    fn token(&mut self) -> i32 {
+        if self.rotated.elapsed() >= CHALLENGE_ROTATE {
+            self.previous = self.current;
+            self.current = rand::thread_rng().gen();
+            self.rotated = Instant::now();
+        }
+        self.current
+    }
+
+    /// Check whether given token is valid.
+    fn valid(&mut self, token: i32) -> bool {
+        token == self.token() || token == self.previous
+    }
+}
+
+/// Serve GS4 query requests on UDP.
+///
+/// Answers basic and full stat requests with the last known server status.
+/// See: https://wiki.vg/Query
+pub async fn serve(config: Arc<Config>, server: Arc<ServerState>) -> Result<(), ()> {
+    let addr = config.query.address;
+    let socket = UdpSocket::bind(addr).await.map_err(|err| {
+        error!(target: "lazymc", "Failed to bind query socket on {}: {}", addr, err);
+    })?;
+    info!(target: "lazymc", "Serving query on {}", addr);
+
+    let mut challenge = Challenge::new();
+    let mut buf = [0; MAX_REQUEST_LEN];
+
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(result) => result,
+            Err(err) => {
+                debug!(target: "lazymc", "Failed to receive query packet: {}", err);
+                continue;
+            }
+        };
+
+        if let Some(response) = respond(&config, &server, &mut challenge, &buf[..len], peer) {
+            if let Err(err) = socket.send_to(&response, peer).await {
+                debug!(target: "lazymc", "Failed to send query response to {}: {}", peer, err);
+            }
+        }
+    }
+}
+
+/// Build response to query request, `None` if request is invalid.
+fn respond(
+    config: &Config,
+    server: &ServerState,
+    challenge: &mut Challenge,
+    request: &[u8],
+    peer: SocketAddr,
+) -> Option<Vec<u8>> {
+    if request.len() < 7 || request[..2] != MAGIC {
+        return None;
+    }
+    let packet_type = request[2];
+    let session = i32::from_be_bytes(request[3..7].try_into().ok()?) & 0x0F0F0F0F;
+
+    let mut response = vec![packet_type];
+    response.extend_from_slice(&session.to_be_bytes());
+
+    match packet_type {
+        TYPE_HANDSHAKE => {
+            trace!(target: "lazymc", "Query handshake from {}", peer);
+            write_string(&mut response, &challenge.token().to_string());
+        }
+        TYPE_STAT => {
+            let token = i32::from_be_bytes(request.get(7..11)?.try_into().ok()?);
+            if !challenge.valid(token) {
+                debug!(target: "lazymc", "Query from {} with invalid challenge token", peer);
+                return None;
+            }
+
+            // Full stat requests are padded to 15 bytes
+            let stat = Stat::new(config, server);
+            if request.len() >= 15 {
+                stat.write_full(&mut response);
+            } else {
+                stat.write_basic(&mut response);
+            }
+        }
+        _ => return None,
+    }
+
+    Some(response)
+}
+
+/// Server stats reported through query.
+struct Stat {
+    /// Message of the day.
+    motd: String,
+
+    /// Server version name.
+    version: String,
+
+    /// Number of online players.
+    online: u32,
+
+    /// Maximum number of players.
+    max: u32,
+
+    /// Names of online players.
+    players: Vec<String>,
+
+    /// Public server address.
+    address: SocketAddr,
+
+    /// World name.
+    map: String,
+}
+
+impl Stat {
+    /// Collect stats from the last known server status.
+    fn new(config: &Config, server: &ServerState) -> Self {
+        let status = server.clone_status();
+        let online = server.online();
+
+        // Report server MOTD if online, configured MOTD otherwise
+        let motd = match status.as_ref().map(|status| &status.description.payload) {
+            Some(Payload::Text { text }) if online => text.clone(),
+            _ if server.starting() => {
+                messages::render(&config.messages.motd_starting, config, server)
+            }
+            _ => messages::render(&config.messages.motd_sleeping, config, server),
+        };
+
+        Self {
+            motd: motd.replace('\n', " "),
+            version: status
+                .as_ref()
+                .map(|status| status.version.name.clone())
+                .unwrap_or_default(),
+            online: status
+                .as_ref()
+                .filter(|_| online)
+                .map(|status| status.players.online)
+                .unwrap_or(0),
+            max: status
+                .as_ref()
+                .map(|status| status.players.max)
+                .unwrap_or(0),
+            players: status
+                .filter(|_| online)
+                .map(|status| status.players.sample.into_iter().map(|p| p.name).collect())
+                .unwrap_or_default(),
+            address: config.public.address.first(),
+            map: config.query.map.clone(),
+        }
+    }
+
+    /// Write basic stat response data.
+    fn write_basic(&self, buf: &mut Vec<u8>) {
+        write_string(buf, &self.motd);
+        write_string(buf, "SMP");
+        write_string(buf, &self.map);
+        write_string(buf, &self.online.to_string());
+        write_string(buf, &self.max.to_string());
+        buf.extend_from_slice(&self.address.port().to_le_bytes());
+        write_string(buf, &self.address.ip().to_string());
+    }
+
+    /// Write full stat response data.
+    fn write_full(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(FULL_STAT_PADDING_KV);
+        for (key, value) in [
+            ("hostname", self.motd.as_str()),
+            ("gametype", "SMP"),
+            ("game_id", "MINECRAFT"),
+            ("version", self.version.as_str()),
+            ("plugins", ""),
+            ("map", self.map.as_str()),
+            ("numplayers", &self.online.to_string()),
+            ("maxplayers", &self.max.to_string()),
+            ("hostport", &self.address.port().to_string()),
+            ("hostip", &self.address.ip().to_string()),
+        ] {
+            write_string(buf, key);
+            write_string(buf, value);
+        }
+        buf.push(0);
+
+        buf.extend_from_slice(FULL_STAT_PADDING_PLAYERS);
+        for player in &self.players {
+            write_string(buf, player);
+        }
+        buf.push(0);
+    }
+}
+
+/// Write null-terminated string.
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.push(0);
+}
@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// FIFO queue of clients waiting to join while the server starts.
+///
+/// Clients take a ticket, and are admitted in order by the monitor once the server is online.
+#[derive(Debug, Default)]
+pub struct Queue {
+    /// Queued ticket IDs, in join order.
+    queue: Mutex<VecDeque<u64>>,
+
+    /// Next ticket ID.
+    next_id: AtomicU64,
+
+    /// Tickets with an ID below this are admitted.
+    admitted_until: AtomicU64,
+}
+
+impl Queue {
+    /// Enqueue a new client, returns its ticket.
+    ///
+    /// The client leaves the queue when the ticket is dropped.
+    pub fn enqueue(self: &Arc<Self>) -> Ticket {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.queue.lock().unwrap().push_back(id);
+        Ticket {
+            id,
+            queue: self.clone(),
+        }
+    }
+
+    /// Number of queued clients.
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Admit the given number of clients from the front of the queue.
+    ///
+    /// Returns the number of admitted clients.
+    pub fn admit(&self, count: usize) -> usize {
+        let mut queue = self.queue.lock().unwrap();
+        let count = count.min(queue.len());
+        if let Some(id) = queue.drain(..count).next_back() {
+            self.admitted_until.fetch_max(id + 1, Ordering::Relaxed);
+        }
+        count
+    }
+
+    /// Get position of ticket in queue, starting at 1.
+    fn position(&self, id: u64) -> Option<usize> {
+        self.queue
+            .lock()
+            .unwrap()
+            .iter()
+            .position(|i| *i == id)
+            .map(|p| p + 1)
+    }
+
+    /// Remove ticket from queue.
+    fn remove(&self, id: u64) {
+        self.queue.lock().unwrap().retain(|i| *i != id);
+    }
+}
+
+/// Join queue ticket.
+///
+/// Leaves the queue when dropped.
+#[derive(Debug)]
+pub struct Ticket {
+    /// Ticket ID.
+    id: u64,
+
+    /// Queue this ticket is in.
+    queue: Arc<Queue>,
+}
+
+impl Ticket {
+    /// Position in queue, starting at 1.
+    ///
+    /// Returns `None` if admitted.
+    pub fn position(&self) -> Option<usize> {
+        self.queue.position(self.id)
+    }
+
+    /// Whether this ticket is admitted to join.
+    pub fn admitted(&self) -> bool {
+        self.id < self.queue.admitted_until.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        self.queue.remove(self.id);
+    }
+}
+
+/// Estimate time to wait after the server is online for the client at the given queue position.
+pub fn estimate_wait(config: &Config, position: usize) -> Duration {
+    let admit = config.join.queue.admit.max(1) as usize;
+    let polls = position.div_ceil(admit);
+    config.monitor.interval() * polls as u32
+}
+
+/// Build queue message for the client at the given queue position.
+pub fn message(config: &Config, position: usize) -> String {
+    config
+        .join
+        .queue
+        .message
+        .replace("{position}", &position.to_string())
+        .replace(
+            "{wait}",
+            &estimate_wait(config, position).as_secs().to_string(),
+        )
+}
@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::Config;
+use crate::server::{self, ServerState};
+use crate::util::net;
+
+/// RCON login packet type.
+const TYPE_LOGIN: i32 = 3;
+
+/// RCON command packet type, also used for login responses.
+const TYPE_COMMAND: i32 = 2;
+
+/// RCON command response packet type.
+const TYPE_RESPONSE: i32 = 0;
+
+/// Request ID responded with on failed login.
+const ID_LOGIN_FAILED: i32 = -1;
+
+/// Minimum RCON packet length, for an empty body.
+const MIN_PACKET_LEN: i32 = 10;
+
+/// Maximum RCON packet length accepted from clients.
+const MAX_PACKET_LEN: i32 = 4096 + MIN_PACKET_LEN;
+
+/// Time after which idle RCON clients are disconnected.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Commands queued while the server is not online.
+#[derive(Debug, Default)]
+pub struct CommandQueue(Mutex<VecDeque<String>>);
+
+impl CommandQueue {
+    /// Queue a command, returns its position or `None` if the queue is full.
+    fn push(&self, cmd: String, max: usize) -> Option<usize> {
+        let mut queue = self.0.lock().unwrap();
+        if queue.len() >= max {
+            return None;
+        }
+        queue.push_back(cmd);
+        Some(queue.len())
+    }
+
+    /// Take the next queued command.
+    fn pop(&self) -> Option<String> {
+        self.0.lock().unwrap().pop_front()
+    }
+
+    /// Put a command back in front of the queue.
+    fn unpop(&self, cmd: String) {
+        self.0.lock().unwrap().push_front(cmd);
+    }
+}
+
+/// RCON packet.
+struct Packet {
+    /// Request ID.
+    id: i32,
+
+    /// Packet type.
+    kind: i32,
+
+    /// Packet body.
+    body: String,
+}
+
+impl Packet {
+    /// Encode packet to raw buffer.
+    fn encode(&self) -> Vec<u8> {
+        let len = self.body.len() as i32 + MIN_PACKET_LEN;
+        let mut packet = Vec::with_capacity(len as usize + 4);
+        packet.extend_from_slice(&len.to_le_bytes());
+        packet.extend_from_slice(&self.id.to_le_bytes());
+        packet.extend_from_slice(&self.kind.to_le_bytes());
+        packet.extend_from_slice(self.body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        packet
+    }
+}
+
+/// Serve public RCON endpoint.
+pub async fn serve(config: Arc<Config>, server: Arc<ServerState>) -> Result<(), ()> {
+    let listener = TcpListener::bind(config.rcon.public.address)
+        .await
+        .map_err(|err| {
+            error!(target: "lazymc::rcon", "Failed to bind RCON socket: {}", err);
+        })?;
+
+    info!(
+        target: "lazymc",
+        "Serving RCON on {}",
+        config.rcon.public.address,
+    );
+
+    while let Ok((inbound, peer)) = listener.accept().await {
+        if !net::allowed(&config.public.allow, &config.public.deny, peer.ip()) {
+            debug!(target: "lazymc::rcon", "Dropping RCON client {}, IP not allowed", peer.ip());
+            continue;
+        }
+
+        let config = config.clone();
+        let server = server.clone();
+        tokio::spawn(async move {
+            let _ = serve_client(&config, &server, inbound, peer).await;
+        });
+    }
+
+    Ok(())
+}
+
+/// Serve a single RCON client.
+async fn serve_client(
+    config: &Config,
+    server: &ServerState,
+    mut stream: TcpStream,
+    peer: SocketAddr,
+) -> Result<(), ()> {
+    let mut authenticated = false;
+
+    while let Some(packet) = read_packet_timeout(&mut stream).await? {
+        let response = match packet.kind {
+            TYPE_LOGIN => {
+                authenticated = packet.body == config.rcon.public.password;
+                if !authenticated {
+                    warn!(target: "lazymc::rcon", "RCON login from {} failed", peer);
+                }
+                Packet {
+                    id: if authenticated {
+                        packet.id
+                    } else {
+                        ID_LOGIN_FAILED
+                    },
+                    kind: TYPE_COMMAND,
+                    body: String::new(),
+                }
+            }
+            TYPE_COMMAND if authenticated => Packet {
+                id: packet.id,
+                kind: TYPE_RESPONSE,
+                body: command(config, server, packet.body).await,
+            },
+            _ => {
+                debug!(target: "lazymc::rcon", "Closing RCON client {}, unexpected packet", peer);
+                return Ok(());
+            }
+        };
+
+        stream.write_all(&response.encode()).await.map_err(|_| ())?;
+
+        // Close connection after failed login
+        if !authenticated {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+/// Invoke a command from an RCON client, returns the response body.
+///
+/// Commands are forwarded if the server is online, and queued or refused otherwise.
+async fn command(config: &Config, server: &ServerState, cmd: String) -> String {
+    if server.online() {
+        return match server::rcon_query(config, &cmd).await {
+            Ok(response) => response,
+            Err(_) => error_response("command_failed", None),
+        };
+    }
+
+    let error = if server.starting() {
+        "server_starting"
+    } else {
+        "server_sleeping"
+    };
+    if !config.rcon.public.queue {
+        return error_response(error, None);
+    }
+
+    match server
+        .rcon_queue
+        .push(cmd, config.rcon.public.queue_size as usize)
+    {
+        Some(position) => {
+            debug!(target: "lazymc::rcon", "Queued RCON command at position {}", position);
+            error_response(error, Some(position))
+        }
+        None => error_response("queue_full", None),
+    }
+}
+
+/// Build a structured JSON error response, with the queue position if the command was queued.
+fn error_response(error: &str, position: Option<usize>) -> String {
+    let response = match position {
+        Some(position) => serde_json::json!({
+            "error": error,
+            "queued": true,
+            "position": position,
+        }),
+        None => serde_json::json!({
+            "error": error,
+            "queued": false,
+        }),
+    };
+    response.to_string()
+}
+
+/// Invoke commands queued while the server was not online.
+///
+/// Stops at the first command that fails, to retry it later.
+pub async fn invoke_queued(config: &Config, server: &ServerState) {
+    while let Some(cmd) = server.rcon_queue.pop() {
+        if !server::rcon_command(config, &cmd).await {
+            server.rcon_queue.unpop(cmd);
+            break;
+        }
+        info!(target: "lazymc::rcon", "Invoked queued RCON command: {}", cmd);
+    }
+}
+
+/// Read RCON packet from stream, giving up after the idle timeout.
+///
+/// Returns `Ok(None)` if the stream was closed or timed out.
+async fn read_packet_timeout(stream: &mut TcpStream) -> Result<Option<Packet>, ()> {
+    match tokio::time::timeout(IDLE_TIMEOUT, read_packet(stream)).await {
+        Ok(result) => result,
+        Err(_) => Ok(None),
+    }
+}
+
+/// Read RCON packet from stream.
+///
+/// Returns `Ok(None)` if the stream was closed.
+async fn read_packet(stream: &mut TcpStream) -> Result<Option<Packet>, ()> {
+    let len = match stream.read_i32_le().await {
+        Ok(len) => len,
+        Err(_) => return Ok(None),
+    };
+    if !(MIN_PACKET_LEN..=MAX_PACKET_LEN).contains(&len) {
+        debug!(target: "lazymc::rcon", "Malformed RCON packet, invalid length: {}", len);
+        return Err(());
+    }
+
+    let mut buf = vec![0; len as usize];
+    stream.read_exact(&mut buf).await.map_err(|_| ())?;
+
+    let id = i32::from_le_bytes(buf[0..4].try_into().unwrap());
+    let kind = i32::from_le_bytes(buf[4..8].try_into().unwrap());
+    let body = &buf[8..buf.len() - 2];
+    let body = String::from_utf8_lossy(body).into_owned();
+
+    Ok(Some(Packet { id, kind, body }))
+}
@@ -1,35 +1,174 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use chrono::{Local, NaiveDate};
 use futures::FutureExt;
-use minecraft_protocol::data::server_status::ServerStatus;
-use tokio::process::Command;
+use minecraft_protocol::data::server_status::{OnlinePlayer, ServerStatus};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::watch;
 
-use crate::config::Config;
+use crate::analytics::{PingLog, ResourceLog};
+use crate::backend;
+use crate::backup;
+use crate::config::{Backend, Config, ReadySignal, SleepMethod, StopMethod};
+use crate::hooks::{self, Event};
+use crate::mc::eula;
+use crate::monitor;
+use crate::persist;
+use crate::queue::Queue;
+use crate::status::StatusCache;
+use crate::util::rate::TokenBucket;
+
+/// Number of past server start durations to remember for estimating start time.
+const START_HISTORY: usize = 10;
+
+/// Maximum delay before restarting a crashed server.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(300);
+
+/// Interval to check whether a stopping server should be killed.
+const KILL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delay before retrying to connect to RCON, increasing with each attempt.
+#[cfg(feature = "rcon")]
+const RCON_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Server log marker reporting world preparation progress, followed by a percentage.
+const PROGRESS_MARKER: &str = "Preparing spawn area";
+
+/// Server log markers reporting the server is done starting, such as
+/// `Done (3.456s)! For help, type "help"`.
+const READY_MARKERS: (&str, &str) = ("Done (", ")! For help");
+
+/// Server state, with validated transitions between states.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// Server is not running.
+    Sleeping,
+
+    /// Server is starting.
+    Starting,
+
+    /// Server is online.
+    Online,
+
+    /// Server is online, but was not started by lazymc and is never put to sleep.
+    Unmanaged,
+
+    /// Server is stopping.
+    Stopping,
+
+    /// Server process is frozen.
+    Frozen,
+}
+
+impl State {
+    /// Get state from its atomic representation.
+    fn from_u8(state: u8) -> Self {
+        match state {
+            1 => Self::Starting,
+            2 => Self::Online,
+            3 => Self::Unmanaged,
+            4 => Self::Stopping,
+            5 => Self::Frozen,
+            _ => Self::Sleeping,
+        }
+    }
+
+    /// Whether the server may transition from this state into the given state.
+    fn can_transition(self, to: State) -> bool {
+        use State::*;
+        matches!(
+            (self, to),
+            (Sleeping, Starting | Unmanaged)
+                | (Starting, Online | Stopping | Sleeping)
+                | (Online, Starting | Stopping | Frozen | Sleeping)
+                | (Unmanaged, Starting | Sleeping)
+                | (Stopping, Sleeping)
+                | (Frozen, Starting | Sleeping)
+        )
+    }
+}
+
+/// Channel publishing server state transitions.
+#[derive(Debug)]
+struct StateChannel {
+    tx: watch::Sender<State>,
+
+    /// Kept to never close the channel, sending fails without receivers.
+    rx: watch::Receiver<State>,
+}
+
+impl Default for StateChannel {
+    fn default() -> Self {
+        let (tx, rx) = watch::channel(State::Sleeping);
+        Self { tx, rx }
+    }
+}
 
 /// Shared server state.
 #[derive(Default, Debug)]
 pub struct ServerState {
-    /// Whether the server is online.
-    online: AtomicBool,
+    /// Current server state, as `State` discriminant.
+    state: AtomicU8,
 
-    /// Whether the server is starting.
-    // TODO: use enum for starting/started/stopping states
-    starting: AtomicBool,
+    /// Channel publishing server state transitions.
+    state_channel: StateChannel,
 
-    /// Whether the server is stopping.
-    stopping: AtomicBool,
+    /// Whether the server crashed and is being restarted.
+    crashed: AtomicBool,
+
+    /// Number of consecutive server crashes.
+    crashes: AtomicU32,
+
+    /// Whether players were online since the server came online.
+    had_players: AtomicBool,
+
+    /// Number of client connections currently proxied to the server.
+    proxied: AtomicU32,
+
+    /// Total number of bytes proxied clients sent to the server.
+    traffic: AtomicU64,
+
+    /// Time and total traffic at the last throughput measurement.
+    traffic_sample: Mutex<Option<(Instant, u64)>>,
+
+    /// Time to sleep at after the countdown, and the last warning given in seconds.
+    sleep_countdown: Mutex<Option<(Instant, u32)>>,
+
+    /// Time the server last failed to come online in time.
+    start_failed: Mutex<Option<Instant>>,
+
+    /// Readiness signals seen while the server is starting.
+    ready_signals: Mutex<Vec<ReadySignal>>,
 
     /// Server PID.
     pid: Mutex<Option<u32>>,
 
+    /// Server console input, if piped.
+    stdin: Mutex<Option<ChildStdin>>,
+
+    /// Time after which a stopping server is killed if it hasn't quit.
+    kill_deadline: Mutex<Option<Instant>>,
+
     /// Last known server status.
     ///
     /// Once set, this will remain set, and isn't cleared when the server goes offline.
     // TODO: make this private?
     pub status: Mutex<Option<ServerStatus>>,
 
+    /// Last known non-empty sample of online players.
+    last_players: Mutex<Vec<OnlinePlayer>>,
+
+    /// Last known server favicon, as base64 encoded PNG data URI.
+    ///
+    /// Once set, this will remain set, and isn't cleared when the server goes offline.
+    favicon: Mutex<Option<String>>,
+
     /// Last active time.
     ///
     /// The last known time when the server was active with online players.
@@ -37,64 +176,325 @@ pub struct ServerState {
 
     /// Keep server online until.
     keep_online_until: Mutex<Option<Instant>>,
+
+    /// Log of recent status pings.
+    pub pings: PingLog,
+
+    /// Server process resource usage since the last summary.
+    pub resources: ResourceLog,
+
+    /// Cache of status responses while sleeping or starting.
+    pub status_cache: StatusCache,
+
+    /// Queue of clients waiting to join while the server starts.
+    pub queue: Arc<Queue>,
+
+    /// RCON commands queued while the server is not online.
+    #[cfg(feature = "rcon")]
+    pub rcon_queue: crate::rcon::CommandQueue,
+
+    /// Time the current server start was initiated at.
+    start_time: Mutex<Option<Instant>>,
+
+    /// Start progress percentage, as reported in the server log while starting.
+    start_progress: Mutex<Option<u8>>,
+
+    /// Durations of recent server starts, most recent last.
+    start_durations: Mutex<VecDeque<Duration>>,
+
+    /// Time the server last stopped at.
+    stop_time: Mutex<Option<Instant>>,
+
+    /// Number of times the server was started.
+    wake_count: AtomicU32,
+
+    /// Local date and number of times the server was started on that day.
+    wakes_today: Mutex<Option<(NaiveDate, u32)>>,
+
+    /// Rate limiter for waking the server, created on first use.
+    wake_limiter: Mutex<Option<TokenBucket>>,
 }
 
 impl ServerState {
     /// Whether the server is online.
     pub fn online(&self) -> bool {
-        self.online.load(Ordering::Relaxed)
+        matches!(self.state(), State::Online | State::Unmanaged)
     }
 
-    /// Set whether the server is online.
-    pub fn set_online(&self, online: bool) {
-        self.online.store(online, Ordering::Relaxed)
+    /// Whether the server lazymc started is running, so starting, online or stopping.
+    pub fn starting(&self) -> bool {
+        matches!(
+            self.state(),
+            State::Starting | State::Online | State::Stopping
+        )
     }
 
-    /// Whether the server is starting.
-    pub fn starting(&self) -> bool {
-        self.starting.load(Ordering::Relaxed)
+    /// Whether the server is stopping.
+    pub fn stopping(&self) -> bool {
+        self.state() == State::Stopping
     }
 
-    /// Set whether the server is starting.
-    pub fn set_starting(&self, starting: bool) {
-        self.starting.store(starting, Ordering::Relaxed)
+    /// Whether the server process is frozen.
+    pub fn frozen(&self) -> bool {
+        self.state() == State::Frozen
     }
 
-    /// Kill any running server.
+    /// Current server state.
+    pub fn state(&self) -> State {
+        State::from_u8(self.state.load(Ordering::SeqCst))
+    }
+
+    /// Subscribe to server state transitions.
+    pub fn subscribe(&self) -> watch::Receiver<State> {
+        self.state_channel.rx.clone()
+    }
+
+    /// Transition to the given state, if valid from the current state.
+    fn transition(&self, to: State) -> bool {
+        self.transition_if(|from| from.can_transition(to), to)
+    }
+
+    /// Transition to the given state, only if currently in one of the given states.
+    fn transition_from(&self, from: &[State], to: State) -> bool {
+        self.transition_if(|state| from.contains(&state), to)
+    }
+
+    /// Atomically transition to the given state, if valid and allowed by the predicate.
+    ///
+    /// Publishes the new state to subscribers. Returns `false` if the state was not changed.
+    fn transition_if(&self, allow: impl Fn(State) -> bool, to: State) -> bool {
+        let result = self
+            .state
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |from| {
+                let from = State::from_u8(from);
+                (allow(from) && from.can_transition(to)).then_some(to as u8)
+            });
+
+        match result {
+            Ok(from) => {
+                let from = State::from_u8(from);
+                trace!(target: "lazymc", "Server state changed from {:?} to {:?}", from, to);
+                let _ = self.state_channel.tx.send(self.state());
+                true
+            }
+            Err(from) => {
+                let from = State::from_u8(from);
+                if allow(from) {
+                    trace!(target: "lazymc", "Invalid server state change from {:?} to {:?}", from, to);
+                }
+                false
+            }
+        }
+    }
+
+    /// Whether the server crashed and is being restarted.
+    pub fn crashed(&self) -> bool {
+        self.crashed.load(Ordering::Relaxed)
+    }
+
+    /// Number of consecutive server crashes.
+    pub fn crashes(&self) -> u32 {
+        self.crashes.load(Ordering::Relaxed)
+    }
+
+    /// Put the server to sleep with the configured sleep method.
+    ///
+    /// Stops the server if freezing fails.
+    pub async fn sleep_server(&self, config: &Config) -> bool {
+        if config.server.sleep_method == SleepMethod::Freeze {
+            if self.freeze_server(config).await {
+                hooks::fire(config, Event::Sleep);
+                return true;
+            }
+            warn!(target: "lazymc", "Failed to freeze server, stopping it instead");
+        }
+
+        let stopped = self.kill_server(config).await;
+        if stopped {
+            hooks::fire(config, Event::Sleep);
+        }
+        stopped
+    }
+
+    /// Freeze the running server process.
     #[allow(unused_variables)]
-    pub async fn kill_server(&self, config: &Config) -> bool {
-        // Ensure we have a running process
-        let has_process = self.pid.lock().unwrap().is_some();
-        if !has_process {
+    async fn freeze_server(&self, config: &Config) -> bool {
+        let pid = *self.pid.lock().unwrap();
+        let pid = match pid {
+            Some(pid) => pid,
+            None => return false,
+        };
+
+        // Save world first, so nothing is lost if the process is killed while frozen
+        #[cfg(feature = "rcon")]
+        save_server_rcon(config).await;
+
+        if !crate::os::freeze(pid) {
             return false;
         }
 
-        // Try to kill through RCON
-        #[cfg(feature = "rcon")]
-        if stop_server_rcon(config, &self).await {
-            // TODO: set stopping state elsewhere
-            self.stopping.store(true, Ordering::Relaxed);
+        // Server may have changed state while saving, don't leave it frozen then
+        if !self.transition_from(&[State::Online], State::Frozen) {
+            crate::os::unfreeze(pid);
+            return false;
+        }
 
-            return true;
+        self.set_keep_online_until(None);
+        self.stop_time.lock().unwrap().replace(Instant::now());
+        true
+    }
+
+    /// Unfreeze the server process if it is frozen.
+    ///
+    /// Returns `true` if the server was frozen and is now running again.
+    fn unfreeze_server(&self) -> bool {
+        if !self.frozen() {
+            return false;
+        }
+
+        let pid = *self.pid.lock().unwrap();
+        match pid {
+            Some(pid) if crate::os::unfreeze(pid) => {
+                self.transition_from(&[State::Frozen], State::Starting)
+            }
+            _ => {
+                self.transition_from(&[State::Frozen], State::Sleeping);
+                false
+            }
         }
+    }
 
-        // Try to kill through signal
-        #[cfg(unix)]
-        if stop_server_signal(&self) {
-            // TODO: set stopping state elsewhere
-            self.stopping.store(true, Ordering::Relaxed);
+    /// Mark server as stopping, and schedule killing it if it doesn't quit in time.
+    fn set_stopping(&self, config: &Config) {
+        if !self.transition(State::Stopping) {
+            return;
+        }
 
-            return true;
+        if config.server.stop_timeout > 0 {
+            let timeout = Duration::from_secs(config.server.stop_timeout as u64);
+            self.kill_deadline
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| Instant::now() + timeout);
+        }
+    }
+
+    /// Check whether the server failed to come online in time after starting.
+    ///
+    /// Marks the server as stopping and as failed to start if so, which isn't a crash.
+    pub fn check_start_timeout(&self, config: &Config) -> bool {
+        let timeout = Duration::from_secs(config.server.start_timeout as u64);
+        let started = *self.start_time.lock().unwrap();
+        let timed_out =
+            !timeout.is_zero() && started.is_some_and(|started| started.elapsed() >= timeout);
+        if !timed_out || !self.transition_from(&[State::Starting], State::Stopping) {
+            return false;
+        }
+
+        let timeout = timeout.as_secs();
+        warn!(target: "lazymc", "Server did not come online within {}s, stopping it", timeout);
+        self.start_failed.lock().unwrap().replace(Instant::now());
+        true
+    }
+
+    /// Write input to the server console, such as a command.
+    ///
+    /// Returns `false` if the server isn't running or writing failed.
+    pub async fn write_console(&self, input: &[u8]) -> bool {
+        // Take console input while writing, we can't hold the lock across await
+        let mut stdin = match self.stdin.lock().unwrap().take() {
+            Some(stdin) => stdin,
+            None => return false,
+        };
+
+        let result = stdin.write_all(input).await;
+        let _ = stdin.flush().await;
+        self.stdin.lock().unwrap().replace(stdin);
+
+        if let Err(err) = result {
+            debug!(target: "lazymc", "Failed to write to server console: {}", err);
+            return false;
+        }
+        true
+    }
+
+    /// Kill any running server.
+    pub async fn kill_server(&self, config: &Config) -> bool {
+        // Stop server through backend if not running it as process
+        let stopped = match config.server.backend {
+            Backend::Process => None,
+            Backend::Systemd => Some(self.starting() && backend::systemd::stop(config).await),
+            Backend::Ssh => Some(self.starting() && backend::ssh::stop(config).await),
+            Backend::Wol => Some(self.starting() && backend::wol::stop(config, self).await),
+            Backend::Pterodactyl => {
+                Some(self.starting() && backend::pterodactyl::stop(config).await)
+            }
+            #[cfg(feature = "kubernetes")]
+            Backend::Kubernetes => Some(self.starting() && backend::kubernetes::stop(config).await),
+        };
+        if let Some(stopped) = stopped {
+            if stopped {
+                self.set_stopping(config);
+            }
+            return stopped;
+        }
+
+        // Ensure we have a running process, adopted servers may not have a known process
+        if self.pid().is_none() && !self.starting() {
+            return false;
+        }
+
+        // Resume frozen server, so it can handle being stopped
+        self.unfreeze_server();
+
+        // Try configured stop methods in order
+        for method in &config.server.stop_methods {
+            let stopped = match method {
+                #[cfg(feature = "rcon")]
+                StopMethod::Rcon => stop_server_rcon(config, self).await,
+                #[cfg(not(feature = "rcon"))]
+                StopMethod::Rcon => false,
+                StopMethod::Stdin => stop_server_stdin(self).await,
+                #[cfg(unix)]
+                StopMethod::Signal => stop_server_signal(self),
+                #[cfg(not(unix))]
+                StopMethod::Signal => false,
+                StopMethod::Kill => stop_server_kill(self),
+            };
+            if stopped {
+                self.set_stopping(config);
+                return true;
+            }
         }
 
         false
     }
 
+    /// Server PID, if running as process.
+    pub fn pid(&self) -> Option<u32> {
+        *self.pid.lock().unwrap()
+    }
+
     /// Set server PID.
     pub fn set_pid(&self, pid: Option<u32>) {
         *self.pid.lock().unwrap() = pid;
     }
 
+    /// Last known non-empty sample of online players.
+    pub fn last_players(&self) -> Vec<OnlinePlayer> {
+        self.last_players.lock().unwrap().clone()
+    }
+
+    /// Last known server favicon.
+    pub fn favicon(&self) -> Option<String> {
+        self.favicon.lock().unwrap().clone()
+    }
+
+    /// Update the server favicon.
+    pub fn set_favicon(&self, favicon: String) {
+        self.favicon.lock().unwrap().replace(favicon);
+    }
+
     /// Clone the last known server status.
     pub fn clone_status(&self) -> Option<ServerStatus> {
         self.status.lock().unwrap().clone()
@@ -105,11 +505,122 @@ impl ServerState {
         self.status.lock().unwrap().replace(status);
     }
 
+    /// Last active time, when players were last online.
+    pub fn last_active_time(&self) -> Option<Instant> {
+        *self.last_active.lock().unwrap()
+    }
+
+    /// Time the server last stopped at.
+    pub fn stop_time(&self) -> Option<Instant> {
+        *self.stop_time.lock().unwrap()
+    }
+
+    /// Number of times the server was started.
+    pub fn wake_count(&self) -> u32 {
+        self.wake_count.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the server was started today, in local time.
+    pub fn wakes_today(&self) -> u32 {
+        match *self.wakes_today.lock().unwrap() {
+            Some((date, count)) if date == Local::now().date_naive() => count,
+            _ => 0,
+        }
+    }
+
+    /// Count a server start for today.
+    fn count_wake_today(&self) {
+        let today = Local::now().date_naive();
+        let mut wakes = self.wakes_today.lock().unwrap();
+        match wakes.as_mut() {
+            Some((date, count)) if *date == today => *count += 1,
+            _ => *wakes = Some((today, 1)),
+        }
+    }
+
+    /// Take a snapshot of the state to persist across restarts.
+    pub fn persisted(&self) -> persist::State {
+        let last_active = self
+            .last_active_time()
+            .map(|time| persist::unix_now().saturating_sub(time.elapsed().as_secs()));
+        persist::State {
+            status: self.clone_status(),
+            favicon: self.favicon(),
+            last_active,
+            wake_count: self.wake_count(),
+            wakes_today: self
+                .wakes_today
+                .lock()
+                .unwrap()
+                .map(|(date, count)| (date.to_string(), count)),
+            start_durations: self
+                .start_durations()
+                .iter()
+                .map(|duration| duration.as_millis() as u64)
+                .collect(),
+        }
+    }
+
+    /// Restore state persisted before a restart.
+    pub fn restore(&self, state: persist::State) {
+        if let Some(status) = state.status {
+            self.set_status(status);
+        }
+        if let Some(favicon) = state.favicon {
+            self.set_favicon(favicon);
+        }
+        if let Some(last_active) = state.last_active {
+            let elapsed = persist::unix_now().saturating_sub(last_active);
+            *self.last_active.lock().unwrap() =
+                Instant::now().checked_sub(Duration::from_secs(elapsed));
+        }
+        self.wake_count.store(state.wake_count, Ordering::Relaxed);
+        *self.wakes_today.lock().unwrap() = state
+            .wakes_today
+            .and_then(|(date, count)| Some((date.parse().ok()?, count)));
+        let skip = state.start_durations.len().saturating_sub(START_HISTORY);
+        *self.start_durations.lock().unwrap() = state
+            .start_durations
+            .into_iter()
+            .skip(skip)
+            .map(Duration::from_millis)
+            .collect();
+    }
+
     /// Update the last active time.
     pub fn update_last_active_time(&self) {
         self.last_active.lock().unwrap().replace(Instant::now());
     }
 
+    /// Number of client connections currently proxied to the server.
+    pub fn proxied(&self) -> u32 {
+        self.proxied.load(Ordering::Relaxed)
+    }
+
+    /// Measure average number of bytes per second proxied clients sent since the last measurement.
+    fn measure_throughput(&self) -> u64 {
+        let traffic = self.traffic.load(Ordering::Relaxed);
+        let now = Instant::now();
+        let last = self.traffic_sample.lock().unwrap().replace((now, traffic));
+        match last {
+            Some((time, last)) => {
+                let elapsed = now.duration_since(time).as_secs_f64();
+                if elapsed > 0.0 {
+                    (traffic.saturating_sub(last) as f64 / elapsed) as u64
+                } else {
+                    0
+                }
+            }
+            None => 0,
+        }
+    }
+
+    /// Count a client connection proxied to the server, until the returned guard is dropped.
+    pub fn track_proxied(self: &Arc<Self>) -> Proxied {
+        self.proxied.fetch_add(1, Ordering::Relaxed);
+        Proxied(self.clone())
+    }
+
     /// Update the last active time.
     pub fn set_keep_online_until(&self, duration: Option<u32>) {
         *self.keep_online_until.lock().unwrap() = duration
@@ -117,32 +628,200 @@ impl ServerState {
             .map(|d| Instant::now() + Duration::from_secs(d as u64));
     }
 
+    /// Estimate remaining time until the server is online while it is starting.
+    ///
+    /// Based on the estimated start duration. Returns `None` if unknown.
+    pub fn start_eta(&self) -> Option<Duration> {
+        let started = (*self.start_time.lock().unwrap())?;
+        Some(self.start_estimate()?.saturating_sub(started.elapsed()))
+    }
+
+    /// Estimate how long a server start takes, as median duration of recent starts.
+    ///
+    /// Returns `None` if the server wasn't started yet.
+    pub fn start_estimate(&self) -> Option<Duration> {
+        let mut durations = self.start_durations();
+        if durations.is_empty() {
+            return None;
+        }
+
+        durations.sort();
+        let len = durations.len();
+        Some((durations[(len - 1) / 2] + durations[len / 2]) / 2)
+    }
+
+    /// Durations of recent server starts, most recent last.
+    pub fn start_durations(&self) -> Vec<Duration> {
+        self.start_durations
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    /// Start progress percentage while the server is starting, if reported yet.
+    pub fn start_progress(&self) -> Option<u8> {
+        *self.start_progress.lock().unwrap()
+    }
+
+    /// Remaining wake cooldown after the server last stopped, if any.
+    pub fn wake_cooldown(&self, config: &Config) -> Option<Duration> {
+        let stopped = (*self.stop_time.lock().unwrap())?;
+        Duration::from_secs(config.wake.cooldown as u64)
+            .checked_sub(stopped.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Remaining time wakes are blocked after the server failed to start in time, if any.
+    pub fn start_failed_cooldown(&self, config: &Config) -> Option<Duration> {
+        let failed = (*self.start_failed.lock().unwrap())?;
+        Duration::from_secs(config.server.start_failed_cooldown as u64)
+            .checked_sub(failed.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Try to take a wake from the wake rate limit, returns `false` if the limit is reached.
+    pub fn take_wake(&self, config: &Config) -> bool {
+        if config.wake.rate_limit == 0 {
+            return true;
+        }
+
+        self.wake_limiter
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                TokenBucket::new(
+                    config.wake.rate_limit,
+                    Duration::from_secs(config.wake.rate_limit_period as u64),
+                )
+            })
+            .try_take()
+    }
+
+    /// Remember the duration of the current server start, now that it is online.
+    fn finish_start(&self, config: &Config) {
+        self.start_progress.lock().unwrap().take();
+        let started = match self.start_time.lock().unwrap().take() {
+            Some(started) => started,
+            None => return,
+        };
+
+        let duration = started.elapsed();
+        debug!(target: "lazymc", "Server took {}s to start", duration.as_secs());
+
+        {
+            let mut durations = self.start_durations.lock().unwrap();
+            durations.push_back(duration);
+            while durations.len() > START_HISTORY {
+                durations.pop_front();
+            }
+        }
+        persist::save(config, &self.persisted());
+    }
+
+    /// Mark the server ready as reported in its log or by its backend.
+    pub fn set_ready(&self, config: &Config) {
+        self.signal_ready(config, ReadySignal::Log, true);
+    }
+
+    /// Update whether a readiness signal is seen for the starting server.
+    ///
+    /// Returns `true` if the server came online because of it.
+    pub fn signal_ready(&self, config: &Config, signal: ReadySignal, seen: bool) -> bool {
+        {
+            let mut signals = self.ready_signals.lock().unwrap();
+            let had = signals.contains(&signal);
+            if seen && !had {
+                trace!(target: "lazymc", "Server readiness signal seen: {:?}", signal);
+                signals.push(signal);
+            } else if !seen && had {
+                signals.retain(|s| *s != signal);
+            }
+        }
+        self.check_ready(config)
+    }
+
+    /// Mark the starting server online once the configured readiness signals are seen.
+    ///
+    /// Only applies while booting, a server that was online before is back once its status is
+    /// reachable again. Returns `true` if the server came online.
+    pub fn check_ready(&self, config: &Config) -> bool {
+        let booting = self.start_time.lock().unwrap().is_some();
+        let ready = booting && config.monitor.ready(&self.ready_signals.lock().unwrap());
+        ready && self.set_online(config)
+    }
+
+    /// Mark the starting server online.
+    fn set_online(&self, config: &Config) -> bool {
+        if !self.transition_from(&[State::Starting], State::Online) {
+            return false;
+        }
+        self.came_online(config);
+        true
+    }
+
+    /// Update state for server that just came online.
+    fn came_online(&self, config: &Config) {
+        // TODO: move this somewhere else
+        info!(target: "lazymc::monitor", "Server is now online");
+        self.crashed.store(false, Ordering::Relaxed);
+        hooks::fire(config, Event::Online);
+        self.had_players.store(false, Ordering::Relaxed);
+        self.sleep_countdown.lock().unwrap().take();
+        self.ready_signals.lock().unwrap().clear();
+        self.update_last_active_time();
+        self.set_keep_online_until(Some(config.time.min_online_time));
+        self.finish_start(config);
+    }
+
     /// Update the server status, online state and last active time.
     // TODO: clean this up
     pub fn update_status(&self, config: &Config, status: Option<ServerStatus>) {
-        let stopping = self.stopping.load(Ordering::Relaxed);
-        let was_online = self.online();
-        let online = status.is_some() && !stopping;
-        self.set_online(online);
-
-        // If server just came online, update last active time
-        if !was_online && online {
-            // TODO: move this somewhere else
-            info!(target: "lazymc::monitor", "Server is now online");
+        // Transition into online state, or out of it, the stopping state is left once it quits.
+        // A server that was online before is back once reachable, a starting server must signal
+        // it is ready.
+        if status.is_some() {
+            if self.start_time.lock().unwrap().is_some() {
+                self.signal_ready(config, ReadySignal::Status, true);
+            } else {
+                self.set_online(config);
+            }
+            if self.transition_from(&[State::Sleeping], State::Unmanaged) {
+                self.came_online(config);
+            }
+        } else {
+            self.signal_ready(config, ReadySignal::Status, false);
+            self.transition_from(&[State::Online], State::Starting);
+            self.transition_from(&[State::Unmanaged], State::Sleeping);
+        }
+        let online = self.online();
+
+        // Consider server active by proxied traffic only if configured, ignoring players
+        let by_traffic = config.time.idle_throughput > 0;
+        let throughput = self.measure_throughput();
+        if by_traffic && online && throughput >= config.time.idle_throughput as u64 {
+            trace!(target: "lazymc", "Server active, clients sent {} bytes/s", throughput);
             self.update_last_active_time();
-            self.set_keep_online_until(Some(config.time.min_online_time));
         }
 
-        // // If server just went offline, reset stopping state
-        // // TODO: do this elsewhere
-        // if stopping && was_online && !online {
-        //     self.stopping.store(false, Ordering::Relaxed);
-        // }
+        // Update last active time if clients are proxied, the status may not report all players
+        if !by_traffic && online && self.proxied() > 0 {
+            self.update_last_active_time();
+        }
 
         if let Some(status) = status {
             // Update last active time if there are online players
             if status.players.online > 0 {
-                self.update_last_active_time();
+                if !by_traffic {
+                    self.update_last_active_time();
+                }
+                self.had_players.store(true, Ordering::Relaxed);
+            }
+
+            // Remember last players that were online
+            if !status.players.sample.is_empty() {
+                *self.last_players.lock().unwrap() = status.players.sample.clone();
             }
 
             // Update last known players
@@ -150,12 +829,59 @@ impl ServerState {
         }
     }
 
+    /// Advance the countdown before sleeping, once the server should sleep.
+    ///
+    /// Only counts down if players were online and sleep warnings are configured.
+    pub fn sleep_countdown(&self, config: &Config) -> SleepCountdown {
+        let warnings = &config.time.sleep_warnings;
+        let countdown = warnings.iter().max().copied().unwrap_or(0);
+        if countdown == 0 || !self.had_players.load(Ordering::Relaxed) {
+            return SleepCountdown::Sleep;
+        }
+
+        let mut state = self.sleep_countdown.lock().unwrap();
+        let (sleep_at, warned) = state.get_or_insert_with(|| {
+            let sleep_at = Instant::now() + Duration::from_secs(countdown as u64);
+            (sleep_at, u32::MAX)
+        });
+        let remaining = sleep_at.saturating_duration_since(Instant::now()).as_secs() as u32;
+        if remaining == 0 {
+            state.take();
+            return SleepCountdown::Sleep;
+        }
+
+        // Warn once for the last warning point passed
+        match warnings
+            .iter()
+            .copied()
+            .filter(|seconds| *seconds >= remaining && *seconds < *warned)
+            .min()
+        {
+            Some(seconds) => {
+                *warned = seconds;
+                SleepCountdown::Warn(seconds)
+            }
+            None => SleepCountdown::Wait,
+        }
+    }
+
+    /// Cancel the countdown before sleeping, returns `true` if it was counting down.
+    pub fn cancel_sleep_countdown(&self) -> bool {
+        self.sleep_countdown.lock().unwrap().take().is_some()
+    }
+
     /// Check whether the server should now sleep.
     pub fn should_sleep(&self, config: &Config) -> bool {
         // TODO: when initating server start, set last active time!
         // TODO: do not initiate sleep when starting?
         // TODO: do not initiate sleep when already initiated (with timeout)
 
+        // Don't sleep within keep awake schedule
+        if config.schedule.keep_awake() {
+            trace!(target: "lazymc", "Not sleeping because of keep awake schedule");
+            return false;
+        }
+
         // Don't sleep when keep online until isn't expired
         let keep_online = self
             .keep_online_until
@@ -173,7 +899,8 @@ impl ServerState {
             return false;
         }
 
-        // Never idle if players are online
+        // Never idle if players are online, unless only considering proxied traffic
+        let by_traffic = config.time.idle_throughput > 0;
         let players_online = self
             .status
             .lock()
@@ -181,7 +908,13 @@ impl ServerState {
             .as_ref()
             .map(|status| status.players.online > 0)
             .unwrap_or(false);
-        if players_online {
+        if players_online && !by_traffic {
+            return false;
+        }
+
+        // Never idle if clients are proxied
+        if self.proxied() > 0 && !by_traffic {
+            trace!(target: "lazymc", "Not sleeping because clients are proxied");
             return false;
         }
 
@@ -194,99 +927,665 @@ impl ServerState {
     }
 }
 
+/// Client connection proxied to the server, counted as activity until dropped.
+pub struct Proxied(Arc<ServerState>);
+
+impl Proxied {
+    /// Counter for the number of bytes the client sent to the server.
+    pub fn traffic(&self) -> &AtomicU64 {
+        &self.0.traffic
+    }
+}
+
+impl Drop for Proxied {
+    fn drop(&mut self) {
+        self.0.proxied.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Step of the countdown before sleeping.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SleepCountdown {
+    /// Sleep now.
+    Sleep,
+
+    /// Warn online players the server sleeps in number of seconds.
+    Warn(u32),
+
+    /// Keep counting down.
+    Wait,
+}
+
 /// Try to start the server.
 ///
 /// Does not start if alreayd starting.
 // TODO: move this into server state struct?
 pub fn start_server(config: Arc<Config>, server: Arc<ServerState>) {
+    // Resume frozen server instead of starting a new one
+    if server.frozen() {
+        if server.unfreeze_server() {
+            info!(target: "lazymc", "Unfreezing server...");
+            server.update_last_active_time();
+            server.wake_count.fetch_add(1, Ordering::Relaxed);
+            server.count_wake_today();
+        }
+        return;
+    }
+
     // Ensure it is not starting yet
-    if server.starting() {
+    if !server.transition_from(&[State::Sleeping], State::Starting) {
         return;
     }
 
     // Update starting states
-    // TODO: this may data race, use single atomic operation
-    server.set_starting(true);
+    server.ready_signals.lock().unwrap().clear();
     server.update_last_active_time();
+    server.start_time.lock().unwrap().replace(Instant::now());
+    server.start_progress.lock().unwrap().take();
+    server.wake_count.fetch_add(1, Ordering::Relaxed);
+    server.count_wake_today();
 
     // Spawn server in separate task
     tokio::spawn(invoke_server_command(config, server).map(|_| ()));
 }
 
-/// Invoke server command, store PID and wait for it to quit.
+/// Check whether the host has enough memory available to start the server.
+///
+/// Returns the available and required memory in bytes if not. Always passes if the memory guard
+/// is disabled, or if the required or available memory is unknown.
+pub fn check_memory(config: &Config) -> Result<(), (u64, u64)> {
+    if !config.server.memory_guard {
+        return Ok(());
+    }
+
+    let required = config.server.required_memory();
+    let available = crate::os::available_memory();
+    match (available, required) {
+        (Some(available), Some(required)) if available < required => Err((available, required)),
+        _ => Ok(()),
+    }
+}
+
+/// Probe server status by briefly starting the server.
+///
+/// Waits for the server to come online so the monitor captures its status, then puts it to sleep
+/// again unless players joined in the meantime.
+pub async fn probe(config: Arc<Config>, server: Arc<ServerState>) {
+    info!(target: "lazymc", "Starting server to probe its status...");
+    start_server(config.clone(), server.clone());
+
+    // Wait for server to come online, give up if it stopped
+    let mut state = server.subscribe();
+    while !server.online() {
+        if !server.starting() {
+            warn!(target: "lazymc", "Server stopped before it could be probed");
+            return;
+        }
+        if state.changed().await.is_err() {
+            return;
+        }
+    }
+
+    // Keep server running if players joined
+    let players_online = server
+        .clone_status()
+        .map(|status| status.players.online > 0)
+        .unwrap_or(false);
+    if players_online {
+        info!(target: "lazymc", "Probed server status, keeping server online for players");
+        return;
+    }
+
+    info!(target: "lazymc", "Probed server status, sleeping...");
+    if !server.sleep_server(&config).await {
+        warn!(target: "lazymc", "Failed to stop server after probing");
+    }
+}
+
+/// Start server through the configured backend and wait for it to quit.
 pub async fn invoke_server_command(
     config: Arc<Config>,
     state: Arc<ServerState>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // TODO: this doesn't properly handle quotes
-    let args = config
-        .server
-        .command
-        .split_terminator(" ")
-        .collect::<Vec<_>>();
+    // Host must have enough memory to fit the server
+    if let Err((available, required)) = check_memory(&config) {
+        let (available, required) = (available / 1024 / 1024, required / 1024 / 1024);
+        error!(
+            target: "lazymc",
+            "Not starting server, {} MiB memory available but {} MiB required", available, required,
+        );
+        state.start_time.lock().unwrap().take();
+        state.transition(State::Sleeping);
+        return Ok(());
+    }
+
+    // Run pre-start hook, don't start server if it fails
+    if let Some(hook) = &config.server.pre_start_command {
+        if !hooks::run_command(&config, "pre-start", hook).await {
+            error!(target: "lazymc", "Pre-start command failed, not starting server");
+            state.start_time.lock().unwrap().take();
+            state.transition(State::Sleeping);
+            return Ok(());
+        }
+    }
+
+    // Run server until it quits
+    let (success, status) = match config.server.backend {
+        Backend::Process => {
+            let status = run_process(&config, &state).await?;
+            (status.success(), Some(status))
+        }
+        Backend::Systemd => (backend::systemd::run(&config, &state).await, None),
+        Backend::Ssh => (backend::ssh::run(&config, &state).await, None),
+        Backend::Wol => (backend::wol::run(&config, &state).await, None),
+        Backend::Pterodactyl => (backend::pterodactyl::run(&config, &state).await, None),
+        #[cfg(feature = "kubernetes")]
+        Backend::Kubernetes => (backend::kubernetes::run(&config, &state).await, None),
+    };
+
+    server_stopped(config, state, success, status).await;
+    Ok(())
+}
+
+/// Update state for server that stopped, handling a crash.
+async fn server_stopped(
+    config: Arc<Config>,
+    state: Arc<ServerState>,
+    success: bool,
+    status: Option<ExitStatus>,
+) {
+    // Back up world after sleeping, before the server may start again
+    let stopping = state.stopping();
+    if config.backups.enabled && stopping {
+        backup::backup(&config).await;
+    }
+
+    // Run post-stop hook before the server may start again
+    if let Some(hook) = &config.server.post_stop_command {
+        if !hooks::run_command(&config, "post-stop", hook).await {
+            warn!(target: "lazymc", "Post-stop command failed");
+        }
+    }
+
+    // Server crashed if it quit unexpectedly with an error, or before it came online
+    let never_online = state.start_time.lock().unwrap().take().is_some();
+    let crashed = !stopping && (!success || never_online);
+
+    // Drop pooled RCON connection to the stopped server
+    #[cfg(feature = "rcon")]
+    crate::mc::rcon::Rcon::clear_pool();
+
+    // Reset server state
+    state.set_pid(None);
+    state.stdin.lock().unwrap().take();
+    state.kill_deadline.lock().unwrap().take();
+    state.transition(State::Sleeping);
+    state.start_progress.lock().unwrap().take();
+    state.stop_time.lock().unwrap().replace(Instant::now());
+    persist::save(&config, &state.persisted());
+
+    // Don't restart if server quit because the EULA isn't accepted, it would quit again
+    let eula_rejected = crashed
+        && config.server.backend == Backend::Process
+        && !eula::accepted(config.server.dir());
+    if eula_rejected {
+        error!(target: "lazymc", "Server quit because the Minecraft EULA is not accepted");
+        info!(
+            target: "lazymc",
+            "Set 'eula=true' in {} to accept it, or enable 'server.accept_eula' in the config",
+            eula::FILE,
+        );
+        state.crashes.fetch_add(1, Ordering::Relaxed);
+    } else if crashed {
+        hooks::fire(&config, Event::Crash { status });
+        handle_crash(config, state);
+    } else {
+        state.crashes.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Adopt server that is already running, instead of reporting it offline.
+///
+/// Attaches to the server process if it can be found, so it can be stopped like a server lazymc
+/// started. Otherwise the server can only be stopped through RCON. Returns `false` if no server is
+/// running.
+pub async fn adopt(config: Arc<Config>, server: Arc<ServerState>) -> bool {
+    let status = match monitor::poll_server(&config).await {
+        Some(status) => status,
+        None => return false,
+    };
+
+    let pid = crate::os::find_listener(config.server.address.port());
+    match pid {
+        Some(pid) => info!(target: "lazymc", "Adopting running server (PID: {})", pid),
+        None => info!(target: "lazymc", "Adopting running server, process not found"),
+    }
+
+    if !server.transition_from(&[State::Sleeping, State::Unmanaged], State::Starting) {
+        return false;
+    }
+    server.set_pid(pid);
+    server.update_status(&config, Some(status.server_status));
+
+    tokio::spawn(async move {
+        watch_adopted(&config, &server, pid).await;
+        server_stopped(config, server, true, None).await;
+    });
+    true
+}
+
+/// Wait for adopted server to quit.
+///
+/// Waits for its process to exit if known, or for the server to become unreachable otherwise.
+async fn watch_adopted(config: &Config, server: &ServerState, pid: Option<u32>) {
+    loop {
+        tokio::time::sleep(KILL_CHECK_INTERVAL).await;
+
+        let running = match pid {
+            Some(pid) => crate::os::is_running(pid),
+            None => TcpStream::connect(config.server.address).await.is_ok(),
+        };
+        if !running {
+            break;
+        }
+
+        // Kill server if it doesn't quit in time after stopping
+        let deadline = *server.kill_deadline.lock().unwrap();
+        if let Some(pid) = pid.filter(|_| deadline.is_some_and(|d| d <= Instant::now())) {
+            let timeout = config.server.stop_timeout;
+            warn!(target: "lazymc", "Server did not quit within {}s after stopping, killing it", timeout);
+            server.kill_deadline.lock().unwrap().take();
+            crate::os::kill(pid);
+        }
+    }
+    info!(target: "lazymc", "Adopted server stopped\n");
+}
+
+/// Run server command as child process, store PID and wait for it to quit.
+async fn run_process(
+    config: &Arc<Config>,
+    state: &Arc<ServerState>,
+) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    // Accept EULA if configured, the server quits right away if not accepted
+    let dir = config.server.dir();
+    if config.server.accept_eula && !eula::accepted(dir) {
+        info!(target: "lazymc", "Accepting Minecraft EULA in {}", eula::FILE);
+        if let Err(err) = eula::accept(dir) {
+            error!(target: "lazymc", "Failed to accept Minecraft EULA: {}", err);
+        }
+    }
 
     // Build command
-    let mut cmd = Command::new(args[0]);
-    cmd.args(args.iter().skip(1));
+    let command = config
+        .server
+        .command
+        .as_ref()
+        .ok_or("server command not configured")?;
+    let mut cmd = Command::new(command.program());
+    cmd.args(command.args());
     if let Some(ref dir) = config.server.directory {
         cmd.current_dir(dir);
     }
+    cmd.stdout(Stdio::piped());
     cmd.kill_on_drop(true);
 
+    // Listeners passed by a service manager are ours, don't pass them on to the server
+    for key in crate::os::LISTEN_ENV {
+        cmd.env_remove(key);
+    }
+
+    // Pipe console input to forward our console, and to stop the server through it
+    cmd.stdin(Stdio::piped());
+
     info!(target: "lazymc", "Starting server...");
     let mut child = cmd.spawn()?;
 
     state.set_pid(Some(child.id().expect("unknown server PID")));
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(watch_output(stdout, config.clone(), state.clone()));
+    }
+    *state.stdin.lock().unwrap() = child.stdin.take();
 
-    let status = child.wait().await?;
+    // Wait for server to quit, kill it if it doesn't quit in time after stopping
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status?,
+            _ = tokio::time::sleep(KILL_CHECK_INTERVAL) => {}
+        }
+
+        let deadline = *state.kill_deadline.lock().unwrap();
+        if deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+            let timeout = config.server.stop_timeout;
+            warn!(target: "lazymc", "Server did not quit within {}s after stopping, killing it", timeout);
+            state.kill_deadline.lock().unwrap().take();
+            child.start_kill()?;
+        }
+
+        // Kill server if it doesn't come online in time
+        if state.check_start_timeout(config) {
+            child.start_kill()?;
+        }
+    };
     info!(target: "lazymc", "Server stopped (status: {})\n", status);
 
-    // Reset online and starting state
-    // TODO: also set this when returning early due to error
-    state.set_pid(None);
-    state.set_online(false);
-    state.set_starting(false);
-    state.stopping.store(false, Ordering::Relaxed);
+    Ok(status)
+}
 
-    Ok(())
+/// Handle server crash, restart it with exponential backoff if enabled.
+fn handle_crash(config: Arc<Config>, state: Arc<ServerState>) {
+    error!(target: "lazymc", "Server crashed");
+    let crashes = state.crashes.fetch_add(1, Ordering::Relaxed) + 1;
+    if !config.server.restart_on_crash {
+        return;
+    }
+
+    // Give up after too many consecutive crashes
+    if config.server.restart_max > 0 && crashes > config.server.restart_max {
+        error!(target: "lazymc", "Server crashed {} times in a row, not restarting", crashes);
+        state.crashed.store(false, Ordering::Relaxed);
+        return;
+    }
+
+    let delay = Duration::from_secs(config.server.restart_delay as u64)
+        .checked_mul(1 << (crashes - 1).min(16))
+        .unwrap_or(MAX_RESTART_DELAY)
+        .min(MAX_RESTART_DELAY);
+    info!(target: "lazymc", "Restarting server in {}s...", delay.as_secs());
+    state.crashed.store(true, Ordering::Relaxed);
+
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        if state.crashed() && !state.starting() {
+            start_server(config, state);
+        }
+    });
 }
 
-/// Stop server through RCON.
+/// Forward server output to our stdout, tracking start progress and readiness reported in it.
+async fn watch_output<R: AsyncRead + Unpin>(
+    output: R,
+    config: Arc<Config>,
+    state: Arc<ServerState>,
+) {
+    let mut output = BufReader::new(output);
+    let mut stdout = tokio::io::stdout();
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        match output.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        if stdout.write_all(&line).await.is_err() {
+            break;
+        }
+        let _ = stdout.flush().await;
+
+        if state.starting() && !state.online() {
+            let line = String::from_utf8_lossy(&line);
+            if let Some(progress) = parse_progress(&line) {
+                trace!(target: "lazymc", "Server start progress: {}%", progress);
+                state.start_progress.lock().unwrap().replace(progress);
+            }
+            if is_ready(&line) {
+                state.set_ready(&config);
+            }
+        }
+    }
+}
+
+/// Whether a server log line reports the server is done starting.
+fn is_ready(line: &str) -> bool {
+    let (start, end) = READY_MARKERS;
+    line.split_once(start)
+        .is_some_and(|(_, rest)| rest.contains(end))
+}
+
+/// Parse start progress percentage from a server log line.
+///
+/// Matches lines such as `[Server thread/INFO]: Preparing spawn area: 47%`.
+fn parse_progress(line: &str) -> Option<u8> {
+    let (_, progress) = line.split_once(PROGRESS_MARKER)?;
+    let (_, percent) = progress.rsplit_once(": ")?;
+    let percent = percent
+        .trim_end()
+        .strip_suffix('%')?
+        .trim()
+        .parse::<u8>()
+        .ok()?;
+    Some(percent.min(100))
+}
+
+/// Restart the running server.
+///
+/// Stops the server, waits for it to quit and starts it again.
+pub async fn restart(config: Arc<Config>, server: Arc<ServerState>) {
+    info!(target: "lazymc", "Restarting server...");
+    if !server.kill_server(&config).await {
+        warn!(target: "lazymc", "Failed to stop server to restart it");
+        return;
+    }
+
+    // Wait for server to quit
+    while server.starting() {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    start_server(config, server);
+}
+
+/// Broadcast message to online players through RCON.
+#[allow(unused_variables)]
+pub async fn broadcast(config: &Config, message: &str) -> bool {
+    #[cfg(feature = "rcon")]
+    if let Ok(json) = crate::chat::message(message).to_json() {
+        return rcon_command(config, &format!("tellraw @a {}", json)).await;
+    }
+
+    false
+}
+
+/// Save server world through RCON.
+#[cfg(feature = "rcon")]
+async fn save_server_rcon(config: &Config) -> bool {
+    rcon_command(config, "save-all flush").await
+}
+
+/// Invoke a single command through RCON.
+#[cfg(feature = "rcon")]
+pub async fn rcon_command(config: &Config, cmd: &str) -> bool {
+    rcon_query(config, cmd).await.is_ok()
+}
+
+/// Invoke commands through RCON in order, `wait <seconds>` waits in between.
+///
+/// Failing commands are logged and skipped.
+#[cfg(feature = "rcon")]
+pub async fn rcon_commands(config: &Config, cmds: &[String]) {
+    for cmd in cmds {
+        if rcon_wait(cmd).await {
+            continue;
+        }
+
+        if !rcon_command(config, cmd).await {
+            warn!(target: "lazymc", "Skipping RCON command that failed: {}", cmd);
+        }
+    }
+}
+
+/// Wait if the RCON command is `wait <seconds>`, returns whether it was.
+///
+/// Invalid waits are logged and skipped.
+#[cfg(feature = "rcon")]
+async fn rcon_wait(cmd: &str) -> bool {
+    let delay = match cmd.strip_prefix("wait ") {
+        Some(delay) => delay,
+        None => return false,
+    };
+
+    match delay.trim().parse() {
+        Ok(delay) => tokio::time::sleep(Duration::from_secs(delay)).await,
+        Err(_) => error!(target: "lazymc", "invalid RCON wait, ignoring: {}", cmd),
+    }
+    true
+}
+
+/// Invoke a single command through RCON, returning its response.
 #[cfg(feature = "rcon")]
-async fn stop_server_rcon(config: &Config, server: &ServerState) -> bool {
+pub async fn rcon_query(config: &Config, cmd: &str) -> Result<String, ()> {
     use crate::mc::rcon::Rcon;
 
     // RCON must be enabled
     if !config.rcon.enabled {
-        return false;
+        return Err(());
+    }
+
+    let (mut rcon, pooled) = match Rcon::take_pooled() {
+        Some(rcon) => (rcon, true),
+        None => (rcon_connect(config).await?, false),
+    };
+
+    // Reconnect once if the pooled connection went stale
+    let mut result = rcon.cmd(cmd).await;
+    if let (Err(err), true) = (&result, pooled) {
+        debug!(target: "lazymc", "pooled RCON connection failed, reconnecting: {}", err);
+        rcon = rcon_connect(config).await?;
+        result = rcon.cmd(cmd).await;
+    }
+
+    match result {
+        Ok(response) => {
+            rcon.release();
+            Ok(response)
+        }
+        Err(err) => {
+            error!(target: "lazymc", "failed to invoke command through RCON: {}", err);
+            Err(())
+        }
     }
+}
 
-    // RCON address
-    let mut addr = config.server.address.clone();
+/// Connect to server RCON, retrying a few times if it failed.
+#[cfg(feature = "rcon")]
+async fn rcon_connect(config: &Config) -> Result<crate::mc::rcon::Rcon, ()> {
+    use crate::mc::rcon::Rcon;
+
+    let mut addr = config.server.address;
     addr.set_port(config.rcon.port);
     let addr = addr.to_string();
+    let connect_timeout = Duration::from_secs(config.rcon.connect_timeout as u64);
+    let timeout = Duration::from_secs(config.rcon.command_timeout as u64);
 
-    // Create RCON client
-    let mut rcon = match Rcon::connect(&addr, &config.rcon.password).await {
-        Ok(rcon) => rcon,
-        Err(_) => {
-            error!(target: "lazymc", "failed to create RCON client to sleep server");
-            return false;
+    let mut attempt = 0;
+    loop {
+        let err = match Rcon::connect(&addr, &config.rcon.password, connect_timeout, timeout).await
+        {
+            Ok(rcon) => return Ok(rcon),
+            Err(err) => err,
+        };
+
+        // Don't retry if the password is wrong
+        if attempt >= config.rcon.connect_retries || err.is_auth() {
+            error!(target: "lazymc", "failed to create RCON client: {}", err);
+            return Err(());
         }
+        attempt += 1;
+        debug!(target: "lazymc", "failed to connect to RCON, retrying: {}", err);
+        tokio::time::sleep(RCON_RETRY_DELAY * attempt).await;
+    }
+}
+
+/// Stop server through RCON.
+#[cfg(feature = "rcon")]
+pub async fn stop_server_rcon(config: &Config, server: &ServerState) -> bool {
+    use crate::mc::rcon::Rcon;
+
+    // RCON must be enabled
+    if !config.rcon.enabled {
+        return false;
+    }
+
+    // Create RCON client, the connection isn't reused as the server stops
+    let (mut rcon, pooled) = match Rcon::take_pooled() {
+        Some(rcon) => (rcon, true),
+        None => match rcon_connect(config).await {
+            Ok(rcon) => (rcon, false),
+            Err(_) => return false,
+        },
     };
 
-    // Invoke save-all
-    if let Err(err) = rcon.cmd("save-all").await {
-        error!(target: "lazymc", "failed to invoke save-all through RCON, ignoring: {}", err);
+    // Reconnect once if the pooled connection went stale
+    let mut players = rcon.players().await;
+    if players.is_err() && pooled {
+        rcon = match rcon_connect(config).await {
+            Ok(rcon) => rcon,
+            Err(_) => return false,
+        };
+        players = rcon.players().await;
+    }
+
+    // Kick remaining players with a reason
+    match players {
+        Ok(players) => {
+            let reason = &config.messages.kick_stopping;
+            for player in players {
+                if let Err(err) = rcon.cmd(&format!("kick {} {}", player, reason)).await {
+                    error!(target: "lazymc", "failed to kick {} through RCON: {}", player, err);
+                }
+            }
+        }
+        Err(err) => {
+            error!(target: "lazymc", "failed to list players through RCON, ignoring: {}", err)
+        }
+    }
+
+    // Invoke stop commands in order, ignoring failures
+    for cmd in &config.rcon.stop_commands {
+        if rcon_wait(cmd).await {
+            continue;
+        }
+
+        if let Err(err) = rcon.cmd(cmd).await {
+            error!(target: "lazymc", "failed to invoke {} through RCON: {}", cmd, err);
+        }
+    }
+
+    server.set_keep_online_until(None);
+
+    true
+}
+
+/// Stop server by sending the stop command to its console.
+///
+/// Only works if the server console input is piped.
+async fn stop_server_stdin(server: &ServerState) -> bool {
+    debug!(target: "lazymc", "Sending stop command to server console");
+    if !server.write_console(b"stop\n").await {
+        error!(target: "lazymc", "failed to send stop command to server console");
+        return false;
     }
 
-    // Invoke stop
-    if let Err(err) = rcon.cmd("stop").await {
-        error!(target: "lazymc", "failed to invoke stop through RCON: {}", err);
+    server.set_keep_online_until(None);
+
+    true
+}
+
+/// Stop server by forcefully killing its process.
+///
+/// Only works on Unix.
+fn stop_server_kill(server: &ServerState) -> bool {
+    let pid = match server.pid() {
+        Some(pid) => pid,
+        None => return false,
+    };
+
+    debug!(target: "lazymc", "Forcefully killing server");
+    if !crate::os::kill(pid) {
+        return false;
     }
 
-    // TODO: should we set this?
-    server.set_online(false);
     server.set_keep_online_until(None);
 
     true
@@ -301,8 +1600,6 @@ fn stop_server_signal(server: &ServerState) -> bool {
         debug!(target: "lazymc", "Sending kill signal to server");
         crate::os::kill_gracefully(pid);
 
-        // TODO: should we set this?
-        server.set_online(false);
         server.set_keep_online_until(None);
 
         return true;
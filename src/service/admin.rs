@@ -0,0 +1,10 @@
+use std::sync::Arc;
+
+use crate::admin;
+use crate::config::Config;
+use crate::server::ServerState;
+
+/// Admin control server task.
+pub async fn service(config: Arc<Config>, state: Arc<ServerState>) {
+    let _ = admin::serve(config, state).await;
+}
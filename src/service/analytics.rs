@@ -0,0 +1,10 @@
+use std::sync::Arc;
+
+use crate::analytics;
+use crate::config::Config;
+use crate::server::ServerState;
+
+/// Ping analytics report task.
+pub async fn service(config: Arc<Config>, state: Arc<ServerState>) {
+    analytics::report(config, state).await
+}
@@ -0,0 +1,27 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::backup;
+use crate::config::Config;
+use crate::server::ServerState;
+
+/// Live backups task.
+///
+/// Periodically backs up worlds while the server is online, coordinating saving through RCON.
+pub async fn service(config: Arc<Config>, state: Arc<ServerState>) {
+    // RCON is required to coordinate saving
+    if !config.rcon.enabled {
+        warn!(target: "lazymc", "Live backups require RCON, not backing up while online");
+        return;
+    }
+
+    let interval = Duration::from_secs(config.backups.live_interval as u64);
+    loop {
+        tokio::time::sleep(interval).await;
+
+        // Only back up running server
+        if state.online() && !state.frozen() {
+            backup::backup_live(&config).await;
+        }
+    }
+}
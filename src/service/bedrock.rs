@@ -0,0 +1,10 @@
+use std::sync::Arc;
+
+use crate::bedrock;
+use crate::config::Config;
+use crate::server::ServerState;
+
+/// Bedrock ping server task.
+pub async fn service(config: Arc<Config>, state: Arc<ServerState>) {
+    bedrock::serve(config, state).await
+}
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::server::ServerState;
+
+/// Console passthrough task.
+///
+/// Forwards lines typed in our console to the server console while it is running.
+pub async fn service(state: Arc<ServerState>) {
+    let mut stdin = BufReader::new(tokio::io::stdin());
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        match stdin.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        if state.frozen() {
+            warn!(target: "lazymc", "Server is frozen, join it to wake it before sending commands");
+        } else if !state.write_console(&line).await {
+            warn!(target: "lazymc", "Server is sleeping, join it to wake it before sending commands");
+        }
+    }
+
+    debug!(target: "lazymc", "Console closed, stopped forwarding it to the server");
+}
@@ -1,3 +1,15 @@
+pub mod admin;
+pub mod analytics;
+#[cfg(feature = "rcon")]
+pub mod backup;
+pub mod bedrock;
+pub mod console;
 pub mod monitor;
+pub mod query;
+#[cfg(feature = "rcon")]
+pub mod rcon;
+pub mod reaper;
+pub mod resources;
+pub mod schedule;
 pub mod server;
 pub mod signal;
@@ -0,0 +1,10 @@
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::query;
+use crate::server::ServerState;
+
+/// Query server task.
+pub async fn service(config: Arc<Config>, state: Arc<ServerState>) {
+    let _ = query::serve(config, state).await;
+}
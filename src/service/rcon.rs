@@ -0,0 +1,22 @@
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::rcon;
+use crate::server::ServerState;
+
+/// Public RCON server task.
+pub async fn service(config: Arc<Config>, state: Arc<ServerState>) {
+    // RCON is required to forward commands
+    if !config.rcon.enabled {
+        warn!(target: "lazymc", "Public RCON requires RCON to be enabled, not serving RCON");
+        return;
+    }
+
+    // Never serve RCON without a password
+    if config.rcon.public.password.is_empty() {
+        warn!(target: "lazymc", "Public RCON password is not set, not serving RCON");
+        return;
+    }
+
+    let _ = rcon::serve(config, state).await;
+}
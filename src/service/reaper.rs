@@ -0,0 +1,9 @@
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::session::{self, Sessions};
+
+/// Idle client session reaper task.
+pub async fn service(config: Arc<Config>, sessions: Arc<Sessions>) {
+    session::reap_idle(config, sessions).await
+}
@@ -0,0 +1,10 @@
+use std::sync::Arc;
+
+use crate::analytics;
+use crate::config::Config;
+use crate::server::ServerState;
+
+/// Server resource sampling task.
+pub async fn service(config: Arc<Config>, state: Arc<ServerState>) {
+    analytics::sample_resources(config, state).await
+}
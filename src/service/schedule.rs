@@ -0,0 +1,63 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::config::Config;
+use crate::messages;
+use crate::server::{self, ServerState};
+
+/// Scheduled restarts task.
+///
+/// Restarts the server at the configured schedule if it is running, warning online players
+/// beforehand.
+pub async fn service(config: Arc<Config>, state: Arc<ServerState>) {
+    let mut warnings = config.schedule.restart_warnings.clone();
+    warnings.sort_unstable_by(|a, b| b.cmp(a));
+    warnings.dedup();
+
+    loop {
+        let next = match next_restart(&config) {
+            Some(next) => next,
+            None => return,
+        };
+        debug!(target: "lazymc", "Next scheduled restart at {}", next);
+
+        // Warn online players before restarting
+        for seconds in &warnings {
+            let at = next - chrono::Duration::seconds(*seconds as i64);
+            if at <= Local::now() {
+                continue;
+            }
+            sleep_until(at).await;
+
+            if state.online() {
+                let time = messages::format_duration(Duration::from_secs(*seconds as u64));
+                let message = config.schedule.message_restart.replace("{time}", &time);
+                server::broadcast(&config, &message).await;
+            }
+        }
+        sleep_until(next).await;
+
+        // Only restart running server
+        if state.online() && !state.frozen() {
+            server::restart(config.clone(), state.clone()).await;
+        }
+    }
+}
+
+/// Get time of the next scheduled restart.
+fn next_restart(config: &Config) -> Option<DateTime<Local>> {
+    config
+        .schedule
+        .restart
+        .iter()
+        .filter_map(|schedule| schedule.0.upcoming(Local).next())
+        .min()
+}
+
+/// Sleep until the given time.
+async fn sleep_until(time: DateTime<Local>) {
+    let duration = (time - Local::now()).to_std().unwrap_or_default();
+    tokio::time::sleep(duration).await;
+}
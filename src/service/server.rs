@@ -1,38 +1,79 @@
+use std::future;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
 use futures::FutureExt;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 
-use crate::config::Config;
-use crate::proto::Client;
+use crate::config::{Backend, Config};
+use crate::favicon;
+use crate::intercept;
+use crate::persist;
+use crate::proto::{Client, ClientState};
 use crate::proxy;
+use crate::proxy_protocol;
 use crate::server;
 use crate::server::ServerState;
 use crate::service;
+use crate::session::Sessions;
 use crate::status;
 use crate::util::error::{quit_error, ErrorHints};
+use crate::util::net;
 
 /// Start lazymc.
 pub async fn service(config: Arc<Config>) -> Result<(), ()> {
-    // Load server state
+    // Load custom favicons and JSON status
+    favicon::load(&config);
+    status::load(&config);
+
+    // Load server state, restore state persisted before restarting
     let server_state = Arc::new(ServerState::default());
+    if let Some(state) = persist::load(&config) {
+        server_state.restore(state);
+    }
 
-    // Listen for new connections
+    // Listen for new connections on sockets passed by a service manager, or on all public addresses
     // TODO: do not drop error here
-    let listener = TcpListener::bind(config.public.address)
-        .await
-        .map_err(|err| {
-            quit_error(
-                anyhow!(err).context("Failed to start proxy server"),
-                ErrorHints::default(),
+    let mut listeners = net::inherited().map_err(|err| {
+        quit_error(
+            anyhow!(err).context("Failed to use sockets passed by service manager"),
+            ErrorHints::default(),
+        );
+    })?;
+    if !listeners.is_empty() {
+        for listener in &listeners {
+            if let Ok(addr) = listener.local_addr() {
+                info!(
+                    target: "lazymc",
+                    "Proxying public {} to server {}, passed by service manager",
+                    addr, config.server.address,
+                );
+            }
+        }
+    } else {
+        let addresses = config.public.address.all();
+        for addr in addresses {
+            let listener =
+                net::bind(&config.socket, *addr, addresses.len() > 1).map_err(|err| {
+                    quit_error(
+                        anyhow!(err).context(format!("Failed to start proxy server on {}", addr)),
+                        ErrorHints::default(),
+                    );
+                })?;
+            listeners.push(listener);
+
+            info!(
+                target: "lazymc",
+                "Proxying public {} to server {}",
+                addr, config.server.address,
             );
-        })?;
+        }
+    }
 
-    info!(
-        target: "lazymc",
-        "Proxying public {} to server {}",
-        config.public.address, config.server.address,
-    );
+    // Track client sessions, to close idle ones and drain them on shutdown
+    let sessions = Arc::new(Sessions::default());
 
     // Spawn server monitor and signal handler services
     tokio::spawn(service::monitor::service(
@@ -42,38 +83,287 @@ pub async fn service(config: Arc<Config>) -> Result<(), ()> {
     tokio::spawn(service::signal::service(
         config.clone(),
         server_state.clone(),
+        sessions.clone(),
     ));
+    if config.analytics.enabled && config.analytics.summary_interval > 0 {
+        tokio::spawn(service::analytics::service(
+            config.clone(),
+            server_state.clone(),
+        ));
+    }
+    if config.analytics.enabled && config.analytics.resource_interval > 0 {
+        tokio::spawn(service::resources::service(
+            config.clone(),
+            server_state.clone(),
+        ));
+    }
+    if std::io::stdin().is_terminal() {
+        tokio::spawn(service::console::service(server_state.clone()));
+    }
+    if config.bedrock.enabled {
+        tokio::spawn(service::bedrock::service(
+            config.clone(),
+            server_state.clone(),
+        ));
+    }
+    if !config.schedule.restart.is_empty() {
+        tokio::spawn(service::schedule::service(
+            config.clone(),
+            server_state.clone(),
+        ));
+    }
+    #[cfg(feature = "rcon")]
+    if config.backups.enabled && config.backups.live_interval > 0 {
+        tokio::spawn(service::backup::service(
+            config.clone(),
+            server_state.clone(),
+        ));
+    }
+    #[cfg(feature = "rcon")]
+    if config.rcon.public.enabled {
+        tokio::spawn(service::rcon::service(config.clone(), server_state.clone()));
+    }
+    if config.admin.enabled {
+        tokio::spawn(service::admin::service(
+            config.clone(),
+            server_state.clone(),
+        ));
+    }
+    if config.query.enabled {
+        tokio::spawn(service::query::service(
+            config.clone(),
+            server_state.clone(),
+        ));
+    }
+
+    // Close idle client sessions if enabled
+    if config.limits.timeout_idle > 0 {
+        if cfg!(target_os = "linux") {
+            tokio::spawn(service::reaper::service(config.clone(), sessions.clone()));
+        } else {
+            warn!(target: "lazymc", "Closing idle connections is not supported on this platform, ignoring limits.timeout_idle");
+        }
+    }
+
+    // Adopt server if already running, otherwise initiate server start
+    let adopted = config.server.adopt
+        && config.server.backend == Backend::Process
+        && server::adopt(config.clone(), server_state.clone()).await;
+    if !adopted {
+        if config.server.wake_on_start {
+            server::start_server(config.clone(), server_state.clone());
+        } else if config.server.probe_on_start {
+            tokio::spawn(server::probe(config.clone(), server_state.clone()));
+        }
+    }
+
+    let clients = Arc::new(Clients {
+        config: config.clone(),
+        server: server_state,
+        sessions: sessions.clone(),
+        connections: Arc::new(AtomicU32::new(0)),
+        busy_connections: Arc::new(AtomicU32::new(0)),
+    });
+
+    // Proxy all incomming connections, until all listeners fail or we start draining
+    let accept_loops: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| tokio::spawn(clients.clone().listen(listener)))
+        .collect();
+    for accept_loop in accept_loops {
+        let _ = accept_loop.await;
+    }
+
+    // While draining, keep running open sessions, the signal handler quits when done
+    if *sessions.draining().borrow() {
+        future::pending::<()>().await;
+    }
+
+    Ok(())
+}
+
+/// State shared by all client connections.
+struct Clients {
+    config: Arc<Config>,
+    server: Arc<ServerState>,
 
-    // Initiate server start
-    if config.server.wake_on_start {
-        server::start_server(config.clone(), server_state.clone());
+    /// Open client sessions, to limit them per IP, close idle ones and drain them on shutdown.
+    sessions: Arc<Sessions>,
+
+    /// Count of open connections, for connection limit.
+    connections: Arc<AtomicU32>,
+
+    /// Count of connections served with busy message, for connection limit.
+    busy_connections: Arc<AtomicU32>,
+}
+
+impl Clients {
+    /// Accept client connections on the given listener, until accepting fails or we start draining.
+    async fn listen(self: Arc<Self>, listener: TcpListener) {
+        let config = &self.config;
+        let mut draining = self.sessions.draining();
+        loop {
+            if *draining.borrow() {
+                debug!(target: "lazymc", "Draining, no longer accepting connections");
+                break;
+            }
+
+            let (mut inbound, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(_) => break,
+                },
+                _ = draining.changed() => continue,
+            };
+            let local = inbound.local_addr().ok();
+            if let Err(err) = net::configure(&config.socket, &inbound) {
+                debug!(target: "lazymc", "Failed to set socket options for {}: {}", peer, err);
+            }
+
+            // Read PROXY protocol header from trusted proxies first, to know the real client address
+            if net::in_ranges(&config.public.trusted_proxies, peer.ip()) {
+                let clients = self.clone();
+                let timeout = config.limits.timeout(ClientState::Handshake);
+                tokio::spawn(async move {
+                    let header =
+                        tokio::time::timeout(timeout, proxy_protocol::read_header(&mut inbound))
+                            .await;
+                    match header {
+                        Ok(Ok(Some((source, destination)))) => {
+                            clients.accept(inbound, source, Some(destination))
+                        }
+                        Ok(Ok(None)) => clients.accept(inbound, peer, local),
+                        _ => {
+                            debug!(target: "lazymc", "Dropping connection from proxy {}, no valid PROXY protocol header", peer);
+                        }
+                    }
+                });
+                continue;
+            }
+
+            self.accept(inbound, peer, local);
+        }
     }
 
-    // Proxy all incomming connections
-    while let Ok((inbound, _)) = listener.accept().await {
-        let client = Client::default();
+    /// Serve a new client connection from the given peer, to the given local address.
+    fn accept(&self, inbound: TcpStream, peer: SocketAddr, local: Option<SocketAddr>) {
+        let config = &self.config;
+        let server_state = &self.server;
+
+        // Drop connections from IPs that are not allowed
+        if !net::allowed(&config.public.allow, &config.public.deny, peer.ip()) {
+            debug!(target: "lazymc", "Dropping connection from {}, IP not allowed", peer.ip());
+            return;
+        }
+
+        let mut client = Client::new(peer);
+        client.local = local;
+        client.max_packet_length = config.limits.max_packet_length();
+
+        // Drop connections from IPs over the per-IP connection limit
+        let max_per_ip = config.limits.max_connections_per_ip;
+        let session = match self
+            .sessions
+            .open(&inbound, peer, client.waiting.clone(), max_per_ip)
+        {
+            Some(session) => session,
+            None => {
+                debug!(target: "lazymc", "Connection limit per IP reached, dropping {}", peer);
+                return;
+            }
+        };
+
+        // Serve busy message or drop connection if over connection limit
+        let max = config.limits.max_connections;
+        let connection = match Connection::acquire(&self.connections, max) {
+            Some(connection) => connection,
+            None => {
+                match Connection::acquire(&self.busy_connections, max) {
+                    Some(connection) => {
+                        debug!(target: "lazymc", "Connection limit reached, serving busy to {}", peer);
+                        tokio::spawn(
+                            session.run(
+                                status::serve_busy(client, inbound, config.clone())
+                                    .map(move |_| drop(connection)),
+                            ),
+                        );
+                    }
+                    None => {
+                        debug!(target: "lazymc", "Connection limit reached, dropping {}", peer);
+                    }
+                }
+                return;
+            }
+        };
 
         if !server_state.online() {
             // When server is not online, spawn a status server
             let transfer = status::serve(client, inbound, config.clone(), server_state.clone())
-                .map(|r| {
+                .map(move |r| {
+                    drop(connection);
                     if let Err(err) = r {
                         warn!(target: "lazymc", "Failed to serve status: {:?}", err);
                     }
                 });
 
-            tokio::spawn(transfer);
+            tokio::spawn(session.run(transfer));
+        } else if config.status.online.intercept || config.ingame.enabled {
+            // When server is online, proxy all and rewrite status or sniff in-game commands
+            let transfer = intercept::serve(client, inbound, config.clone(), server_state.clone())
+                .map(move |r| {
+                    drop(connection);
+                    if let Err(err) = r {
+                        warn!(target: "lazymc", "Failed to proxy: {:?}", err);
+                    }
+                });
+
+            tokio::spawn(session.run(transfer));
         } else {
-            // When server is online, proxy all
-            let transfer = proxy::proxy(inbound, config.server.address).map(|r| {
-                if let Err(err) = r {
-                    warn!(target: "lazymc", "Failed to proxy: {}", err);
+            // When server is online, proxy all, serve status if it can't be reached after all
+            let config = config.clone();
+            let server = server_state.clone();
+            let transfer = async move {
+                match proxy::connect_to_server(&config, &client).await {
+                    Ok(outbound) => {
+                        let proxied = server.track_proxied();
+                        proxy::proxy_metered(inbound, outbound, &[], proxied.traffic(), &config.limits)
+                            .await
+                            .map_err(|err| {
+                                warn!(target: "lazymc", "Failed to proxy: {}", err);
+                            })
+                    }
+                    Err(err) => {
+                        debug!(target: "lazymc", "Failed to connect to online server, serving status: {}", err);
+                        status::serve(client, inbound, config, server).await
+                    }
                 }
-            });
+            }
+            .map(move |_| drop(connection));
 
-            tokio::spawn(transfer);
+            tokio::spawn(session.run(transfer));
         }
     }
+}
 
-    Ok(())
+/// Open client connection, counted towards the connection limit until dropped.
+struct Connection(Arc<AtomicU32>);
+
+impl Connection {
+    /// Count a new connection, returns `None` if the limit is reached.
+    ///
+    /// There is no limit if `max` is 0.
+    fn acquire(count: &Arc<AtomicU32>, max: u32) -> Option<Self> {
+        count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                (max == 0 || count < max).then(|| count + 1)
+            })
+            .ok()
+            .map(|_| Self(count.clone()))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
@@ -1,21 +1,59 @@
 use std::sync::Arc;
 
 use crate::config::Config;
+use crate::persist;
 use crate::server::ServerState;
+use crate::session::Sessions;
 
 /// Signal handler task.
-pub async fn service(config: Arc<Config>, server_state: Arc<ServerState>) {
-    loop {
-        // Wait for SIGTERM/SIGINT signal
-        tokio::signal::ctrl_c().await.unwrap();
+///
+/// On the first SIGTERM/SIGINT signal, stop accepting connections and wait for open ones to close
+/// before quitting. On a second signal, quit right away.
+pub async fn service(config: Arc<Config>, server_state: Arc<ServerState>, sessions: Arc<Sessions>) {
+    shutdown_signal().await;
+    persist::save(&config, &server_state.persisted());
 
-        // Attemp to kill server
-        let killed = !server_state.kill_server(&config).await;
+    // Stop accepting connections, wait for open ones to close
+    sessions.drain();
+    let timeout = config.advanced.drain_timeout();
+    if !timeout.is_zero() {
+        info!(target: "lazymc", "Shutting down, waiting up to {}s for open connections to close, signal again to quit right away", timeout.as_secs());
+    }
+    let forced = tokio::select! {
+        open = sessions.wait_closed(timeout) => {
+            if open > 0 {
+                warn!(target: "lazymc", "Drain timeout reached, dropping {} open connection(s)", open);
+            }
+            false
+        }
+        _ = shutdown_signal() => {
+            warn!(target: "lazymc", "Received signal again, quitting right away");
+            true
+        }
+    };
+
+    // Stop server unless handing it over to a new instance, then quit this process
+    if config.server.keep_running_on_quit {
+        info!(target: "lazymc", "Keeping server running for a new instance to adopt");
+    } else {
+        server_state.kill_server(&config).await;
+    }
+    std::process::exit(if forced { 1 } else { 0 })
+}
 
-        // If we don't kill the server, quit this process
-        if !killed {
-            // TODO: gracefully kill itself instead
-            std::process::exit(1)
+/// Wait for a SIGTERM/SIGINT signal.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
         }
     }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.unwrap();
 }
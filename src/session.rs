@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+#[cfg(unix)]
+use std::os::unix::io::{AsFd, AsRawFd, OwnedFd};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::net::TcpStream;
+use tokio::sync::{watch, Notify};
+use tokio::time;
+
+use crate::config::Config;
+use crate::os;
+
+/// Interval to check client sessions for being idle.
+const REAP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval to check whether all client sessions are closed while draining.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Registry of open client sessions, to limit connections per IP, close idle connections and drain
+/// them on shutdown.
+#[derive(Debug)]
+pub struct Sessions {
+    /// Open sessions by ID.
+    sessions: Mutex<HashMap<u64, Entry>>,
+
+    /// Number of open sessions by client IP.
+    per_ip: Mutex<HashMap<IpAddr, u32>>,
+
+    /// Next session ID.
+    next_id: AtomicU64,
+
+    /// Whether we're draining, no new sessions should be accepted.
+    draining: (watch::Sender<bool>, watch::Receiver<bool>),
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self {
+            sessions: Default::default(),
+            per_ip: Default::default(),
+            next_id: Default::default(),
+            draining: watch::channel(false),
+        }
+    }
+}
+
+/// Registered client session.
+#[derive(Debug)]
+struct Entry {
+    /// Client address.
+    peer: SocketAddr,
+
+    /// Duplicate of the client socket, so its file descriptor can't be reused while registered.
+    #[cfg(unix)]
+    socket: Option<OwnedFd>,
+
+    /// Whether the client is waiting on us.
+    waiting: Arc<Waiting>,
+
+    /// Notified to close the session.
+    close: Arc<Notify>,
+}
+
+impl Entry {
+    /// Time the client has been idle, since data was last received from it.
+    ///
+    /// Returns `None` if the client is waiting on us, if unknown, or if not supported on this
+    /// platform.
+    #[allow(unreachable_code)]
+    fn idle(&self) -> Option<Duration> {
+        #[cfg(unix)]
+        return self
+            .waiting
+            .idle(os::tcp_idle(self.socket.as_ref()?.as_raw_fd())?);
+
+        None
+    }
+}
+
+/// Tracks whether a client is waiting on us, such as while held, it may be silent meanwhile.
+#[derive(Debug, Default)]
+pub struct Waiting(Mutex<WaitingState>);
+
+/// State of a client waiting on us.
+#[derive(Debug, Default)]
+#[cfg_attr(not(unix), allow(dead_code))]
+struct WaitingState {
+    /// Whether the client is waiting.
+    waiting: bool,
+
+    /// When the client last stopped waiting.
+    ended: Option<Instant>,
+}
+
+impl Waiting {
+    /// Mark the client as waiting, until the returned guard is dropped.
+    pub fn start(&self) -> WaitingGuard<'_> {
+        self.0.lock().unwrap().waiting = true;
+        WaitingGuard(self)
+    }
+
+    /// Time the client has been idle, given the time it has been silent.
+    ///
+    /// Not counting time spent waiting, returns `None` while waiting.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    fn idle(&self, silent: Duration) -> Option<Duration> {
+        let state = self.0.lock().unwrap();
+        match state.ended {
+            _ if state.waiting => None,
+            Some(ended) => Some(silent.min(ended.elapsed())),
+            None => Some(silent),
+        }
+    }
+}
+
+/// Marks a client as waiting until dropped.
+pub struct WaitingGuard<'a>(&'a Waiting);
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0 .0.lock().unwrap();
+        state.waiting = false;
+        state.ended = Some(Instant::now());
+    }
+}
+
+impl Sessions {
+    /// Register a session for a new client connection.
+    ///
+    /// Returns `None` if the client IP already has `max_per_ip` open sessions, there is no limit if
+    /// 0. The session is unregistered when dropped. Clients aren't idle while `waiting`.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    pub fn open(
+        self: &Arc<Self>,
+        inbound: &TcpStream,
+        peer: SocketAddr,
+        waiting: Arc<Waiting>,
+        max_per_ip: u32,
+    ) -> Option<Session> {
+        let ip = peer.ip().to_canonical();
+        {
+            let mut per_ip = self.per_ip.lock().unwrap();
+            let count = per_ip.entry(ip).or_insert(0);
+            if max_per_ip > 0 && *count >= max_per_ip {
+                return None;
+            }
+            *count += 1;
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let close = Arc::new(Notify::new());
+        self.sessions.lock().unwrap().insert(
+            id,
+            Entry {
+                peer,
+                #[cfg(unix)]
+                socket: inbound.as_fd().try_clone_to_owned().ok(),
+                waiting,
+                close: close.clone(),
+            },
+        );
+        Some(Session {
+            id,
+            ip,
+            sessions: self.clone(),
+            close,
+        })
+    }
+
+    /// Start draining, to stop accepting new sessions while open ones finish.
+    pub fn drain(&self) {
+        let _ = self.draining.0.send(true);
+    }
+
+    /// Receiver for whether we're draining.
+    pub fn draining(&self) -> watch::Receiver<bool> {
+        self.draining.1.clone()
+    }
+
+    /// Wait until all sessions are closed, or until the timeout elapses.
+    ///
+    /// Returns the number of sessions still open.
+    pub async fn wait_closed(&self, timeout: Duration) -> usize {
+        let closed = async {
+            while !self.sessions.lock().unwrap().is_empty() {
+                time::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+        };
+        let _ = time::timeout(timeout, closed).await;
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Close sessions of clients that have been silent for at least the given time.
+    fn reap(&self, timeout: Duration) {
+        self.sessions.lock().unwrap().retain(|_, entry| {
+            let idle = match entry.idle() {
+                Some(idle) if idle >= timeout => idle,
+                _ => return true,
+            };
+            debug!(target: "lazymc", "Closing connection from {}, idle for {}s", entry.peer, idle.as_secs());
+            entry.close.notify_one();
+            false
+        });
+    }
+}
+
+/// Open client session, registered until dropped.
+pub struct Session {
+    id: u64,
+    ip: IpAddr,
+    sessions: Arc<Sessions>,
+    close: Arc<Notify>,
+}
+
+impl Session {
+    /// Run the client connection, until it completes or the session is closed.
+    ///
+    /// Closing drops the connection future, which closes the client socket it owns.
+    pub async fn run(self, connection: impl Future<Output = ()>) {
+        tokio::select! {
+            _ = connection => {}
+            _ = self.close.notified() => {}
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.sessions.sessions.lock().unwrap().remove(&self.id);
+
+        let mut per_ip = self.sessions.per_ip.lock().unwrap();
+        if let Some(count) = per_ip.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                per_ip.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Periodically close sessions of clients that have been silent for too long.
+pub async fn reap_idle(config: Arc<Config>, sessions: Arc<Sessions>) {
+    let timeout = match config.limits.timeout_idle() {
+        Some(timeout) => timeout,
+        None => return,
+    };
+
+    let mut interval = time::interval(REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+        sessions.reap(timeout);
+    }
+}
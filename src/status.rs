@@ -1,70 +1,143 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use bytes::BytesMut;
-use minecraft_protocol::data::chat::{Message, Payload};
+use bytes::{Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
 use minecraft_protocol::data::server_status::*;
 use minecraft_protocol::decoder::Decoder;
-use minecraft_protocol::encoder::Encoder;
 use minecraft_protocol::version::v1_14_4::handshake::Handshake;
-use minecraft_protocol::version::v1_14_4::login::LoginDisconnect;
-use minecraft_protocol::version::v1_14_4::status::StatusResponse;
+use serde_json::Value;
 use tokio::io;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio_util::codec::{Framed, FramedParts};
 
+use crate::auth;
+use crate::chat;
 use crate::config::*;
+use crate::favicon;
+use crate::hooks::{self, Event};
+use crate::join::{self, kick};
+use crate::messages;
 use crate::proto::{
-    self, Client, ClientState, RawPacket, PROTO_DEFAULT_PROTOCOL, PROTO_DEFAULT_VERSION,
+    self, Client, ClientState, Frame, MinecraftCodec, PROTO_DEFAULT_PROTOCOL, PROTO_DEFAULT_VERSION,
 };
+use crate::protocol::Protocol;
 use crate::server::{self, ServerState};
+use crate::util::rate::KeyedRateLimiter;
+use crate::wake;
+
+/// Maximum number of cached status responses.
+const MAX_CACHED_RESPONSES: usize = 64;
+
+/// UUID for custom lines in the player sample.
+const SAMPLE_LINE_UUID: &str = "00000000-0000-0000-0000-000000000000";
+
+/// Loaded JSON status file to serve when sleeping.
+static JSON_SLEEPING: Mutex<Option<String>> = Mutex::new(None);
+
+/// Time in seconds a client only served with a message is served for.
+const MESSAGE_TIMEOUT: u64 = 5;
+
+/// Legacy kick packet ID, used to respond to legacy server list pings.
+const LEGACY_KICK_PACKET_ID: u8 = 0xFF;
+
+/// Protocol version reported to legacy clients, never compatible so the version name is shown.
+const LEGACY_PROTOCOL: u32 = 127;
 
 /// Proxy the given inbound stream to a target address.
 // TODO: do not drop error here, return Box<dyn Error>
 pub async fn serve(
     client: Client,
-    mut inbound: TcpStream,
+    inbound: TcpStream,
     config: Arc<Config>,
     server: Arc<ServerState>,
 ) -> Result<(), ()> {
-    let (mut reader, mut writer) = inbound.split();
+    let codec = MinecraftCodec::new(&client).with_legacy_ping();
+    let mut framed = Framed::with_capacity(inbound, codec, proto::READ_BUF_SIZE);
 
-    // Incoming buffer
-    let mut buf = BytesMut::new();
+    // Packet holding queue
+    let mut inbound_history = BytesMut::new();
 
     loop {
         // Read packet from stream
-        let (packet, raw) = match proto::read_packet(&mut buf, &mut reader).await {
-            Ok(Some(packet)) => packet,
+        let timeout = config.limits.timeout(client.state());
+        let (packet, raw) = match tokio::time::timeout(timeout, framed.next()).await {
+            Ok(Some(Ok(Frame::Packet(packet, raw)))) => (packet, raw),
+            Ok(Some(Ok(Frame::LegacyPing))) => {
+                debug!(target: "lazymc", "Responding to legacy server list ping");
+                let response = legacy_status_response(&config, &server, &client);
+                let _ = framed.get_mut().write_all(&response).await;
+                break;
+            }
             Ok(None) => break,
-            Err(_) => {
+            Ok(Some(Err(err))) if err.kind() == io::ErrorKind::ConnectionReset => break,
+            Ok(Some(Err(_))) => {
                 error!(target: "lazymc", "Closing connection, error occurred");
                 break;
             }
+            Err(_) => {
+                debug!(target: "lazymc", "Closing connection, client timed out in {:?} state", client.state());
+                break;
+            }
         };
 
         // Hijack login start
         if client.state() == ClientState::Login && packet.id == proto::LOGIN_PACKET_ID_LOGIN_START {
-            let packet = LoginDisconnect {
-                reason: Message::new(Payload::text(&config.messages.login_starting)),
-            };
-
-            let mut data = Vec::new();
-            packet.encode(&mut data).map_err(|_| ())?;
-
-            let response = RawPacket::new(0, data).encode()?;
+            // Remember username for join methods
+            match Protocol::of(&client).decode_login_start(&packet.data) {
+                Ok(login_start) => client.set_username(login_start.name),
+                Err(_) => break,
+            }
 
-            writer.write_all(&response).await.map_err(|_| ())?;
+            // Check whether player may wake server, authenticate first if required
+            if !server.starting() {
+                hooks::fire(
+                    &config,
+                    Event::Join {
+                        username: client.username(),
+                        ip: client.peer.map(|peer| peer.ip()),
+                    },
+                );
+                if config.wake.online_mode {
+                    let inbound = framed.into_inner();
+                    return auth::wake(client, config, server, inbound).await;
+                }
+                if let Err(message) = wake::check(&config, &server, &client, None) {
+                    kick::kick(&client, &message, framed.get_mut()).await?;
+                    break;
+                }
+            }
 
             // Start server if not starting yet
-            server::start_server(config, server);
+            server::start_server(config.clone(), server.clone());
 
-            break;
+            // Remember login start and queued packets for when client is forwarded
+            let FramedParts {
+                io: inbound,
+                read_buf,
+                ..
+            } = framed.into_parts();
+            inbound_history.extend_from_slice(&raw);
+            inbound_history.extend_from_slice(&read_buf);
+
+            // Occupy client with join methods while server starts
+            return join::occupy(client, config, server, inbound, inbound_history).await;
         }
 
         // Hijack handshake
         if client.state() == ClientState::Handshake && packet.id == proto::STATUS_PACKET_ID_STATUS {
             match Handshake::decode(&mut packet.data.as_slice()) {
                 Ok(handshake) => {
+                    // Remember handshake for when client is forwarded
+                    inbound_history.extend_from_slice(&raw);
+                    client.set_protocol(handshake.protocol_version);
+                    client.set_hostname(&handshake.server_addr);
+
                     // TODO: do not panic here
                     client.set_state(
                         ClientState::from_id(handshake.next_state)
@@ -73,52 +146,46 @@ pub async fn serve(
                 }
                 Err(_) => break,
             }
+
+            // Reject clients connecting with unexpected hostname, such as scanners using the IP
+            if !config.public.hostname_allowed(&client) {
+                let hostname = client.hostname().unwrap_or_default();
+                debug!(target: "lazymc", "Rejecting client with unexpected hostname '{}'", hostname);
+                if let Some(message) = &config.public.message_wrong_hostname {
+                    serve_message(&client, &mut framed, message).await;
+                }
+                break;
+            }
         }
 
         // Hijack server status packet
         if client.state() == ClientState::Status && packet.id == proto::STATUS_PACKET_ID_STATUS {
-            // Select version and player max from last known server status
-            let (version, max) = match server.clone_status() {
-                Some(status) => (status.version, status.players.max),
-                None => (
-                    ServerVersion {
-                        name: String::from(PROTO_DEFAULT_VERSION),
-                        protocol: PROTO_DEFAULT_PROTOCOL,
-                    },
-                    0,
-                ),
-            };
-
-            // Select description
-            let description = if server.starting() {
-                &config.messages.motd_starting
-            } else {
-                &config.messages.motd_sleeping
-            };
-
-            // Build status resposne
-            let server_status = ServerStatus {
-                version,
-                description: Message::new(Payload::text(description)),
-                players: OnlinePlayers {
-                    online: 0,
-                    max,
-                    sample: vec![],
-                },
-            };
-            let packet = StatusResponse { server_status };
+            if config.analytics.enabled {
+                server.pings.record(&config, &client);
+            }
 
-            let mut data = Vec::new();
-            packet.encode(&mut data).map_err(|_| ())?;
+            // Drop clients flooding status requests
+            let cache = &server.status_cache;
+            if !cache.take(&config, &client) {
+                debug!(target: "lazymc", "Status rate limit reached, closing connection");
+                break;
+            }
 
-            let response = RawPacket::new(0, data).encode()?;
-            writer.write_all(&response).await.map_err(|_| ())?;
+            let sleeping = !server.starting() && !server.crashed();
+            let response = cache.get_or_insert(&config, &client, sleeping, || {
+                status_response(&config, &server, &client)
+            })?;
+            framed
+                .get_mut()
+                .write_all(&response)
+                .await
+                .map_err(|_| ())?;
             continue;
         }
 
         // Hijack ping packet
         if client.state() == ClientState::Status && packet.id == proto::STATUS_PACKET_ID_PING {
-            writer.write_all(&raw).await.map_err(|_| ())?;
+            framed.get_mut().write_all(&raw).await.map_err(|_| ())?;
             continue;
         }
 
@@ -129,7 +196,7 @@ pub async fn serve(
     }
 
     // Gracefully close connection
-    match writer.shutdown().await {
+    match framed.get_mut().shutdown().await {
         Ok(_) => {}
         Err(err) if err.kind() == io::ErrorKind::NotConnected => {}
         Err(_) => return Err(()),
@@ -137,3 +204,362 @@ pub async fn serve(
 
     Ok(())
 }
+
+/// Status response cache key, client protocol and message host.
+type CacheKey = (Option<i32>, Option<String>);
+
+/// Cache of encoded status responses, and status rate limiter.
+#[derive(Debug, Default)]
+pub struct StatusCache {
+    /// Encoded sleeping responses by client protocol and host, with time they were encoded at.
+    responses: Mutex<HashMap<CacheKey, (Instant, Bytes)>>,
+
+    /// Status request rate limiter per IP, created on first use.
+    limiter: Mutex<Option<KeyedRateLimiter<IpAddr>>>,
+}
+
+impl StatusCache {
+    /// Take a status request from the rate limit for the client, returns `false` if limited.
+    pub fn take(&self, config: &Config, client: &Client) -> bool {
+        let ip = match client.peer {
+            Some(peer) if config.status.rate_limit > 0 => peer.ip(),
+            _ => return true,
+        };
+
+        self.limiter
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                KeyedRateLimiter::new(config.status.rate_limit, Duration::from_secs(60))
+            })
+            .try_take(ip)
+    }
+
+    /// Get cached status response for client, or encode and cache a new one.
+    ///
+    /// Only responses while the server is sleeping are cached. Other states show live progress,
+    /// such as the start ETA, and are encoded for each request.
+    pub fn get_or_insert<F>(
+        &self,
+        config: &Config,
+        client: &Client,
+        sleeping: bool,
+        encode: F,
+    ) -> Result<Bytes, ()>
+    where
+        F: FnOnce() -> Result<Vec<u8>, ()>,
+    {
+        if !sleeping {
+            return encode().map(Bytes::from);
+        }
+
+        let ttl = Duration::from_secs(config.status.cache_ttl as u64);
+        let host = config.messages.host_key(client).map(|host| host.into());
+        let key = (client.protocol(), host);
+
+        if let Some((time, response)) = self.responses.lock().unwrap().get(&key) {
+            if time.elapsed() < ttl {
+                return Ok(response.clone());
+            }
+        }
+
+        let response = Bytes::from(encode()?);
+        if !ttl.is_zero() {
+            let mut responses = self.responses.lock().unwrap();
+            if responses.len() >= MAX_CACHED_RESPONSES {
+                responses.clear();
+            }
+            responses.insert(key, (Instant::now(), response.clone()));
+        }
+        Ok(response)
+    }
+
+    /// Clear cached status responses, such as after reloading favicons.
+    pub fn clear(&self) {
+        self.responses.lock().unwrap().clear();
+    }
+}
+
+/// Load configured JSON status file.
+///
+/// Called on start, and again to reload it. Prints an error if the file fails to load.
+pub fn load(config: &Config) {
+    let json = config.status.json_sleeping.as_deref().and_then(load_json);
+    *JSON_SLEEPING.lock().unwrap() = json;
+}
+
+/// Load and validate JSON status file.
+fn load_json(path: &Path) -> Option<String> {
+    let result = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| match serde_json::from_str::<Value>(&json) {
+            Ok(_) => Ok(json),
+            Err(err) => Err(err.to_string()),
+        });
+    match result {
+        Ok(json) => Some(json),
+        Err(err) => {
+            error!(target: "lazymc", "Failed to load JSON status from {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Build encoded status response for the client.
+fn status_response(config: &Config, server: &ServerState, client: &Client) -> Result<Vec<u8>, ()> {
+    // Serve JSON status file as-is when sleeping
+    let json = JSON_SLEEPING.lock().unwrap().clone();
+    if let Some(json) = json {
+        if !server.starting() {
+            return json_response(config, server, client, &json);
+        }
+    }
+
+    let (server_status, favicon) = server_status(config, server, client);
+    Protocol::of(client)
+        .encode_status_response(server_status, favicon.as_deref())?
+        .encode(client)
+}
+
+/// Build legacy status response for clients before Minecraft 1.7.
+///
+/// This is a kick packet with the status fields in a UTF-16 string.
+fn legacy_status_response(config: &Config, server: &ServerState, client: &Client) -> Vec<u8> {
+    let (status, _) = server_status(config, server, client);
+    let fields = format!(
+        "§1\0{}\0{}\0{}\0{}\0{}",
+        LEGACY_PROTOCOL,
+        status.version.name,
+        motd(config, server, client).replace('\n', " "),
+        status.players.online,
+        status.players.max,
+    );
+
+    let chars: Vec<u16> = fields.encode_utf16().collect();
+    let mut response = vec![LEGACY_KICK_PACKET_ID];
+    response.extend_from_slice(&(chars.len() as u16).to_be_bytes());
+    response.extend(chars.iter().flat_map(|c| c.to_be_bytes()));
+    response
+}
+
+/// Build server status for the client, and select the favicon to show.
+fn server_status(
+    config: &Config,
+    server: &ServerState,
+    client: &Client,
+) -> (ServerStatus, Option<String>) {
+    // Select version and players from last known server status
+    let (mut version, online, max) = match server.clone_status() {
+        Some(status) => (status.version, status.players.online, status.players.max),
+        None => (
+            ServerVersion {
+                name: String::from(PROTO_DEFAULT_VERSION),
+                protocol: PROTO_DEFAULT_PROTOCOL,
+            },
+            0,
+            config.status.default_max_players.unwrap_or(0),
+        ),
+    };
+
+    // Use custom version name for current state
+    let version_name = if server.starting() {
+        &config.status.version_starting
+    } else {
+        &config.status.version_sleeping
+    };
+    if let Some(name) = version_name {
+        version.name = messages::render(name, config, server);
+    }
+
+    // Echo client protocol so it isn't shown as incompatible
+    if config.status.echo_protocol {
+        if let Some(protocol) = client.protocol() {
+            version.protocol = protocol as u32;
+        }
+    }
+
+    // Select player count
+    let (online, max) = match config.status.players_while_sleeping {
+        PlayerCount::Zero => (0, max),
+        PlayerCount::LastKnown => (online, max),
+        PlayerCount::Custom { online, max } => (online, max),
+    };
+
+    // Select favicon, prefer custom favicon for current state
+    let favicons = favicon::favicons();
+    let favicon = if server.starting() {
+        favicons.starting.clone()
+    } else {
+        favicons.sleeping.clone()
+    }
+    .or_else(|| server.favicon());
+
+    // Build status resposne
+    let server_status = ServerStatus {
+        version,
+        description: chat::message(&motd(config, server, client)),
+        players: OnlinePlayers {
+            online,
+            max,
+            sample: sample(config, server),
+        },
+    };
+    (server_status, favicon)
+}
+
+/// Render MOTD for the current server state.
+fn motd(config: &Config, server: &ServerState, client: &Client) -> String {
+    let motd = if server.crashed() {
+        &config.messages.motd_crashed
+    } else if server.starting() {
+        config.messages.motd_starting(client)
+    } else {
+        config.messages.motd_sleeping(client)
+    };
+    messages::render(motd, config, server)
+}
+
+/// Build encoded status response from JSON status file, replacing placeholders if enabled.
+fn json_response(
+    config: &Config,
+    server: &ServerState,
+    client: &Client,
+    json: &str,
+) -> Result<Vec<u8>, ()> {
+    if !config.status.json_placeholders {
+        return Protocol::of(client)
+            .encode_status_json(json)?
+            .encode(client);
+    }
+
+    let mut status: Value = serde_json::from_str(json).map_err(|_| ())?;
+    render_json(&mut status, config, server);
+    let json = serde_json::to_string(&status).map_err(|_| ())?;
+    Protocol::of(client)
+        .encode_status_json(&json)?
+        .encode(client)
+}
+
+/// Replace placeholders in all strings of a JSON value.
+fn render_json(value: &mut Value, config: &Config, server: &ServerState) {
+    match value {
+        Value::String(text) => *text = messages::render(text, config, server),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| render_json(value, config, server)),
+        Value::Object(values) => values
+            .values_mut()
+            .for_each(|value| render_json(value, config, server)),
+        _ => {}
+    }
+}
+
+/// Build player sample to show in the player list hover.
+///
+/// Includes the last online players if enabled, and custom lines.
+fn sample(config: &Config, server: &ServerState) -> Vec<OnlinePlayer> {
+    let mut sample = if config.status.sample_last_players {
+        server.last_players()
+    } else {
+        vec![]
+    };
+    sample.extend(
+        config
+            .status
+            .sample
+            .iter()
+            .filter_map(|line| sample_line(&messages::render(line, config, server))),
+    );
+    sample
+}
+
+/// Build player sample entry to show a line of text.
+fn sample_line(text: &str) -> Option<OnlinePlayer> {
+    serde_json::from_value(serde_json::json!({
+        "name": text,
+        "id": SAMPLE_LINE_UUID,
+    }))
+    .ok()
+}
+
+/// Serve a client connecting beyond the connection limit.
+///
+/// Responds to status requests and kicks on login with the busy message.
+pub async fn serve_busy(client: Client, inbound: TcpStream, config: Arc<Config>) {
+    let mut framed =
+        Framed::with_capacity(inbound, MinecraftCodec::new(&client), proto::READ_BUF_SIZE);
+
+    serve_message(&client, &mut framed, &config.limits.message_busy).await;
+
+    let _ = framed.get_mut().shutdown().await;
+}
+
+/// Serve a client with just a message.
+///
+/// Responds to status requests with the message as MOTD and kicks on login with it. Gives up if
+/// the client doesn't finish within a few seconds.
+async fn serve_message<S>(
+    client: &Client,
+    framed: &mut Framed<S, MinecraftCodec<'_>>,
+    message: &str,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let serve = async {
+        loop {
+            let (packet, raw) = match framed.next().await {
+                Some(Ok(Frame::Packet(packet, raw))) => (packet, raw),
+                _ => return,
+            };
+
+            match (client.state(), packet.id) {
+                (ClientState::Handshake, proto::HANDSHAKE_PACKET_ID_HANDSHAKE) => {
+                    let handshake = match Handshake::decode(&mut packet.data.as_slice()) {
+                        Ok(handshake) => handshake,
+                        Err(_) => return,
+                    };
+                    client.set_protocol(handshake.protocol_version);
+                    match ClientState::from_id(handshake.next_state) {
+                        Some(state) => client.set_state(state),
+                        None => return,
+                    }
+                }
+                (ClientState::Status, proto::STATUS_PACKET_ID_STATUS) => {
+                    let server_status = ServerStatus {
+                        version: ServerVersion {
+                            name: String::from(PROTO_DEFAULT_VERSION),
+                            protocol: PROTO_DEFAULT_PROTOCOL,
+                        },
+                        description: chat::message(message),
+                        players: OnlinePlayers {
+                            online: 0,
+                            max: 0,
+                            sample: vec![],
+                        },
+                    };
+                    let response =
+                        match Protocol::of(client).encode_status_response(server_status, None) {
+                            Ok(response) => response,
+                            Err(_) => return,
+                        };
+                    if framed.send(response).await.is_err() {
+                        return;
+                    }
+                }
+                (ClientState::Status, proto::STATUS_PACKET_ID_PING) => {
+                    let _ = framed.get_mut().write_all(&raw).await;
+                    return;
+                }
+                (ClientState::Login, proto::LOGIN_PACKET_ID_LOGIN_START) => {
+                    if let Ok(login_start) = Protocol::of(client).decode_login_start(&packet.data) {
+                        client.set_username(login_start.name);
+                    }
+                    let _ = kick::kick(client, message, framed.get_mut()).await;
+                    return;
+                }
+                _ => {}
+            }
+        }
+    };
+    let _ = tokio::time::timeout(Duration::from_secs(MESSAGE_TIMEOUT), serve).await;
+}
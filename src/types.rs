@@ -27,3 +27,23 @@ pub fn encode_var_int(i: i32) -> Result<Vec<u8>, ()> {
     minecraft_protocol::encoder::var_int::encode(&i, &mut buf).map_err(|_| ())?;
     Ok(buf)
 }
+
+/// Try to read var-int prefixed string from data buffer.
+///
+/// Returns number of bytes consumed and the string.
+pub fn read_string(buf: &[u8]) -> Result<(usize, String), ()> {
+    let (read, len) = read_var_int(buf)?;
+    if len < 0 || buf.len() < read + len as usize {
+        return Err(());
+    }
+
+    let string = std::str::from_utf8(&buf[read..read + len as usize]).map_err(|_| ())?;
+    Ok((read + len as usize, string.into()))
+}
+
+/// Encode string into var-int prefixed string.
+pub fn encode_string(s: &str) -> Result<Vec<u8>, ()> {
+    let mut buf = encode_var_int(s.len() as i32)?;
+    buf.extend_from_slice(s.as_bytes());
+    Ok(buf)
+}
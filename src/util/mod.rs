@@ -1,5 +1,7 @@
 pub mod cli;
 pub mod error;
+pub mod net;
+pub mod rate;
 pub mod style;
 
 use std::env;
@@ -0,0 +1,121 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use ipnet::IpNet;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+use crate::config::Socket;
+use crate::os;
+
+/// Check whether the IP is in any of the given ranges.
+///
+/// IPv4-mapped IPv6 addresses are matched as IPv4.
+pub fn in_ranges(ranges: &[IpNet], ip: IpAddr) -> bool {
+    let ip = ip.to_canonical();
+    ranges.iter().any(|range| range.contains(&ip))
+}
+
+/// Check whether the IP is allowed by the given allow and deny lists.
+///
+/// An empty allow list allows everything. The deny list takes precedence.
+pub fn allowed(allow: &[IpNet], deny: &[IpNet], ip: IpAddr) -> bool {
+    (allow.is_empty() || in_ranges(allow, ip)) && !in_ranges(deny, ip)
+}
+
+/// Bind TCP listener to the given address, with the configured socket options.
+///
+/// Only accepts IPv6 connections on IPv6 addresses if `only_v6` is set, so an IPv4 listener can
+/// use the same port.
+pub fn bind(config: &Socket, addr: SocketAddr, only_v6: bool) -> io::Result<TcpListener> {
+    let socket = new_socket(config, addr)?;
+
+    // Allow binding again right after restarting, as `TcpListener::bind` does
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+
+    // Allow a new lazymc instance to listen as well, to take over without downtime
+    #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
+    if config.reuse_port {
+        socket.set_reuseport(true)?;
+    }
+
+    if addr.is_ipv6() && only_v6 {
+        SockRef::from(&socket).set_only_v6(true)?;
+    }
+
+    socket.bind(addr)?;
+    socket.listen(config.backlog)
+}
+
+/// Take TCP listeners passed by a service manager through socket activation, such as systemd.
+///
+/// Returns an empty list if none were passed to this process.
+pub fn inherited() -> io::Result<Vec<TcpListener>> {
+    os::listen_fds()?
+        .into_iter()
+        .map(|listener| {
+            listener.set_nonblocking(true)?;
+            TcpListener::from_std(listener)
+        })
+        .collect()
+}
+
+/// Connect TCP stream to the given address, with the configured socket options.
+///
+/// Connects from the given local IP and through the given network interface, if set. Binding to
+/// an interface is only supported on Linux.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+pub async fn connect(
+    config: &Socket,
+    addr: SocketAddr,
+    bind: Option<IpAddr>,
+    interface: Option<&str>,
+) -> io::Result<TcpStream> {
+    let socket = new_socket(config, addr)?;
+    if let Some(ip) = bind {
+        socket.bind(SocketAddr::new(ip, 0))?;
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(interface) = interface {
+        SockRef::from(&socket).bind_device(Some(interface.as_bytes()))?;
+    }
+
+    let stream = socket.connect(addr).await?;
+    configure(config, &stream)?;
+    Ok(stream)
+}
+
+/// Apply the configured socket options to a connected TCP stream.
+///
+/// Buffer sizes are not applied, as they must be set before connecting.
+pub fn configure(config: &Socket, stream: &TcpStream) -> io::Result<()> {
+    stream.set_nodelay(config.nodelay)?;
+
+    if let Some(time) = config.keepalive() {
+        let keepalive = TcpKeepalive::new().with_time(time);
+        #[cfg(any(target_os = "linux", target_vendor = "apple", windows))]
+        let keepalive = keepalive.with_interval(time);
+        SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+    }
+
+    Ok(())
+}
+
+/// Create TCP socket for the given address, with the configured buffer sizes.
+fn new_socket(config: &Socket, addr: SocketAddr) -> io::Result<TcpSocket> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+
+    let sock_ref = SockRef::from(&socket);
+    if let Some(size) = config.send_buffer_size {
+        sock_ref.set_send_buffer_size(size as usize)?;
+    }
+    if let Some(size) = config.recv_buffer_size {
+        sock_ref.set_recv_buffer_size(size as usize)?;
+    }
+
+    Ok(socket)
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Number of keys after which a keyed rate limiter forgets idle keys.
+const MAX_KEYS: usize = 1024;
+
+/// Token bucket rate limiter.
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Maximum number of tokens.
+    capacity: f64,
+
+    /// Tokens refilled per second.
+    refill_rate: f64,
+
+    /// Currently available tokens.
+    tokens: f64,
+
+    /// Time tokens were last refilled at.
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    /// Construct full bucket with given capacity, fully refilling in the given period.
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        let capacity = capacity as f64;
+        Self {
+            capacity,
+            refill_rate: capacity / period.as_secs_f64().max(f64::EPSILON),
+            tokens: capacity,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Try to take a token, returns `false` if none is available.
+    pub fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.refilled = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// Rate limiter with a token bucket per key, such as per IP.
+#[derive(Debug)]
+pub struct KeyedRateLimiter<K> {
+    /// Token buckets per key.
+    buckets: HashMap<K, TokenBucket>,
+
+    /// Bucket capacity.
+    capacity: u32,
+
+    /// Bucket refill period.
+    period: Duration,
+}
+
+impl<K: Eq + Hash> KeyedRateLimiter<K> {
+    /// Construct rate limiter, allowing `capacity` takes per key in the given period.
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        Self {
+            buckets: HashMap::new(),
+            capacity,
+            period,
+        }
+    }
+
+    /// Try to take a token for the given key, returns `false` if none is available.
+    pub fn try_take(&mut self, key: K) -> bool {
+        // Forget keys that have fully refilled to bound memory usage
+        if self.buckets.len() >= MAX_KEYS {
+            let period = self.period;
+            self.buckets
+                .retain(|_, bucket| bucket.refilled.elapsed() < period);
+        }
+
+        let (capacity, period) = (self.capacity, self.period);
+        self.buckets
+            .entry(key)
+            .or_insert_with(|| TokenBucket::new(capacity, period))
+            .try_take()
+    }
+}
@@ -0,0 +1,96 @@
+use crate::config::Config;
+use crate::mc::ban::Bans;
+use crate::mc::session::Profile;
+use crate::mc::whitelist::{Players, OPS_FILE, WHITELIST_FILE};
+use crate::proto::Client;
+use crate::server::{self, ServerState};
+use crate::util::net;
+
+/// Ban reason used if a ban has no reason.
+const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+
+/// Check whether a client may wake the server.
+///
+/// The profile is given if the client is authenticated with the session server. Returns the
+/// message to kick the client with if not allowed. Counts towards the wake rate limit if allowed,
+/// so the server must be started afterwards.
+pub fn check(
+    config: &Config,
+    server: &ServerState,
+    client: &Client,
+    profile: Option<&Profile>,
+) -> Result<(), String> {
+    let username = client.username().unwrap_or_default();
+    let uuid = profile.map(|profile| profile.id.as_str());
+
+    // Client IP must be allowed to wake
+    if let Some(peer) = client.peer {
+        if !net::allowed(&config.wake.allow, &[], peer.ip()) {
+            info!(target: "lazymc", "Client {} may not wake server from its IP", peer.ip());
+            return Err(config.wake.message_address_not_allowed.clone());
+        }
+    }
+
+    // Player and IP must not be banned
+    if config.wake.bans {
+        let bans = Bans::load(config.server.dir());
+        if let Some(ban) = bans.find(&username, uuid, client.peer.map(|peer| peer.ip())) {
+            info!(target: "lazymc", "Player {} is banned, not waking server", username);
+            let reason = ban.reason.as_deref().unwrap_or(DEFAULT_BAN_REASON);
+            return Err(config.wake.message_banned.replace("{reason}", reason));
+        }
+    }
+
+    // Player must be whitelisted or operator
+    if config.wake.whitelist {
+        let players = Players::load(config.server.dir(), &[WHITELIST_FILE, OPS_FILE]);
+        if !players.contains(&username, uuid) {
+            info!(target: "lazymc", "Player {} is not whitelisted, not waking server", username);
+            return Err(config.wake.message_not_whitelisted.clone());
+        }
+    }
+
+    // Server must not have stopped just now
+    if let Some(remaining) = server.wake_cooldown(config) {
+        info!(target: "lazymc", "Server stopped recently, not waking server");
+        let minutes = remaining.as_secs().div_ceil(60);
+        return Err(config
+            .wake
+            .message_cooldown
+            .replace("{minutes}", &minutes.to_string()));
+    }
+
+    // Server must not have failed to start just now
+    if let Some(remaining) = server.start_failed_cooldown(config) {
+        info!(target: "lazymc", "Server failed to start recently, not waking server");
+        let minutes = remaining.as_secs().div_ceil(60);
+        return Err(config
+            .wake
+            .message_start_failed
+            .replace("{minutes}", &minutes.to_string()));
+    }
+
+    // Player must be operator
+    if config.wake.ops_only {
+        let mut operators = Players::load(config.server.dir(), &[OPS_FILE]);
+        operators.extend(&config.wake.operators);
+        if !operators.contains(&username, uuid) {
+            info!(target: "lazymc", "Player {} is not an operator, not waking server", username);
+            return Err(config.wake.message_not_operator.clone());
+        }
+    }
+
+    // Host must have enough memory to fit the server
+    if server::check_memory(config).is_err() {
+        warn!(target: "lazymc", "Host is low on memory, not waking server");
+        return Err(config.wake.message_low_memory.clone());
+    }
+
+    // Server must not wake too often, takes a wake if allowed
+    if !server.take_wake(config) {
+        warn!(target: "lazymc", "Wake rate limit reached, not waking server");
+        return Err(config.wake.message_rate_limited.clone());
+    }
+
+    Ok(())
+}